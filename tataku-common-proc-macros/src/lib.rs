@@ -2,9 +2,16 @@ mod reflect;
 mod packets;
 mod from_string;
 mod serializable;
+mod random;
 use proc_macro::TokenStream;
 
 // automatic read/write macro for the packet list
+// also implements `WireSchema`, using the `///` docs on each variant and field
+// `#[packet(handler = Trait)]` generates a handler trait with a no-op method per variant, and `dispatch` to call it.
+// `handler_context(name: Type, ...)` adds arguments before the fields, and `#[packet(no_handler)]` leaves a variant out
+// `#[packet(directional)]` implements `Directional`, from each variant's `Client_`/`Server_` prefix,
+// `#[packet(direction = client/server/both)]`, or `#[packet(direction_from = field)]` to use a nested packet's direction.
+// it also generates `{Enum}ClientToServer` and `{Enum}ServerToClient`, with only the variants going that way
 #[proc_macro_derive(PacketSerialization, attributes(packet, packet_type))]
 pub fn packet_serialization(input: TokenStream) -> TokenStream {
     // Parse the string representation
@@ -16,12 +23,53 @@ pub fn packet_serialization(input: TokenStream) -> TokenStream {
     }.into()
 }
 
+/// read/write a struct's fields in order, or an enum's variant id and then its fields
+///
+/// - named, tuple and unit structs all work. type parameters need to be `Serializable` (and `WireSchema` for the schema)
+/// - `#[serialize(with = path)]` on a field reads and writes it with `path::read(sr)` and `path::write(&value, sw)`,
+///   and describes it with `path::schema()`
+/// - `#[serialize(name = "x")]` on a field names it in errors and schemas (tuple fields are `field0`, `field1`, ... otherwise)
+///
+/// enums:
+/// - the variant id is the discriminant (explicit or implicit), written as the `#[repr]` type or a u8
+/// - unknown ids are an error, unless a unit variant has `#[serialize(fallback)]`
+/// - fields can use `with`, `name`, `skip` and `default`, but not versions
+///
+/// versioning (structs only):
+/// - `#[serialize(current_version = CONST)]` on the struct writes (and reads) a u16 version first.
+///   if the first field is called `version`, it's set to the version that was read
+///   this also implements `VersionedSerializable`, so older versions can be written too
+/// - `#[serialize(added = 5, removed = 9)]` on a field says which versions it's in (`removed` is exclusive)
+/// - `read_as = f64, convert = fn, convert_back = fn` reads the field as another type for those versions.
+///   without `convert_back` the default `read_as` value is written, and the field is reported as lost
+/// - `lossy = fn` (`fn(&field) -> bool`) reports the field as lost when `convert_back` couldnt keep all of it
+/// - a field can have one `#[serialize(...)]` per range of versions
+/// - `default = expr` is used when the field isnt in the version being read
+/// - `skip` never reads or writes the field
+/// - `removed_field(name = "x", ty = u8, added = 1, removed = 2)` is a field before this one which only exists in old versions
+///
+/// this also implements `WireSchema`, with one entry per field layout
 #[proc_macro_derive(Serializable, attributes(serialize))]
 pub fn serializable(input: TokenStream) -> TokenStream {
     let ast = syn::parse::<syn::DeriveInput>(input).unwrap();
     serializable::derive(ast)
 }
 
+/// generate random values, for roundtrip tests (crate-internal, like `PacketSerialization`)
+///
+/// - enums pick a random variant, and `random_variants` gives one of each
+/// - `#[serialize(skip)]` fields are always the default, since they're never written
+/// - with `#[serialize(current_version = X)]`, a `version` field is always `X`
+/// - `#[random(with = fn)]` generates the field with `fn(&mut RandomSource)`, `#[random(default)]` uses the default
+#[proc_macro_derive(RandomValue, attributes(random))]
+pub fn derive_random(input: TokenStream) -> TokenStream {
+    let ast = syn::parse(input).unwrap();
+    match random::derive(&ast) {
+        Ok(a) => a,
+        Err(e) => e.into_compile_error()
+    }.into()
+}
+
 #[proc_macro_derive(Reflect, attributes(reflect))]
 pub fn derive_reflect(input: TokenStream) -> TokenStream {
     let ast = syn::parse(input).unwrap();
@@ -38,3 +86,18 @@ pub fn derive_from_string(input: TokenStream) -> TokenStream {
         Err(e) => e.into_compile_error()
     }.into()
 }
+
+
+/// the `///` docs on an item, one line per doc line
+pub(crate) fn doc_string(attrs: &[syn::Attribute]) -> String {
+    attrs.iter()
+        .filter(|a| a.path().is_ident("doc"))
+        .filter_map(|a| match &a.meta {
+            syn::Meta::NameValue(syn::MetaNameValue { value: syn::Expr::Lit(syn::ExprLit { lit: syn::Lit::Str(s), .. }), .. }) => Some(s.value().trim().to_owned()),
+            _ => None,
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+        .trim()
+        .to_owned()
+}
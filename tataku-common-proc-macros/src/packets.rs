@@ -2,6 +2,7 @@
 use syn::*;
 use quote::*;
 use syn::spanned::Spanned;
+use syn::parse::Parse;
 use proc_macro2::TokenStream;
 use std::collections::HashMap;
 
@@ -11,6 +12,14 @@ const TYPE_ATTRIBUTE: &str = "packet_type";
 const REPR_ATTRIBUTE: &str = "repr";
 const EXTRA_LOGGING_ATTRIBUTE: &str = "extra_logging";
 const GEN_TO_FROM_ATTRIBUTE: &str = "gen_to_from";
+const FRAMED_ATTRIBUTE: &str = "framed";
+const STRICT_ATTRIBUTE: &str = "strict";
+const HANDLER_ATTRIBUTE: &str = "handler";
+const HANDLER_CONTEXT_ATTRIBUTE: &str = "handler_context";
+const NO_HANDLER_ATTRIBUTE: &str = "no_handler";
+const DIRECTIONAL_ATTRIBUTE: &str = "directional";
+const DIRECTION_ATTRIBUTE: &str = "direction";
+const DIRECTION_FROM_ATTRIBUTE: &str = "direction_from";
 
 const ID_ATTRIBUTE: &str = "id";
 const DEFAULT_VARIANT_ATTRIBUTE: &str = "default";
@@ -28,6 +37,8 @@ pub fn derive(ast: &syn::DeriveInput) -> Result<TokenStream> {
 
     let mut id_map: HashMap<u16, &Ident> = HashMap::new();
     let mut default_variant = DefaultVariant::Default;
+    // name of the variant Default::default() returns, if we can tell
+    let mut default_variant_name = None;
 
     let enum_name = &ast.ident;
     let type_ = packet_attrs.type_;
@@ -37,6 +48,11 @@ pub fn derive(ast: &syn::DeriveInput) -> Result<TokenStream> {
 
     let mut read_fields = Vec::new();
     let mut write_fields = Vec::new();
+    let mut len_fields = Vec::new();
+    let mut variant_schemas = Vec::new();
+    let mut handler_methods = Vec::new();
+    let mut directions = Vec::new();
+    let mut directional_variants = Vec::new();
 
     if let Data::Enum(data) = &ast.data {
         for v in data.variants.iter() {
@@ -46,6 +62,9 @@ pub fn derive(ast: &syn::DeriveInput) -> Result<TokenStream> {
             let variant_attrs = PacketAttrs::parse(&v.attrs)?;
             if variant_attrs.is_default {
                 default_variant = DefaultVariant::Variant(variant_name);
+                default_variant_name = Some(variant_name.to_string());
+            } else if default_variant_name.is_none() && v.attrs.iter().any(|a| a.path().is_ident(DEFAULT_VARIANT_ATTRIBUTE)) {
+                default_variant_name = Some(variant_name.to_string());
             }
 
             // ensure this packet has an id
@@ -69,27 +88,111 @@ pub fn derive(ast: &syn::DeriveInput) -> Result<TokenStream> {
             variants.push(variant_name);
             ids.push(id);
 
+            let field_schemas = v.fields.iter().filter_map(|f| {
+                let ident = f.ident.as_ref()?.to_string();
+                let ty = &f.ty;
+                let docs = crate::doc_string(&f.attrs);
+                Some(quote! {
+                    crate::serialization::FieldSchema { docs: #docs, ..crate::serialization::FieldSchema::new::<#ty>(#ident) }
+                })
+            });
+            let name = variant_name.to_string();
+            let docs = crate::doc_string(&v.attrs);
+            variant_schemas.push(quote! {
+                crate::serialization::VariantSchema {
+                    name: #name,
+                    id: #id as u64,
+                    fields: vec![ #(#field_schemas),* ],
+                    docs: #docs,
+                }
+            });
+
             let fields = v.fields.iter()
                 .filter_map(|f| f.ident.as_ref()) // ident should always exist
                 .collect::<Vec<_>>();
 
+            // who sends this variant. the prefix is the convention, but it can be overridden
+            let direction = match (&variant_attrs.direction, &variant_attrs.direction_from) {
+                (_, Some(field)) => quote! { #field.direction() },
+                (Some(direction), None) => direction_tokens(direction)?,
+                (None, None) => {
+                    let name = variant_name.to_string();
+                    let direction = if name.starts_with("Client_") { "client" } else if name.starts_with("Server_") { "server" } else { "both" };
+                    direction_tokens(&Ident::new(direction, variant_name.span()))?
+                }
+            };
+            let pattern = match &variant_attrs.direction_from {
+                Some(field) => quote! { { #field, .. } },
+                None => quote! { { .. } },
+            };
+            directions.push(quote! { Self::#variant_name #pattern => #direction, });
+            if packet_attrs.directional {
+                directional_variants.push(DirectionalVariant::new(v, &variant_attrs)?);
+            }
+
+            if !variant_attrs.no_handler {
+                handler_methods.push(HandlerMethod {
+                    variant: variant_name,
+                    method: format_ident!("{}", snake_case(&variant_name.to_string())),
+                    docs: v.attrs.iter().filter(|a| a.path().is_ident("doc")).collect(),
+                    fields: fields.clone(),
+                    types: v.fields.iter().map(|f| &f.ty).collect(),
+                });
+            }
+
             if fields.is_empty() {
                 read_fields.push(proc_macro2::TokenStream::new());
 
-                write_fields.push(quote! {
-                    => sw.write(&(#id as #type_)),
-                });
+                if packet_attrs.framed {
+                    write_fields.push(quote! {
+                        => {
+                            sw.write(&(#id as #type_));
+                            let frame = sw.begin_frame();
+                            sw.end_frame(frame);
+                        }
+                    });
+                    len_fields.push(quote! {
+                        => sw.serialized_len(&(#id as #type_)) + sw.frame_size(0),
+                    });
+                } else {
+                    write_fields.push(quote! {
+                        => sw.write(&(#id as #type_)),
+                    });
+                    len_fields.push(quote! {
+                        => sw.serialized_len(&(#id as #type_)),
+                    });
+                }
             } else {
                 read_fields.push(quote!{ {
                     #( #fields: sr.read(stringify!(#fields))?, )*
                 } });
 
-                write_fields.push(quote! {
-                    { #(#fields),* } => {
-                        sw.write(&(#id as #type_));
-                        #( sw.write(#fields); )*
-                    }
-                });
+                if packet_attrs.framed {
+                    write_fields.push(quote! {
+                        { #(#fields),* } => {
+                            sw.write(&(#id as #type_));
+                            let frame = sw.begin_frame();
+                            #( sw.write(#fields); )*
+                            sw.end_frame(frame);
+                        }
+                    });
+                    len_fields.push(quote! {
+                        { #(#fields),* } => {
+                            let payload = 0 #( + sw.serialized_len(#fields) )*;
+                            sw.serialized_len(&(#id as #type_)) + sw.frame_size(payload) + payload
+                        }
+                    });
+                } else {
+                    write_fields.push(quote! {
+                        { #(#fields),* } => {
+                            sw.write(&(#id as #type_));
+                            #( sw.write(#fields); )*
+                        }
+                    });
+                    len_fields.push(quote! {
+                        { #(#fields),* } => sw.serialized_len(&(#id as #type_)) #( + sw.serialized_len(#fields) )*,
+                    });
+                }
             }
         }
     }
@@ -107,19 +210,39 @@ pub fn derive(ast: &syn::DeriveInput) -> Result<TokenStream> {
         }
     }
 
+    // framed packets have their payload length written after the id,
+    // so unknown packets and unknown trailing fields can be skipped
+    let (begin_frame, end_frame) = if packet_attrs.framed {
+        (quote! { let frame = sr.begin_frame()?; }, quote! { sr.end_frame(frame); })
+    } else {
+        Default::default()
+    };
+
     let name = enum_name.to_string();
+
+    // strict enums error on unknown ids, lenient ones fall back to the default variant
+    let unknown_variant = if packet_attrs.strict {
+        quote! { return Err(sr.unknown_variant(#name, packet_id, id_offset)) }
+    } else {
+        quote! { Self::#default_variant }
+    };
+
     let mut tokens = quote! {
         impl Serializable for #enum_name {
             fn read(sr: &mut crate::serialization::SerializationReader) -> SerializationResult<Self> {
                 sr.push_parent(#name);
+                #[allow(unused_variables)]
+                let id_offset = sr.offset();
                 let packet_id = sr.read::<#type_>("packet_id")? as u16;
                 #debug_read_line
+                #begin_frame
 
                 let a = match packet_id {
                     #( #ids => Self::#variants #read_fields, )*
-                    _ => Self::#default_variant
+                    _ => #unknown_variant
                 };
 
+                #end_frame
                 sr.pop_parent();
                 Ok(a)
             }
@@ -130,9 +253,75 @@ pub fn derive(ast: &syn::DeriveInput) -> Result<TokenStream> {
                     _ => {}
                 }
             }
+
+            fn serialized_len(&self, sw: &crate::serialization::SerializationWriter) -> usize {
+                match self {
+                    #( Self::#variants #len_fields )*
+                    _ => 0
+                }
+            }
         }
     };
 
+    // so the serde bridge can write this enum the same way
+    let variant_names = variants.iter().map(|v| v.to_string());
+    let fallback_variant = match default_variant_name.filter(|_| !packet_attrs.strict) {
+        Some(name) => quote! { Some(#name) },
+        None => quote! { None },
+    };
+    let framed = packet_attrs.framed;
+    tokens.extend(quote! {
+        impl crate::serialization::HasVariantIds for #enum_name {
+            const VARIANT_IDS: crate::serialization::VariantIds = crate::serialization::VariantIds {
+                enum_name: #name,
+                id_size: std::mem::size_of::<#type_>(),
+                ids: &[ #( (#variant_names, #ids as u64), )* ],
+                fallback: #fallback_variant,
+                framed: #framed,
+            };
+        }
+    });
+
+    // describes all of the above
+    let id_type = type_.to_string();
+    let strict = packet_attrs.strict;
+    tokens.extend(quote! {
+        impl crate::serialization::WireSchema for #enum_name {
+            fn schema() -> crate::serialization::TypeSchema {
+                crate::serialization::TypeSchema::new(#name, crate::serialization::SchemaKind::Enum {
+                    id_type: #id_type,
+                    framed: #framed,
+                    strict: #strict,
+                    fallback: <Self as crate::serialization::HasVariantIds>::VARIANT_IDS.fallback,
+                    variants: vec![ #(#variant_schemas),* ],
+                })
+            }
+        }
+    });
+
+    if packet_attrs.directional {
+        let client = format_ident!("{enum_name}ClientToServer");
+        let server = format_ident!("{enum_name}ServerToClient");
+        tokens.extend(quote! {
+            impl crate::serialization::Directional for #enum_name {
+                type ClientToServer = #client;
+                type ServerToClient = #server;
+
+                fn direction(&self) -> crate::serialization::PacketDirection {
+                    match self {
+                        #(#directions)*
+                    }
+                }
+            }
+        });
+        tokens.extend(direction_enum(ast, &client, true, &directional_variants, &write_fields, &len_fields));
+        tokens.extend(direction_enum(ast, &server, false, &directional_variants, &write_fields, &len_fields));
+    }
+
+    if let Some(handler) = &packet_attrs.handler {
+        tokens.extend(handler_trait(ast, handler, &packet_attrs.handler_context, &handler_methods));
+    }
+
     if packet_attrs.should_impl_into_from_type {
         tokens.extend(quote! {
             impl From<#enum_name> for #type_ {
@@ -194,11 +383,19 @@ struct PacketAttrs {
     // container attrs
     extra_logging: bool,
     should_impl_into_from_type: bool,
+    framed: bool,
+    strict: bool,
     type_: TokenStream,
+    handler: Option<Ident>,
+    handler_context: Vec<BareFnArg>,
+    directional: bool,
 
     // variant attrs
     id: Option<u16>,
     is_default: bool,
+    no_handler: bool,
+    direction: Option<Ident>,
+    direction_from: Option<Ident>,
 }
 impl PacketAttrs {
     fn parse(attrs: &[Attribute]) -> Result<Self> {
@@ -220,6 +417,21 @@ impl PacketAttrs {
                     this.should_impl_into_from_type = true;
                 } else if meta.path.is_ident(EXTRA_LOGGING_ATTRIBUTE) {
                     this.extra_logging = true;
+                } else if meta.path.is_ident(FRAMED_ATTRIBUTE) {
+                    this.framed = true;
+                } else if meta.path.is_ident(STRICT_ATTRIBUTE) {
+                    this.strict = true;
+                } else if meta.path.is_ident(DIRECTIONAL_ATTRIBUTE) {
+                    this.directional = true;
+                } else if meta.path.is_ident(HANDLER_ATTRIBUTE) {
+                    this.handler = Some(meta.value()?.parse()?);
+                } else if meta.path.is_ident(HANDLER_CONTEXT_ATTRIBUTE) {
+                    let content;
+                    parenthesized!(content in meta.input);
+                    for arg in content.parse_terminated(BareFnArg::parse, Token![,])? {
+                        if arg.name.is_none() { return Err(Error::new(arg.span(), "context arguments need a name")) }
+                        this.handler_context.push(arg);
+                    }
                 } 
                 
                 
@@ -232,6 +444,12 @@ impl PacketAttrs {
                 } 
                 else if meta.path.is_ident(DEFAULT_VARIANT_ATTRIBUTE) {
                     this.is_default = true;
+                } else if meta.path.is_ident(NO_HANDLER_ATTRIBUTE) {
+                    this.no_handler = true;
+                } else if meta.path.is_ident(DIRECTION_ATTRIBUTE) {
+                    this.direction = Some(meta.value()?.parse()?);
+                } else if meta.path.is_ident(DIRECTION_FROM_ATTRIBUTE) {
+                    this.direction_from = Some(meta.value()?.parse()?);
                 } 
                 
                 else {
@@ -245,3 +463,237 @@ impl PacketAttrs {
         Ok(this)
     }
 }
+
+
+/// `client`, `server` or `both` as a `PacketDirection`
+fn direction_tokens(direction: &Ident) -> Result<TokenStream> {
+    let variant = match direction.to_string().as_str() {
+        "client" => quote! { ClientToServer },
+        "server" => quote! { ServerToClient },
+        "both" => quote! { Both },
+        _ => return Err(Error::new(direction.span(), "direction should be `client`, `server` or `both`")),
+    };
+    Ok(quote! { crate::serialization::PacketDirection::#variant })
+}
+
+/// a variant, for the enums with only the variants going one way
+struct DirectionalVariant<'a> {
+    variant: &'a Variant,
+    /// `(from client, from server)`, for variants which dont depend on their fields
+    allowed: (bool, bool),
+    /// the field this variant's direction comes from
+    direction_from: Option<&'a Ident>,
+}
+impl<'a> DirectionalVariant<'a> {
+    fn new(variant: &'a Variant, attrs: &PacketAttrs) -> Result<Self> {
+        let name = variant.ident.to_string();
+        let direction = match &attrs.direction {
+            Some(direction) => direction.to_string(),
+            None if name.starts_with("Client_") => "client".to_owned(),
+            None if name.starts_with("Server_") => "server".to_owned(),
+            None => "both".to_owned(),
+        };
+        let allowed = match direction.as_str() {
+            "client" => (true, false),
+            "server" => (false, true),
+            _ => (true, true),
+        };
+
+        let direction_from = attrs.direction_from.as_ref()
+            .map(|field| variant.fields.iter()
+                .find_map(|f| f.ident.as_ref().filter(|i| *i == field))
+                .ok_or_else(|| Error::new(field.span(), "no field with this name"))
+            )
+            .transpose()?;
+
+        Ok(Self { variant, allowed: if direction_from.is_some() { (true, true) } else { allowed }, direction_from })
+    }
+}
+
+/// an enum with only the variants which can be sent one way, and conversions to and from the full enum.
+/// the direction is only checked when one is read (or converted from the full enum), since anything could be in the data
+fn direction_enum(
+    ast: &DeriveInput,
+    name: &Ident,
+    from_client: bool,
+    variants: &[DirectionalVariant],
+    write_fields: &[TokenStream],
+    len_fields: &[TokenStream],
+) -> TokenStream {
+    let enum_name = &ast.ident;
+    let vis = &ast.vis;
+    let (view, direction) = if from_client {
+        (quote! { crate::serialization::ClientToServer }, quote! { crate::serialization::PacketDirection::ClientToServer })
+    } else {
+        (quote! { crate::serialization::ServerToClient }, quote! { crate::serialization::PacketDirection::ServerToClient })
+    };
+    let doc = format!(" the [`{enum_name}`]s which {} can send\n\n reading one errors if the packet can't be sent this way", if from_client { "a client" } else { "a server" });
+
+    let mut definitions = Vec::new();
+    let mut into_full = Vec::new();
+    let mut from_full = Vec::new();
+    let mut writes = Vec::new();
+    let mut lens = Vec::new();
+    for (i, v) in variants.iter().enumerate() {
+        if !(if from_client { v.allowed.0 } else { v.allowed.1 }) { continue }
+
+        let variant_name = &v.variant.ident;
+        let docs = v.variant.attrs.iter().filter(|a| a.path().is_ident("doc"));
+        let fields = v.variant.fields.iter().filter_map(|f| f.ident.as_ref()).collect::<Vec<_>>();
+        let field_defs = v.variant.fields.iter().map(|f| {
+            let field_docs = f.attrs.iter().filter(|a| a.path().is_ident("doc"));
+            let (ident, ty) = (&f.ident, &f.ty);
+            // the wrapped packet has to go the same way
+            let ty = if f.ident.as_ref() == v.direction_from { quote! { #view<#ty> } } else { quote! { #ty } };
+            quote! { #(#field_docs)* #ident: #ty }
+        });
+        let field_defs = if fields.is_empty() { quote! {} } else { quote! { { #(#field_defs),* } } };
+        definitions.push(quote! { #(#docs)* #variant_name #field_defs });
+
+        match v.direction_from {
+            Some(from) => {
+                let others = fields.iter().filter(|f| **f != from);
+                into_full.push(quote! { #name::#variant_name { #(#fields),* } => Self::#variant_name { #(#others,)* #from: #from.into() }, });
+                from_full.push(quote! {
+                    #enum_name::#variant_name { #(#fields),* } => match #from.try_into() {
+                        Ok(#from) => Ok(Self::#variant_name { #(#fields),* }),
+                        Err(crate::serialization::WrongDirection { packet: #from, expected }) => Err(crate::serialization::WrongDirection {
+                            packet: #enum_name::#variant_name { #(#fields),* },
+                            expected,
+                        }),
+                    },
+                });
+            }
+            None => {
+                into_full.push(quote! { #name::#variant_name { #(#fields),* } => Self::#variant_name { #(#fields),* }, });
+                from_full.push(quote! { #enum_name::#variant_name { #(#fields),* } => Ok(Self::#variant_name { #(#fields),* }), });
+            }
+        }
+
+        // the wrapped packet is written the same way no matter which enum it's in
+        let (write, len) = (&write_fields[i], &len_fields[i]);
+        writes.push(quote! { Self::#variant_name #write });
+        lens.push(quote! { Self::#variant_name #len });
+    }
+
+    let name_str = name.to_string();
+    quote! {
+        #[doc = #doc]
+        #[derive(Clone, Debug)]
+        #[allow(non_camel_case_types, clippy::large_enum_variant)]
+        #vis enum #name {
+            #(#definitions,)*
+        }
+
+        impl From<#name> for #enum_name {
+            fn from(value: #name) -> Self {
+                match value {
+                    #(#into_full)*
+                }
+            }
+        }
+        impl TryFrom<#enum_name> for #name {
+            type Error = crate::serialization::WrongDirection<#enum_name>;
+            #[allow(unreachable_patterns)]
+            fn try_from(value: #enum_name) -> Result<Self, Self::Error> {
+                match value {
+                    #(#from_full)*
+                    packet => Err(crate::serialization::WrongDirection { packet, expected: #direction }),
+                }
+            }
+        }
+
+        impl Serializable for #name {
+            fn read(sr: &mut crate::serialization::SerializationReader) -> SerializationResult<Self> {
+                let offset = sr.offset();
+                let packet = sr.read::<#enum_name>("packet")?;
+                Self::try_from(packet).map_err(|e| sr.invalid_value(#name_str, format!("can't be sent {:?}", e.expected), offset))
+            }
+
+            fn write(&self, sw: &mut crate::serialization::SerializationWriter) {
+                match self {
+                    #(#writes)*
+                    _ => {}
+                }
+            }
+
+            fn serialized_len(&self, sw: &crate::serialization::SerializationWriter) -> usize {
+                match self {
+                    #(#lens)*
+                    _ => 0
+                }
+            }
+        }
+    }
+}
+
+/// a variant's method in the handler trait
+struct HandlerMethod<'a> {
+    variant: &'a Ident,
+    method: Ident,
+    docs: Vec<&'a Attribute>,
+    fields: Vec<&'a Ident>,
+    types: Vec<&'a Type>,
+}
+
+/// a trait with a method for each variant (which does nothing by default), and a `dispatch` fn which calls the right one
+fn handler_trait(ast: &DeriveInput, handler: &Ident, context: &[BareFnArg], methods: &[HandlerMethod]) -> TokenStream {
+    let enum_name = &ast.ident;
+    let vis = &ast.vis;
+    let context_names = context.iter().filter_map(|a| a.name.as_ref().map(|(name, _)| name)).collect::<Vec<_>>();
+    let context_types = context.iter().map(|a| &a.ty).collect::<Vec<_>>();
+
+    let trait_doc = format!(" handles [`{enum_name}`]s, see [`{enum_name}::dispatch`]\n\n every method does nothing by default, so only implement the ones you need");
+    let trait_methods = methods.iter().map(|HandlerMethod { method, docs, fields, types, .. }| quote! {
+        #(#docs)*
+        fn #method(&mut self, #( #context_names: #context_types, )* #( #fields: #types ),*) {}
+    });
+    let forwarded_methods = methods.iter().map(|HandlerMethod { method, fields, types, .. }| quote! {
+        fn #method(&mut self, #( #context_names: #context_types, )* #( #fields: #types ),*) {
+            (**self).#method(#( #context_names, )* #( #fields ),*)
+        }
+    });
+    let dispatch_arms = methods.iter().map(|HandlerMethod { variant, method, fields, .. }| quote! {
+        Self::#variant { #( #fields ),* } => handler.#method(#( #context_names, )* #( #fields ),*),
+    });
+
+    quote! {
+        #[doc = #trait_doc]
+        #[allow(unused_variables)]
+        #vis trait #handler {
+            #(#trait_methods)*
+        }
+        impl<H: #handler + ?Sized> #handler for &mut H {
+            #(#forwarded_methods)*
+        }
+
+        impl #enum_name {
+            /// call `handler`'s method for this packet
+            #[allow(unreachable_patterns)]
+            pub fn dispatch<H: #handler + ?Sized>(self, handler: &mut H, #( #context_names: #context_types ),*) {
+                match self {
+                    #(#dispatch_arms)*
+                    _ => {}
+                }
+            }
+        }
+    }
+}
+
+/// `Server_LobbyUserJoined` -> `server_lobby_user_joined`
+fn snake_case(name: &str) -> String {
+    let chars = name.chars().collect::<Vec<_>>();
+    let mut snake = String::new();
+
+    for (i, c) in chars.iter().enumerate() {
+        if c.is_uppercase() && i > 0 {
+            let prev = chars[i - 1];
+            let next_is_lower = chars.get(i + 1).is_some_and(|n| n.is_lowercase());
+            // a new word, or the last capital of an acronym (ie `HTTPServer` -> `http_server`)
+            if prev != '_' && (!prev.is_uppercase() || next_is_lower) { snake.push('_') }
+        }
+        snake.extend(c.to_lowercase());
+    }
+
+    snake
+}
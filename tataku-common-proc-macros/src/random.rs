@@ -0,0 +1,161 @@
+use syn::*;
+use quote::*;
+use proc_macro2::TokenStream;
+use syn::spanned::Spanned;
+
+const RANDOM_ATTRIBUTE: &str = "random";
+const WITH_ATTRIBUTE: &str = "with";
+const DEFAULT_ATTRIBUTE: &str = "default";
+
+// the Serializable attributes we care about
+const SERIALIZE_ATTRIBUTE: &str = "serialize";
+const SKIP_ATTRIBUTE: &str = "skip";
+const CURRENT_VERSION_ATTRIBUTE: &str = "current_version";
+const VERSION_FIELD: &str = "version";
+const READ_AS_ATTRIBUTE: &str = "read_as";
+
+// generate random values for roundtrip tests
+pub fn derive(ast: &DeriveInput) -> Result<TokenStream> {
+    let name = &ast.ident;
+    let (impl_generics, ty_generics, where_clause) = ast.generics.split_for_impl();
+
+    let (random, random_variants) = match &ast.data {
+        Data::Struct(data) => {
+            // the version field is always the version being written
+            let current_version = current_version(&ast.attrs)?;
+            let version = current_version.as_ref().filter(|_| {
+                data.fields.iter().next().is_some_and(|f| f.ident.as_ref().is_some_and(|i| i == VERSION_FIELD))
+            });
+
+            let value = construct(quote! { Self }, &data.fields, version)?;
+            (value, quote! {})
+        }
+
+        Data::Enum(data) => {
+            let variants = data.variants.iter()
+                .map(|v| {
+                    let ident = &v.ident;
+                    construct(quote! { Self::#ident }, &v.fields, None)
+                })
+                .collect::<Result<Vec<_>>>()?;
+            if variants.is_empty() {
+                return Err(Error::new(ast.span(), "RandomValue needs at least one variant"))
+            }
+
+            let count = variants.len();
+            let indices = 0..count;
+            (
+                quote! {
+                    match rng.below(#count) {
+                        #( #indices => #variants, )*
+                        _ => unreachable!(),
+                    }
+                },
+                quote! {
+                    fn random_variants(rng: &mut crate::serialization::RandomSource) -> Vec<Self> {
+                        vec![ #( #variants ),* ]
+                    }
+                }
+            )
+        }
+
+        Data::Union(_) => return Err(Error::new(ast.span(), "RandomValue cant be derived for unions")),
+    };
+
+    Ok(quote! {
+        impl #impl_generics crate::serialization::RandomValue for #name #ty_generics #where_clause {
+            #[allow(unused_variables)]
+            fn random(rng: &mut crate::serialization::RandomSource) -> Self {
+                #random
+            }
+
+            #random_variants
+        }
+    })
+}
+
+/// build `path` with random fields
+fn construct(path: TokenStream, fields: &Fields, version: Option<&Expr>) -> Result<TokenStream> {
+    let mut values = Vec::new();
+    for (n, f) in fields.iter().enumerate() {
+        let value = match (n, version) {
+            (0, Some(version)) => quote! { #version },
+            _ => field_value(f)?,
+        };
+        values.push(value);
+    }
+
+    Ok(match fields {
+        Fields::Named(_) => {
+            let names = fields.iter().map(|f| &f.ident);
+            quote! { #path { #( #names: #values, )* } }
+        }
+        Fields::Unnamed(_) => quote! { #path ( #( #values, )* ) },
+        Fields::Unit => path,
+    })
+}
+
+fn field_value(field: &Field) -> Result<TokenStream> {
+    let mut value = None;
+
+    for a in field.attrs.iter() {
+        // skipped fields are never written, so they'll always be read as the default
+        if a.path().is_ident(SERIALIZE_ATTRIBUTE) {
+            a.parse_nested_meta(|meta| {
+                if meta.path.is_ident(SKIP_ATTRIBUTE) {
+                    value = Some(quote! { Default::default() });
+                    Ok(())
+                } else {
+                    skip_meta(&meta)
+                }
+            })?;
+        }
+
+        if a.path().is_ident(RANDOM_ATTRIBUTE) {
+            a.parse_nested_meta(|meta| {
+                if meta.path.is_ident(WITH_ATTRIBUTE) {
+                    let with = meta.value()?.parse::<Expr>()?;
+                    value = Some(quote! { (#with)(rng) });
+                } else if meta.path.is_ident(DEFAULT_ATTRIBUTE) {
+                    value = Some(quote! { Default::default() });
+                } else {
+                    return Err(meta.error("invalid attribute"));
+                }
+                Ok(())
+            })?;
+        }
+    }
+
+    let ty = &field.ty;
+    Ok(value.unwrap_or_else(|| quote! { <#ty as crate::serialization::RandomValue>::random(rng) }))
+}
+
+fn current_version(attrs: &[Attribute]) -> Result<Option<Expr>> {
+    let mut current_version = None;
+    for a in attrs.iter().filter(|a| a.path().is_ident(SERIALIZE_ATTRIBUTE)) {
+        a.parse_nested_meta(|meta| {
+            if meta.path.is_ident(CURRENT_VERSION_ATTRIBUTE) {
+                current_version = Some(meta.value()?.parse()?);
+                Ok(())
+            } else {
+                skip_meta(&meta)
+            }
+        })?;
+    }
+    Ok(current_version)
+}
+
+/// skip a `#[serialize(...)]` item which isnt ours
+fn skip_meta(meta: &meta::ParseNestedMeta) -> Result<()> {
+    if meta.input.peek(token::Paren) {
+        let _ = meta.input.parse::<proc_macro2::Group>()?;
+    } else if meta.input.peek(Token![=]) {
+        let value = meta.value()?;
+        if meta.path.is_ident(READ_AS_ATTRIBUTE) {
+            let _ = value.parse::<Type>()?;
+        } else {
+            let _ = value.parse::<Expr>()?;
+        }
+    }
+    Ok(())
+}
@@ -1,123 +1,721 @@
 use syn::*;
 use quote::*;
 use proc_macro::TokenStream;
+use syn::spanned::Spanned;
 
 const SERIALIZE_ATTRIBUTE: &str = "serialize";
 const READ_VERSION_ATTRIBUTE: &str = "read_version";
-const VERSION_ATTRIBUTE: &str = "version";
+const CURRENT_VERSION_ATTRIBUTE: &str = "current_version";
 const VERSION_FIELD: &str = "version";
 
+// field attributes
+const VERSION_ATTRIBUTE: &str = "version";
+const ADDED_ATTRIBUTE: &str = "added";
+const REMOVED_ATTRIBUTE: &str = "removed";
+const READ_AS_ATTRIBUTE: &str = "read_as";
+const CONVERT_ATTRIBUTE: &str = "convert";
+const CONVERT_BACK_ATTRIBUTE: &str = "convert_back";
+const LOSSY_ATTRIBUTE: &str = "lossy";
+const DEFAULT_ATTRIBUTE: &str = "default";
+const SKIP_ATTRIBUTE: &str = "skip";
+const REMOVED_FIELD_ATTRIBUTE: &str = "removed_field";
+const NAME_ATTRIBUTE: &str = "name";
+const TYPE_ATTRIBUTE: &str = "ty";
+const WITH_ATTRIBUTE: &str = "with";
+
+// enum attributes
+const FALLBACK_ATTRIBUTE: &str = "fallback";
+/// the types variant ids can be written as
+const ID_TYPES: &[(&str, u64)] = &[("u8", u8::MAX as u64), ("u16", u16::MAX as u64), ("u32", u32::MAX as u64), ("u64", u64::MAX)];
+
 
 pub fn derive(ast: syn::DeriveInput) -> TokenStream {
+    let tokens = match derive_inner(&ast) {
+        Ok(tokens) => tokens,
+        Err(e) => return e.into_compile_error().into(),
+    };
+
+    #[cfg(feature="serialization_logging")] {
+        std::fs::create_dir_all("debug").unwrap();
+        std::fs::write(format!("debug/{}.rs", ast.ident), &tokens.to_string()).unwrap();
+        // println!("generated: {}", impl_str)
+    }
+
+    tokens.into()
+}
+
+fn derive_inner(ast: &syn::DeriveInput) -> Result<proc_macro2::TokenStream> {
+    match &ast.data {
+        Data::Struct(data) => derive_struct(ast, data),
+        Data::Enum(data) => derive_enum(ast, data),
+        Data::Union(_) => Err(Error::new(ast.span(), "Serializable cant be derived for unions")),
+    }
+}
+
+fn derive_struct(ast: &syn::DeriveInput, data: &DataStruct) -> Result<proc_macro2::TokenStream> {
     let struct_name = &ast.ident;
     let struct_name_str = struct_name.to_string();
+    let schema_name = schema_name(ast);
+    let (_, ty_generics, _) = ast.generics.split_for_impl();
+    let serializable_generics = bounded_generics(ast, quote! { Serializable });
+    let (serializable_impl_generics, _, serializable_where_clause) = serializable_generics.split_for_impl();
+    let schema_generics = bounded_generics(ast, quote! { WireSchema });
+    let (schema_impl_generics, _, schema_where_clause) = schema_generics.split_for_impl();
 
+    // check if this struct has a version attached
     let mut read_version = false;
-    let mut read_version_line = proc_macro2::TokenStream::new();
+    let mut current_version = None;
+    for attr in ast.attrs.iter() {
+        if !attr.path().is_ident(SERIALIZE_ATTRIBUTE) { continue }
+
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident(READ_VERSION_ATTRIBUTE) {
+                let _ = meta.value()?;
+                let value: LitBool = meta.input.parse()?;
+                read_version = value.value;
+            } else if meta.path.is_ident(CURRENT_VERSION_ATTRIBUTE) {
+                current_version = Some(meta.value()?.parse::<Expr>()?);
+            } else {
+                return Err(meta.error("invalid attribute"));
+            }
+
+            Ok(())
+        })?;
+    }
 
-    let mut field_names = Vec::new();
-    let mut field_name_strs = Vec::new();
-    let mut field_versions = Vec::new();
+    // check to see if we have a version field
+    let has_version_field = data.fields.iter().next().is_some_and(|f| f.ident.as_ref().is_some_and(|i| i == VERSION_FIELD));
+    let (read_version_line, write_version_line, len_version_line) = match (has_version_field, &current_version) {
+        (true, Some(_)) | (false, Some(_)) => (
+            quote! { let version: u16 = sr.read("version")?; },
+            quote! { sw.write(&version); },
+            quote! { let version: u16 = Self::CURRENT_VERSION; len += sw.serialized_len(&version); }
+        ),
+        (true, None) => (
+            quote! { let version: u16 = sr.read("version")?; },
+            quote! { let version: u16 = self.version; sw.write(&version); },
+            quote! { let version: u16 = self.version; len += sw.serialized_len(&version); }
+        ),
+        (false, None) if read_version => return Err(Error::new(
+            ast.span(),
+            "read_version needs a current_version to know which version to write"
+        )),
+        (false, None) => (
+            quote! { let version = 0u16; },
+            quote! { let version = 0u16; },
+            quote! { let version = 0u16; }
+        ),
+    };
 
-    if let Data::Struct(data) = &ast.data {
+    let mut members = Vec::new();
+    let mut bindings = Vec::new();
+    let mut read_fields = Vec::new();
+    let mut write_fields = Vec::new();
+    let mut len_fields = Vec::new();
+    let mut field_schemas = Vec::new();
 
-        // check if this struct has a version attached
-        for attr in ast.attrs.iter() {
-            if !attr.path().is_ident(SERIALIZE_ATTRIBUTE) { continue }
+    for (n, field) in data.fields.iter().enumerate() {
+        let (member, name) = field_binding(field, n);
+        members.push(member.clone());
+        bindings.push(name.clone());
 
-            if let Err(e) = attr.parse_nested_meta(|meta| {
-                if meta.path.is_ident(READ_VERSION_ATTRIBUTE) {
-                    let _ = meta.value()?;
-                    let value: LitBool = meta.input.parse()?;
-                    read_version = value.value;
-                } else {
-                    return Err(meta.error("invalid attribute"));
+        // the version was already read
+        if n == 0 && has_version_field {
+            read_fields.push(quote! { let #name = version; });
+            continue;
+        }
+
+        let attrs = FieldAttrs::parse(field)?;
+
+        // fields which were removed from the struct, but are still in older versions
+        for removed in attrs.removed_fields.iter() {
+            let RemovedField { name, ty, range } = removed;
+            let live = range.live();
+            read_fields.push(quote! {
+                if #live { let _ = sr.read::<#ty>(#name)?; }
+            });
+            write_fields.push(quote! {
+                if #live { sw.write(&<#ty as Default>::default()); }
+            });
+            len_fields.push(quote! {
+                if #live { len += sw.serialized_len(&<#ty as Default>::default()); }
+            });
+            field_schemas.push(range.schema(quote! { #name }, quote! { <#ty as WireSchema>::schema }, ""));
+        }
+
+        // skipped fields are never written, so theyre not lost
+        if attrs.skip {
+            let ty = &field.ty;
+            let default = attrs.default.map(|d| quote! { #d }).unwrap_or_else(|| quote! { Default::default() });
+            read_fields.push(quote! { let #name: #ty = #default; });
+            continue;
+        }
+
+        let name_str = attrs.name.as_ref().map(LitStr::value).unwrap_or_else(|| name.to_string());
+        let docs = crate::doc_string(&field.attrs);
+        for (i, layout) in attrs.layouts.iter().enumerate() {
+            let ty = layout.read_as.as_ref().unwrap_or(&field.ty);
+            let docs = if i == 0 { docs.as_str() } else { "" };
+            let schema = match &attrs.with {
+                Some(with) if layout.read_as.is_none() => quote! { #with::schema },
+                _ => quote! { <#ty as WireSchema>::schema },
+            };
+            field_schemas.push(layout.range.schema(quote! { #name_str }, schema, docs));
+        }
+
+        let default = attrs.default.map(|d| quote! { #d }).unwrap_or_else(|| quote! { Default::default() });
+
+        let mut read_layouts = Vec::new();
+        let mut write_layouts = Vec::new();
+        let mut len_layouts = Vec::new();
+        let mut always_read = None;
+        for layout in attrs.layouts.iter() {
+            let live = layout.range.live();
+            let (read, write, len) = match (&layout.read_as, &attrs.with) {
+                (None, None) => (
+                    quote! { sr.read(#name_str)? },
+                    quote! { sw.write(&self.#member); },
+                    quote! { len += sw.serialized_len(&self.#member); }
+                ),
+                (None, Some(with)) => (
+                    quote! { sr.read_with(#name_str, #with::read)? },
+                    quote! { #with::write(&self.#member, sw); },
+                    quote! { len += sw.measure(|sw| #with::write(&self.#member, sw)); }
+                ),
+                (Some(read_as), _) => {
+                    let read = match &layout.convert {
+                        Some(convert) => quote! { (#convert)(sr.read::<#read_as>(#name_str)?) },
+                        None => quote! { Into::into(sr.read::<#read_as>(#name_str)?) },
+                    };
+                    // cant be converted back, so the default is written instead
+                    let write = match &layout.convert_back {
+                        Some(convert_back) => {
+                            let lossy = layout.lossy.as_ref().map(|lossy| quote! { if (#lossy)(&self.#member) { lost.push(#name_str); } });
+                            quote! { sw.write::<#read_as>(&(#convert_back)(&self.#member)); #lossy }
+                        }
+                        None => quote! { 
+                            sw.write(&<#read_as as Default>::default()); 
+                            lost.push(#name_str);
+                        },
+                    };
+                    let len = match &layout.convert_back {
+                        Some(convert_back) => quote! { len += sw.serialized_len::<#read_as>(&(#convert_back)(&self.#member)); },
+                        None => quote! { len += sw.serialized_len(&<#read_as as Default>::default()); },
+                    };
+                    (read, write, len)
+                }
+            };
+
+            if layout.range.added == 0 && layout.range.removed.is_none() { always_read = Some(read.clone()) }
+            read_layouts.push(quote! { if #live { #read } });
+            write_layouts.push(quote! { if #live { #write } });
+            len_layouts.push(quote! { if #live { #len } });
+        }
+
+        let ty = &field.ty;
+        // fields which are in every version dont need a default
+        match always_read {
+            Some(read) => read_fields.push(quote! { let #name: #ty = #read; }),
+            None => read_fields.push(quote! {
+                let #name: #ty = #( #read_layouts else )* { #default };
+            }),
+        }
+        write_fields.push(quote! {
+            #( #write_layouts else )* { lost.push(#name_str); }
+        });
+        len_fields.push(quote! {
+            #( #len_layouts else )* {}
+        });
+    }
+
+    let write_body = quote! {
+        #[allow(unused_mut)]
+        let mut lost: Vec<&'static str> = Vec::new();
+        #write_version_line
+        #( #write_fields )*
+        lost
+    };
+    let (write_fn, versioned_impl) = match &current_version {
+        Some(current) => (
+            quote! {
+                fn write(&self, sw: &mut SerializationWriter) {
+                    VersionedSerializable::write_versioned(self, sw, #current);
+                }
+            },
+            quote! {
+                impl #serializable_impl_generics VersionedSerializable for #struct_name #ty_generics #serializable_where_clause {
+                    const CURRENT_VERSION: u16 = #current;
+
+                    #[allow(clippy::manual_range_contains)]
+                    fn write_versioned(&self, sw: &mut SerializationWriter, version: u16) -> Vec<&'static str> {
+                        assert!(
+                            version <= Self::CURRENT_VERSION, 
+                            "cant write {} v{version}, the newest version is v{}", #struct_name_str, Self::CURRENT_VERSION
+                        );
+                        #write_body
+                    }
                 }
- 
-                Ok(())
-            }) {
-                return e.into_compile_error().into()
+            }
+        ),
+        None => (
+            quote! {
+                #[allow(clippy::manual_range_contains, unused_variables)]
+                fn write(&self, sw: &mut SerializationWriter) {
+                    let _: Vec<&'static str> = { #write_body };
+                }
+            },
+            quote! {}
+        ),
+    };
+
+    let versioned = has_version_field || current_version.is_some();
+    let current_version = match &current_version {
+        Some(current) => quote! { Some(#current) },
+        None => quote! { None },
+    };
+
+    Ok(quote! {
+        impl #schema_impl_generics WireSchema for #struct_name #ty_generics #schema_where_clause {
+            fn schema() -> TypeSchema {
+                TypeSchema::new(#schema_name, SchemaKind::Struct {
+                    versioned: #versioned,
+                    current_version: #current_version,
+                    fields: vec![ #( #field_schemas ),* ],
+                })
             }
         }
 
-        // check to see if we have a version field
-        if let Some(f) = data.fields.iter().next() {
-            if *f.ident.as_ref().unwrap() == VERSION_FIELD {
-                read_version_line.extend(quote!{
-                    s.version = sr.read("version")?;
-                    let version = s.version;
-                });
-            } else if read_version {
-                read_version_line.extend(quote! {
-                    let version = sr.read::<u16>("version")?;
-                });
-            } else {
-                read_version_line.extend(quote! {
-                    let version = 0u16;
-                });
+        impl #serializable_impl_generics Serializable for #struct_name #ty_generics #serializable_where_clause {
+            #[allow(clippy::manual_range_contains, unused_variables)]
+            fn read(sr: &mut SerializationReader) -> SerializationResult<Self> where Self: Sized {
+                sr.push_parent(#struct_name_str);
+                #read_version_line
+
+                #( #read_fields )*
+
+                sr.pop_parent();
+                Ok(Self {
+                    #( #members: #bindings, )*
+                })
+            }
+
+            #write_fn
+
+            #[allow(clippy::manual_range_contains, unused_variables)]
+            fn serialized_len(&self, sw: &SerializationWriter) -> usize {
+                let mut len = 0;
+                #len_version_line
+                #( #len_fields )*
+                len
             }
         }
+        #versioned_impl
+    })
+}
+
+fn derive_enum(ast: &syn::DeriveInput, data: &DataEnum) -> Result<proc_macro2::TokenStream> {
+    let enum_name = &ast.ident;
+    let enum_name_str = enum_name.to_string();
+    let schema_name = schema_name(ast);
+    if let Some(a) = ast.attrs.iter().find(|a| a.path().is_ident(SERIALIZE_ATTRIBUTE)) {
+        return Err(Error::new(a.span(), "enums cant be versioned"))
+    }
+    if data.variants.is_empty() {
+        return Err(Error::new(ast.span(), "Serializable needs at least one variant"))
+    }
 
+    // the id is written as the enum's repr, or a u8
+    let id_type = repr_type(&ast.attrs)?.unwrap_or_else(|| format_ident!("u8"));
+    let id_type_str = id_type.to_string();
+    let max_id = ID_TYPES.iter().find(|(t, _)| *t == id_type_str).map(|(_, max)| *max)
+        .ok_or_else(|| Error::new(id_type.span(), "variant ids must be written as a u8, u16, u32 or u64"))?;
 
-        for field in data.fields.iter() {
-            let name = field.ident.as_ref().unwrap();
-            let mut version:u16 = 0;
+    let mut ids = Vec::new();
+    let mut patterns = Vec::new();
+    let mut reads = Vec::new();
+    let mut writes = Vec::new();
+    let mut lens = Vec::new();
+    let mut variant_schemas = Vec::new();
+    let mut fallback = None;
+    let mut next_id = Some(0u64);
 
-            // check for version tag
-            for a in field.attrs.iter() {
-                if !a.path().is_ident(SERIALIZE_ATTRIBUTE) { continue }
+    for v in data.variants.iter() {
+        let variant = &v.ident;
+        let variant_str = variant.to_string();
 
-                if let Err(e) = a.parse_nested_meta(|meta| {
-                    if meta.path.is_ident(VERSION_ATTRIBUTE) {
-                        let _ = meta.value()?;
-                        let value: LitInt = meta.input.parse()?;
-                        version = value.base10_parse()?;
-                    } else {
-                        return Err(meta.error("invalid attribute"));
-                    }
+        // ids follow the discriminants, like `as` would
+        let id = match &v.discriminant {
+            Some((_, Expr::Lit(ExprLit { lit: Lit::Int(n), .. }))) => n.base10_parse::<u64>()?,
+            Some((_, e)) => return Err(Error::new(e.span(), "Serializable needs the discriminant to be an integer literal")),
+            None => next_id.ok_or_else(|| Error::new(v.span(), "variant id is too big"))?,
+        };
+        if id > max_id {
+            return Err(Error::new(v.span(), format!("variant id {id} doesnt fit in a {id_type_str}")))
+        }
+        if ids.contains(&id) {
+            return Err(Error::new(v.span(), format!("variant id {id} is used more than once")))
+        }
+        ids.push(id);
+        next_id = id.checked_add(1);
 
-                    Ok(())
-                }) {
-                    return e.into_compile_error().into();
-                }
+        for a in v.attrs.iter().filter(|a| a.path().is_ident(SERIALIZE_ATTRIBUTE)) {
+            a.parse_nested_meta(|meta| {
+                if !meta.path.is_ident(FALLBACK_ATTRIBUTE) { return Err(meta.error("invalid attribute")) }
+                if fallback.is_some() { return Err(meta.error("only one variant can be the fallback")) }
+                if !v.fields.is_empty() { return Err(meta.error("the fallback variant cant have fields")) }
+                fallback = Some(variant.clone());
+                Ok(())
+            })?;
+        }
+
+        let mut bindings = Vec::new();
+        let mut variant_reads = Vec::new();
+        let mut variant_writes = Vec::new();
+        let mut variant_lens = Vec::new();
+        let mut field_schemas = Vec::new();
+        for (n, field) in v.fields.iter().enumerate() {
+            let attrs = FieldAttrs::parse(field)?;
+            if !attrs.is_unversioned() {
+                return Err(Error::new(field.span(), "enum fields cant be versioned"))
             }
 
-            field_names.push(name);
-            field_name_strs.push(name.to_string());
-            field_versions.push(version);
+            let (_, name) = field_binding(field, n);
+            let name_str = attrs.name.as_ref().map(LitStr::value).unwrap_or_else(|| name.to_string());
+            let ty = &field.ty;
+
+            if attrs.skip {
+                let default = attrs.default.map(|d| quote! { #d }).unwrap_or_else(|| quote! { Default::default() });
+                bindings.push(match &field.ident {
+                    Some(ident) => quote! { #ident: _ },
+                    None => quote! { _ },
+                });
+                variant_reads.push(default);
+                continue;
+            }
+            bindings.push(quote! { #name });
+
+            let docs = crate::doc_string(&field.attrs);
+            match &attrs.with {
+                Some(with) => {
+                    variant_reads.push(quote! { sr.read_with(#name_str, #with::read)? });
+                    variant_writes.push(quote! { #with::write(#name, sw); });
+                    variant_lens.push(quote! { sw.measure(|sw| #with::write(#name, sw)) });
+                    field_schemas.push(quote! { FieldSchema { name: #name_str, ty: #with::schema, added: 0, removed: None, docs: #docs } });
+                }
+                None => {
+                    variant_reads.push(quote! { sr.read(#name_str)? });
+                    variant_writes.push(quote! { sw.write(#name); });
+                    variant_lens.push(quote! { sw.serialized_len(#name) });
+                    field_schemas.push(quote! { FieldSchema { docs: #docs, ..FieldSchema::new::<#ty>(#name_str) } });
+                }
+            }
         }
+
+        let (pattern, read) = match &v.fields {
+            Fields::Named(_) => {
+                let members = v.fields.iter().enumerate().map(|(n, f)| field_binding(f, n).0);
+                (
+                    quote! { Self::#variant { #( #bindings ),* } },
+                    quote! { Self::#variant { #( #members: #variant_reads ),* } }
+                )
+            }
+            Fields::Unnamed(_) => (
+                quote! { Self::#variant ( #( #bindings ),* ) },
+                quote! { Self::#variant ( #( #variant_reads ),* ) }
+            ),
+            Fields::Unit => (quote! { Self::#variant }, quote! { Self::#variant }),
+        };
+
+        let id = proc_macro2::Literal::u64_unsuffixed(id);
+        patterns.push(pattern);
+        reads.push(read);
+        writes.push(quote! {
+            sw.write::<#id_type>(&#id);
+            #( #variant_writes )*
+        });
+        lens.push(quote! { sw.serialized_len::<#id_type>(&#id) #( + #variant_lens )* });
+
+        let docs = crate::doc_string(&v.attrs);
+        variant_schemas.push(quote! {
+            VariantSchema { name: #variant_str, id: #id, fields: vec![ #( #field_schemas ),* ], docs: #docs }
+        });
     }
+    let ids = ids.into_iter().map(proc_macro2::Literal::u64_unsuffixed);
 
-    let tokens = quote! {
-        impl Serializable for #struct_name {
+    // unknown ids are an error, unless theres a fallback
+    let (unknown_variant, strict, fallback_name) = match &fallback {
+        Some(fallback) => {
+            let name = fallback.to_string();
+            (quote! { Self::#fallback }, false, quote! { Some(#name) })
+        }
+        None => (quote! { return Err(sr.unknown_variant(#enum_name_str, id, id_offset)) }, true, quote! { None }),
+    };
+
+    let (_, ty_generics, _) = ast.generics.split_for_impl();
+    let serializable_generics = bounded_generics(ast, quote! { Serializable });
+    let (serializable_impl_generics, _, serializable_where_clause) = serializable_generics.split_for_impl();
+    let schema_generics = bounded_generics(ast, quote! { WireSchema });
+    let (schema_impl_generics, _, schema_where_clause) = schema_generics.split_for_impl();
+
+    Ok(quote! {
+        impl #schema_impl_generics WireSchema for #enum_name #ty_generics #schema_where_clause {
+            fn schema() -> TypeSchema {
+                TypeSchema::new(#schema_name, SchemaKind::Enum {
+                    id_type: #id_type_str,
+                    framed: false,
+                    strict: #strict,
+                    fallback: #fallback_name,
+                    variants: vec![ #( #variant_schemas ),* ],
+                })
+            }
+        }
+
+        impl #serializable_impl_generics Serializable for #enum_name #ty_generics #serializable_where_clause {
+            #[allow(unused_variables, unreachable_patterns)]
             fn read(sr: &mut SerializationReader) -> SerializationResult<Self> where Self: Sized {
-                sr.push_parent(#struct_name_str);
-                let mut s = Self::default();
-                #read_version_line
+                sr.push_parent(#enum_name_str);
+                let id_offset = sr.offset();
+                let id = sr.read::<#id_type>("id")?;
 
-                #(
-                    if version >= #field_versions { 
-                        s.#field_names = sr.read(#field_name_strs)?; 
-                    }
-                )*
+                let a = match id {
+                    #( #ids => #reads, )*
+                    _ => #unknown_variant
+                };
 
                 sr.pop_parent();
-                Ok(s)
+                Ok(a)
             }
 
             fn write(&self, sw: &mut SerializationWriter) {
-                #(
-                    sw.write(&self.#field_names);
-                )*
+                match self {
+                    #( #patterns => { #writes } )*
+                }
+            }
+
+            fn serialized_len(&self, sw: &SerializationWriter) -> usize {
+                match self {
+                    #( #patterns => #lens, )*
+                }
             }
         }
-    };
+    })
+}
 
-    #[cfg(feature="serialization_logging")] {
-        std::fs::create_dir_all("debug").unwrap();
-        std::fs::write(format!("debug/{struct_name}.rs"), &tokens.to_string()).unwrap();
-        // println!("generated: {}", impl_str)
+/// the member to access a field with, and the name to bind it to (`field0` for tuple fields)
+fn field_binding(field: &Field, n: usize) -> (Member, Ident) {
+    match &field.ident {
+        Some(ident) => (Member::Named(ident.clone()), ident.clone()),
+        None => (Member::Unnamed(Index::from(n)), format_ident!("field{n}")),
     }
+}
 
-    tokens.into()
-}
\ No newline at end of file
+/// the ast's generics, with every type parameter bound by `bound`
+fn bounded_generics(ast: &syn::DeriveInput, bound: proc_macro2::TokenStream) -> Generics {
+    let mut generics = ast.generics.clone();
+    let params = generics.type_params().map(|p| p.ident.clone()).collect::<Vec<_>>();
+    let where_clause = generics.make_where_clause();
+    for param in params {
+        where_clause.predicates.push(parse_quote! { #param: #bound });
+    }
+    generics
+}
+
+/// the type's name in schemas, with its type parameters filled in (ie `Wrapper<u32>`)
+fn schema_name(ast: &syn::DeriveInput) -> proc_macro2::TokenStream {
+    let name = ast.ident.to_string();
+    let params = ast.generics.type_params().map(|p| &p.ident).collect::<Vec<_>>();
+    if params.is_empty() { return quote! { #name } }
+
+    quote! { format!("{}<{}>", #name, [ #( <#params as WireSchema>::schema().name ),* ].join(", ")) }
+}
+
+/// the int type in `#[repr(...)]`, if there is one
+fn repr_type(attrs: &[Attribute]) -> Result<Option<Ident>> {
+    let mut repr = None;
+    for a in attrs.iter().filter(|a| a.path().is_ident("repr")) {
+        a.parse_nested_meta(|meta| {
+            // skip ie `align(8)`
+            if meta.input.peek(token::Paren) {
+                let _ = meta.input.parse::<proc_macro2::Group>()?;
+            } else if let Some(ident) = meta.path.get_ident() && ident != "C" && ident != "transparent" {
+                repr = Some(ident.clone());
+            }
+            Ok(())
+        })?;
+    }
+    Ok(repr)
+}
+
+
+/// which versions something is in
+#[derive(Default)]
+struct VersionRange {
+    added: u16,
+    removed: Option<u16>,
+}
+impl VersionRange {
+    /// is this in the current version
+    fn live(&self) -> proc_macro2::TokenStream {
+        match (self.added, self.removed) {
+            (0, None) => quote! { true },
+            (0, Some(removed)) => quote! { (version < #removed) },
+            (added, Some(removed)) => quote! { (version >= #added && version < #removed) },
+            (added, None) => quote! { (version >= #added) },
+        }
+    }
+
+    /// a `FieldSchema` for a field in these versions, described by the `schema` fn
+    fn schema(&self, name: proc_macro2::TokenStream, schema: proc_macro2::TokenStream, docs: &str) -> proc_macro2::TokenStream {
+        let added = self.added;
+        let removed = match self.removed {
+            Some(removed) => quote! { Some(#removed) },
+            None => quote! { None },
+        };
+        quote! {
+            FieldSchema { name: #name, ty: #schema, added: #added, removed: #removed, docs: #docs }
+        }
+    }
+
+    fn overlaps(&self, other: &Self) -> bool {
+        self.added < other.removed.unwrap_or(u16::MAX) && other.added < self.removed.unwrap_or(u16::MAX)
+    }
+}
+
+/// how a field is written for a range of versions
+#[derive(Default)]
+struct FieldLayout {
+    range: VersionRange,
+    /// what the field was written as in these versions
+    read_as: Option<Type>,
+    /// `fn(read_as) -> field`
+    convert: Option<Expr>,
+    /// `fn(&field) -> read_as`
+    convert_back: Option<Expr>,
+    /// `fn(&field) -> bool`, if `convert_back` couldnt keep all of the field
+    lossy: Option<Expr>,
+}
+
+/// a field which doesnt exist on the struct anymore
+struct RemovedField {
+    name: LitStr,
+    ty: Type,
+    range: VersionRange,
+}
+
+#[derive(Default)]
+struct FieldAttrs {
+    layouts: Vec<FieldLayout>,
+    removed_fields: Vec<RemovedField>,
+    default: Option<Expr>,
+    skip: bool,
+    /// a module with `read`, `write` and `schema` fns for the field
+    with: Option<Path>,
+    /// the field's name in errors and schemas, if it isnt the field's name (ie for tuple fields)
+    name: Option<LitStr>,
+}
+impl FieldAttrs {
+    fn parse(field: &Field) -> Result<Self> {
+        let mut this = Self::default();
+
+        for a in field.attrs.iter() {
+            if !a.path().is_ident(SERIALIZE_ATTRIBUTE) { continue }
+
+            let mut layout = FieldLayout::default();
+            let mut is_layout = false;
+
+            a.parse_nested_meta(|meta| {
+                if meta.path.is_ident(VERSION_ATTRIBUTE) || meta.path.is_ident(ADDED_ATTRIBUTE) {
+                    layout.range.added = meta.value()?.parse::<LitInt>()?.base10_parse()?;
+                    is_layout = true;
+                } else if meta.path.is_ident(REMOVED_ATTRIBUTE) {
+                    layout.range.removed = Some(meta.value()?.parse::<LitInt>()?.base10_parse()?);
+                    is_layout = true;
+                } else if meta.path.is_ident(READ_AS_ATTRIBUTE) {
+                    layout.read_as = Some(meta.value()?.parse()?);
+                    is_layout = true;
+                } else if meta.path.is_ident(CONVERT_ATTRIBUTE) {
+                    layout.convert = Some(meta.value()?.parse()?);
+                    is_layout = true;
+                } else if meta.path.is_ident(CONVERT_BACK_ATTRIBUTE) {
+                    layout.convert_back = Some(meta.value()?.parse()?);
+                    is_layout = true;
+                } else if meta.path.is_ident(LOSSY_ATTRIBUTE) {
+                    layout.lossy = Some(meta.value()?.parse()?);
+                    is_layout = true;
+                } else if meta.path.is_ident(DEFAULT_ATTRIBUTE) {
+                    this.default = Some(meta.value()?.parse()?);
+                } else if meta.path.is_ident(SKIP_ATTRIBUTE) {
+                    this.skip = true;
+                } else if meta.path.is_ident(WITH_ATTRIBUTE) {
+                    this.with = Some(meta.value()?.parse()?);
+                } else if meta.path.is_ident(NAME_ATTRIBUTE) {
+                    this.name = Some(meta.value()?.parse()?);
+                } else if meta.path.is_ident(REMOVED_FIELD_ATTRIBUTE) {
+                    this.removed_fields.push(RemovedField::parse(&meta)?);
+                } else {
+                    return Err(meta.error("invalid attribute"));
+                }
+
+                Ok(())
+            })?;
+
+            if !is_layout { continue }
+            if layout.read_as.is_none() && (layout.convert.is_some() || layout.convert_back.is_some()) {
+                return Err(Error::new(a.span(), "convert and convert_back need read_as"))
+            }
+            if layout.lossy.is_some() && layout.convert_back.is_none() {
+                return Err(Error::new(a.span(), "lossy needs convert_back"))
+            }
+            if layout.read_as.is_some() && this.with.is_some() {
+                return Err(Error::new(a.span(), "read_as cant be used with `with`"))
+            }
+            if this.layouts.iter().any(|l| l.range.overlaps(&layout.range)) {
+                return Err(Error::new(a.span(), "field has more than one layout for the same version"))
+            }
+            this.layouts.push(layout);
+        }
+
+        // fields without any versioning are always there
+        if this.skip {
+            this.layouts.clear();
+        } else if this.layouts.is_empty() {
+            this.layouts.push(FieldLayout::default());
+        }
+
+        Ok(this)
+    }
+
+    /// is this field written the same way in every version
+    fn is_unversioned(&self) -> bool {
+        self.removed_fields.is_empty() && self.layouts.iter().all(|l| l.range.added == 0 && l.range.removed.is_none() && l.read_as.is_none())
+    }
+}
+impl RemovedField {
+    fn parse(meta: &meta::ParseNestedMeta) -> Result<Self> {
+        let mut name = None;
+        let mut ty = None;
+        let mut range = VersionRange::default();
+
+        meta.parse_nested_meta(|meta| {
+            if meta.path.is_ident(NAME_ATTRIBUTE) {
+                name = Some(meta.value()?.parse()?);
+            } else if meta.path.is_ident(TYPE_ATTRIBUTE) {
+                ty = Some(meta.value()?.parse()?);
+            } else if meta.path.is_ident(VERSION_ATTRIBUTE) || meta.path.is_ident(ADDED_ATTRIBUTE) {
+                range.added = meta.value()?.parse::<LitInt>()?.base10_parse()?;
+            } else if meta.path.is_ident(REMOVED_ATTRIBUTE) {
+                range.removed = Some(meta.value()?.parse::<LitInt>()?.base10_parse()?);
+            } else {
+                return Err(meta.error("invalid attribute"));
+            }
+
+            Ok(())
+        })?;
+
+        let Some(name) = name else { return Err(meta.error("removed_field needs a name")) };
+        let Some(ty) = ty else { return Err(meta.error("removed_field needs a ty")) };
+        if range.removed.is_none() { return Err(meta.error("removed_field needs a removed version")) }
+
+        Ok(Self { name, ty, range })
+    }
+}
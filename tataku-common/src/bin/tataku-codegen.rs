@@ -0,0 +1,33 @@
+//! generate protocol readers and writers for other languages
+//!
+//! usage: `tataku-codegen <typescript|csharp> [output file] [c# namespace]`
+use tataku_common::codegen::*;
+use tataku_common::packets::PROTOCOL_SCHEMA;
+
+fn main() {
+    let mut args = std::env::args().skip(1);
+    let lang = args.next().unwrap_or_default();
+    let output = args.next();
+
+    let code = match &*lang {
+        "typescript" | "ts" => generate_typescript(PROTOCOL_SCHEMA),
+        "csharp" | "cs" => generate_csharp(PROTOCOL_SCHEMA, &args.next().unwrap_or_else(|| "Tataku.Protocol".to_owned())),
+        _ => {
+            eprintln!("usage: tataku-codegen <typescript|csharp> [output file] [c# namespace]");
+            std::process::exit(1);
+        }
+    };
+
+    let code = match code {
+        Ok(code) => code,
+        Err(e) => {
+            eprintln!("{e}");
+            std::process::exit(1);
+        }
+    };
+
+    match output {
+        Some(path) => std::fs::write(&path, code).unwrap_or_else(|e| panic!("failed to write {path}: {e}")),
+        None => print!("{code}"),
+    }
+}
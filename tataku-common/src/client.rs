@@ -0,0 +1,656 @@
+//! async client connection, so every client doesnt have to reimplement the login/ping/logout dance
+//!
+//! works over anything which is `AsyncRead + AsyncWrite`, ie a `TcpStream`, or `tokio::io::duplex` for tests.
+//! incoming packets are split into an event stream per subsystem, and requests which have a response can be awaited
+use std::sync::{ Arc, Mutex };
+use std::sync::atomic::{ AtomicU32, Ordering };
+use std::time::Duration;
+use tokio::io::{ AsyncRead, AsyncWrite, AsyncWriteExt };
+use tokio::sync::{ broadcast, mpsc, oneshot, watch };
+use tokio::task::JoinHandle;
+use crate::packets::*;
+use crate::serialization::*;
+use crate::types::network::*;
+use crate::types::network::multiplayer::*;
+use crate::types::network::spectator::SpectateResult;
+
+#[derive(Clone, Debug)]
+pub struct ClientOptions {
+    /// which protocol to ask the server for
+    pub protocol: Protocol,
+    /// how long to wait for a response to a request (including the login)
+    pub request_timeout: Duration,
+    /// how many events each stream holds before slow receivers start missing some
+    pub event_capacity: usize,
+    /// limits for packets coming from the server
+    pub limits: DecodeLimits,
+}
+impl Default for ClientOptions {
+    fn default() -> Self {
+        Self {
+            protocol: Protocol::CURRENT,
+            request_timeout: Duration::from_secs(10),
+            event_capacity: 256,
+            limits: DecodeLimits::default(),
+        }
+    }
+}
+impl ClientOptions {
+    pub fn with_protocol(mut self, protocol: Protocol) -> Self {
+        self.protocol = protocol;
+        self
+    }
+    pub fn with_request_timeout(mut self, timeout: Duration) -> Self {
+        self.request_timeout = timeout;
+        self
+    }
+
+    /// connect to a server over tcp and log in
+    pub async fn connect(
+        self,
+        addr: impl tokio::net::ToSocketAddrs,
+        username: impl Into<String>,
+        password: impl Into<String>,
+        game: impl Into<String>,
+    ) -> ClientResult<TatakuClient> {
+        let stream = tokio::net::TcpStream::connect(addr).await?;
+        stream.set_nodelay(true)?;
+        self.login(stream, username, password, game).await
+    }
+
+    /// log in over an already open stream
+    pub async fn login<S: AsyncRead + AsyncWrite + Send + 'static>(
+        self,
+        stream: S,
+        username: impl Into<String>,
+        password: impl Into<String>,
+        game: impl Into<String>,
+    ) -> ClientResult<TatakuClient> {
+        let (read, mut write) = tokio::io::split(stream);
+
+        // the server doesnt know which protocol we speak yet, so the login is always legacy
+        let login = {
+            let mut writer = SerializationWriter::new();
+            writer.write(&PacketId::Client_UserLogin {
+                protocol_version: self.protocol.version(),
+                username: username.into(),
+                password: password.into(),
+                game: game.into(),
+            });
+            writer.data()
+        };
+        write.write_all(&login).await?;
+        write.flush().await?;
+
+        // everything after the login uses the protocol we asked for
+        let shared = Arc::new(Shared::new(self.event_capacity));
+        let response = shared.wait_for(None, |p| matches!(p, PacketId::Server_LoginResponse { .. }));
+        let (outgoing, outgoing_rx) = mpsc::unbounded_channel();
+        let decoder = PacketDecoder::new().with_protocol(self.protocol).with_limits(self.limits);
+
+        let mut client = TatakuClient {
+            user_id: 0,
+            protocol: self.protocol,
+            request_timeout: self.request_timeout,
+            reader: tokio::spawn(read_loop(read, decoder, shared.clone(), outgoing.clone())),
+            writer: tokio::spawn(write_loop(write, self.protocol, outgoing_rx, shared.clone())),
+            outgoing,
+            shared,
+        };
+
+        match client.response(response).await? {
+            PacketId::Server_LoginResponse { status: LoginStatus::Ok, user_id } => {
+                client.user_id = user_id;
+                Ok(client)
+            }
+            PacketId::Server_LoginResponse { status, .. } => Err(ClientError::Login(status)),
+            _ => Err(ClientError::Rejected),
+        }
+    }
+}
+
+
+/// a logged in connection to a server
+///
+/// pings are answered in the background. dropping the client closes the connection without logging out
+pub struct TatakuClient {
+    user_id: u32,
+    protocol: Protocol,
+    request_timeout: Duration,
+    outgoing: mpsc::UnboundedSender<Envelope<PacketId>>,
+    shared: Arc<Shared>,
+    reader: JoinHandle<()>,
+    writer: JoinHandle<()>,
+}
+impl TatakuClient {
+    /// connect to a server over tcp and log in, with the default options
+    pub async fn connect(
+        addr: impl tokio::net::ToSocketAddrs,
+        username: impl Into<String>,
+        password: impl Into<String>,
+        game: impl Into<String>,
+    ) -> ClientResult<Self> {
+        ClientOptions::default().connect(addr, username, password, game).await
+    }
+
+    /// log in over an already open stream, with the default options
+    pub async fn login<S: AsyncRead + AsyncWrite + Send + 'static>(
+        stream: S,
+        username: impl Into<String>,
+        password: impl Into<String>,
+        game: impl Into<String>,
+    ) -> ClientResult<Self> {
+        ClientOptions::default().login(stream, username, password, game).await
+    }
+
+    /// our user id, from the login response
+    pub fn user_id(&self) -> u32 { self.user_id }
+    /// the protocol being spoken
+    pub fn protocol(&self) -> Protocol { self.protocol }
+
+    /// chat packets from the server
+    pub fn chat_events(&self) -> broadcast::Receiver<ChatPacket> { self.shared.chat.subscribe() }
+    /// spectator packets from the server
+    pub fn spectator_events(&self) -> broadcast::Receiver<SpectatorEvent> { self.shared.spectator.subscribe() }
+    /// multiplayer packets from the server
+    pub fn multiplayer_events(&self) -> broadcast::Receiver<MultiplayerPacket> { self.shared.multiplayer.subscribe() }
+    /// every other packet from the server (user joins/leaves, status updates, notifications, errors, etc)
+    pub fn events(&self) -> broadcast::Receiver<PacketId> { self.shared.events.subscribe() }
+
+    /// is the connection still open?
+    pub fn is_connected(&self) -> bool { self.shared.state.borrow().is_none() }
+
+    /// wait until the connection is closed, and get why
+    pub async fn disconnected(&self) -> DisconnectReason {
+        let mut state = self.shared.state.subscribe();
+        let _ = state.wait_for(Option::is_some).await;
+        self.shared.disconnect_reason()
+    }
+
+    /// send a packet to the server
+    pub fn send(&self, packet: PacketId) -> ClientResult<()> {
+        self.send_envelope(Envelope::new(packet))
+    }
+    fn send_envelope(&self, envelope: Envelope<PacketId>) -> ClientResult<()> {
+        self.outgoing.send(envelope).map_err(|_| ClientError::Disconnected(self.shared.disconnect_reason()))
+    }
+
+    /// send a packet, and wait for the server's reply to it
+    ///
+    /// if the protocol has request ids, the reply is whatever the server sends back with this request's id,
+    /// and a `Server_Error` for it is a [`ClientError::Server`].
+    /// otherwise, it's the first packet from the server which `matches`.
+    /// the reply is still sent to the event streams too
+    pub async fn request(&self, packet: PacketId, matches: fn(&PacketId) -> bool) -> ClientResult<PacketId> {
+        let request_id = self.protocol.has_request_ids().then(|| self.shared.next_request_id());
+        let response = self.shared.wait_for(request_id, matches);
+        self.send_envelope(Envelope { request_id, packet })?;
+
+        match self.response(response).await? {
+            PacketId::Server_Error { code, error } => Err(ClientError::Server { code, error }),
+            packet => Ok(packet),
+        }
+    }
+
+    async fn response(&self, response: oneshot::Receiver<PacketId>) -> ClientResult<PacketId> {
+        match tokio::time::timeout(self.request_timeout, response).await {
+            Ok(Ok(packet)) => Ok(packet),
+            Ok(Err(_)) => Err(ClientError::Disconnected(self.shared.disconnect_reason())),
+            Err(_) => Err(ClientError::Timeout),
+        }
+    }
+
+    /// tell the server we're leaving, and close the connection once everything queued has been sent
+    pub async fn logout(mut self) -> ClientResult<()> {
+        self.send(PacketId::Client_LogOut)?;
+        let _ = (&mut self.writer).await;
+        self.reader.abort();
+        self.shared.disconnect(DisconnectReason::LoggedOut);
+        Ok(())
+    }
+
+
+    // ======= chat =======
+
+    /// join a chat channel, and get its previous messages. an empty password is no password
+    pub async fn join_channel(&self, channel: impl Into<String>, password: impl Into<String>) -> ClientResult<Vec<ChatHistoryMessage>> {
+        let response = self.request(
+            ChatPacket::Client_JoinChannel { channel: channel.into(), password: password.into() }.into(),
+            |p| matches!(p, PacketId::Chat_Packet { packet: ChatPacket::Server_JoinChannel { .. } })
+        ).await?;
+
+        let PacketId::Chat_Packet { packet: ChatPacket::Server_JoinChannel { previous_messages, .. } } = response else { return Err(ClientError::Rejected) };
+        Ok(previous_messages)
+    }
+
+    pub fn send_message(&self, channel: impl Into<String>, message: impl Into<String>) -> ClientResult<()> {
+        self.send(ChatPacket::Client_SendMessage { channel: channel.into(), message: message.into() }.into())
+    }
+
+    /// get the ids of our friends
+    pub async fn friends(&self) -> ClientResult<Vec<u32>> {
+        let response = self.request(
+            ChatPacket::Client_GetFriends.into(),
+            |p| matches!(p, PacketId::Chat_Packet { packet: ChatPacket::Server_FriendsList { .. } })
+        ).await?;
+
+        let PacketId::Chat_Packet { packet: ChatPacket::Server_FriendsList { friend_ids } } = response else { return Err(ClientError::Rejected) };
+        Ok(friend_ids)
+    }
+
+
+    // ======= spectator =======
+
+    /// start spectating someone. anything other than [`SpectateResult::Ok`] is an error
+    pub async fn spectate(&self, host_id: u32) -> ClientResult<()> {
+        let response = self.request(
+            SpectatorPacket::Client_Spectate.with_host(host_id),
+            |p| matches!(p, PacketId::Spectator_Packet { packet: SpectatorPacket::Server_SpectateResult { .. }, .. })
+        ).await?;
+
+        match response {
+            PacketId::Spectator_Packet { packet: SpectatorPacket::Server_SpectateResult { result: SpectateResult::Ok }, .. } => Ok(()),
+            PacketId::Spectator_Packet { packet: SpectatorPacket::Server_SpectateResult { result }, .. } => Err(ClientError::Spectate(result)),
+            _ => Err(ClientError::Rejected),
+        }
+    }
+
+
+    // ======= multiplayer =======
+
+    /// get the lobbies we can see
+    pub async fn lobby_list(&self) -> ClientResult<Vec<LobbyInfo>> {
+        let response = self.request(
+            MultiplayerPacket::Client_LobbyList.into(),
+            |p| matches!(p, PacketId::Multiplayer_Packet { packet: MultiplayerPacket::Server_LobbyList { .. } })
+        ).await?;
+
+        let PacketId::Multiplayer_Packet { packet: MultiplayerPacket::Server_LobbyList { lobbies } } = response else { return Err(ClientError::Rejected) };
+        Ok(lobbies)
+    }
+
+    /// create a lobby (and join it)
+    pub async fn create_lobby(&self, name: impl Into<String>, password: impl Into<String>, private: bool, players: u8) -> ClientResult<FullLobbyInfo> {
+        let response = self.request(
+            MultiplayerPacket::Client_CreateLobby { name: name.into(), password: password.into(), private, players }.into(),
+            |p| matches!(p, PacketId::Multiplayer_Packet { packet: MultiplayerPacket::Server_CreateLobby { .. } })
+        ).await?;
+
+        let PacketId::Multiplayer_Packet { packet: MultiplayerPacket::Server_CreateLobby { success: true, lobby: Some(lobby) } } = response else { return Err(ClientError::Rejected) };
+        Ok(lobby)
+    }
+
+    /// join a lobby. an empty password is no password
+    pub async fn join_lobby(&self, lobby_id: u32, password: impl Into<String>) -> ClientResult<FullLobbyInfo> {
+        let response = self.request(
+            MultiplayerPacket::Client_JoinLobby { lobby_id, password: password.into() }.into(),
+            |p| matches!(p, PacketId::Multiplayer_Packet { packet: MultiplayerPacket::Server_JoinLobby { .. } })
+        ).await?;
+
+        let PacketId::Multiplayer_Packet { packet: MultiplayerPacket::Server_JoinLobby { success: true, lobby: Some(lobby) } } = response else { return Err(ClientError::Rejected) };
+        Ok(lobby)
+    }
+
+    pub fn leave_lobby(&self) -> ClientResult<()> {
+        self.send(MultiplayerPacket::Client_LeaveLobby.into())
+    }
+}
+impl Drop for TatakuClient {
+    fn drop(&mut self) {
+        // the reader holds a sender for pongs, so the writer only stops once the reader has
+        self.reader.abort();
+    }
+}
+
+
+/// a spectator packet, and which host it's for
+#[derive(Clone, Debug)]
+pub struct SpectatorEvent {
+    pub host_id: u32,
+    pub packet: SpectatorPacket,
+}
+
+/// why the connection closed
+#[derive(Clone, Debug)]
+pub enum DisconnectReason {
+    /// the server dropped us (`Server_DropConnection`)
+    Dropped {
+        reason: ServerDropReason,
+        message: String,
+    },
+    /// we logged out
+    LoggedOut,
+    /// the stream ended
+    Closed,
+    /// reading or writing failed
+    Error(String),
+}
+
+#[derive(Debug)]
+pub enum ClientError {
+    Io(std::io::Error),
+    /// the server didnt accept the login
+    Login(LoginStatus),
+    /// the server said no (ie a wrong lobby password)
+    Rejected,
+    /// the server answered a request with a `Server_Error`
+    Server {
+        code: ServerErrorCode,
+        error: String,
+    },
+    /// couldn't spectate someone
+    Spectate(SpectateResult),
+    /// the server didnt respond in time
+    Timeout,
+    Disconnected(DisconnectReason),
+}
+impl From<std::io::Error> for ClientError {
+    fn from(value: std::io::Error) -> Self {
+        Self::Io(value)
+    }
+}
+impl core::fmt::Display for ClientError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "io error: {e}"),
+            Self::Login(status) => write!(f, "login failed: {status:?}"),
+            Self::Rejected => write!(f, "the server rejected the request"),
+            Self::Server { code, error } => write!(f, "server error ({code:?}): {error}"),
+            Self::Spectate(result) => write!(f, "couldn't spectate: {result:?}"),
+            Self::Timeout => write!(f, "the server didn't respond in time"),
+            Self::Disconnected(reason) => write!(f, "disconnected: {reason:?}"),
+        }
+    }
+}
+impl std::error::Error for ClientError {}
+
+pub type ClientResult<T> = Result<T, ClientError>;
+
+
+/// state shared between the client and its background tasks
+struct Shared {
+    /// requests waiting for a response
+    pending: Mutex<Vec<Pending>>,
+    /// the id for the next request, if the protocol has them
+    next_request_id: AtomicU32,
+    chat: broadcast::Sender<ChatPacket>,
+    spectator: broadcast::Sender<SpectatorEvent>,
+    multiplayer: broadcast::Sender<MultiplayerPacket>,
+    events: broadcast::Sender<PacketId>,
+    /// set once the connection is closed
+    state: watch::Sender<Option<DisconnectReason>>,
+}
+struct Pending {
+    /// with an id, only a packet with the same id is the response
+    request_id: Option<u32>,
+    matches: fn(&PacketId) -> bool,
+    response: oneshot::Sender<PacketId>,
+}
+impl Shared {
+    fn new(capacity: usize) -> Self {
+        Self {
+            pending: Mutex::new(Vec::new()),
+            next_request_id: AtomicU32::new(1),
+            chat: broadcast::channel(capacity).0,
+            spectator: broadcast::channel(capacity).0,
+            multiplayer: broadcast::channel(capacity).0,
+            events: broadcast::channel(capacity).0,
+            state: watch::channel(None).0,
+        }
+    }
+
+    fn next_request_id(&self) -> u32 {
+        self.next_request_id.fetch_add(1, Ordering::Relaxed)
+    }
+
+    /// get the reply to `request_id`, or without one, the next packet which `matches`
+    ///
+    /// if the connection is already closed, the receiver errors straight away
+    fn wait_for(&self, request_id: Option<u32>, matches: fn(&PacketId) -> bool) -> oneshot::Receiver<PacketId> {
+        let (response, receiver) = oneshot::channel();
+        let mut pending = self.pending.lock().unwrap();
+        if self.state.borrow().is_none() {
+            // clean up any which timed out
+            pending.retain(|p| !p.response.is_closed());
+            pending.push(Pending { request_id, matches, response });
+        }
+        receiver
+    }
+
+    /// give a packet to whoever is waiting for it, and to its event stream
+    fn dispatch(&self, Envelope { request_id, packet }: Envelope<PacketId>) {
+        {
+            let mut pending = self.pending.lock().unwrap();
+            let is_response = |p: &Pending| match p.request_id {
+                Some(id) => request_id == Some(id),
+                None => (p.matches)(&packet),
+            };
+            if let Some(i) = pending.iter().position(|p| !p.response.is_closed() && is_response(p)) {
+                let _ = pending.remove(i).response.send(packet.clone());
+            }
+        }
+
+        // errors just mean nobody is listening
+        match packet {
+            PacketId::Chat_Packet { packet } => { let _ = self.chat.send(packet); }
+            PacketId::Spectator_Packet { host_id, packet } => { let _ = self.spectator.send(SpectatorEvent { host_id, packet }); }
+            PacketId::Multiplayer_Packet { packet } => { let _ = self.multiplayer.send(packet); }
+            packet => { let _ = self.events.send(packet); }
+        }
+    }
+
+    /// mark the connection as closed (if it wasnt already), which fails every pending request
+    fn disconnect(&self, reason: DisconnectReason) {
+        let mut pending = self.pending.lock().unwrap();
+        self.state.send_if_modified(|state| {
+            if state.is_some() { return false }
+            *state = Some(reason);
+            true
+        });
+        pending.clear();
+    }
+
+    fn disconnect_reason(&self) -> DisconnectReason {
+        self.state.borrow().clone().unwrap_or(DisconnectReason::Closed)
+    }
+}
+
+
+async fn read_loop<R: AsyncRead + Unpin>(mut stream: R, mut decoder: PacketDecoder<Envelope<ServerToClient<PacketId>>>, shared: Arc<Shared>, outgoing: mpsc::UnboundedSender<Envelope<PacketId>>) {
+    let reason = loop {
+        match decoder.read_packet_async(&mut stream).await {
+            Ok(Some(Envelope { request_id, packet: PacketIdServerToClient::Ping })) => { let _ = outgoing.send(Envelope { request_id, packet: PacketId::Pong }); }
+            Ok(Some(Envelope { request_id, packet: PacketIdServerToClient::Server_DropConnection { reason, message } })) => {
+                shared.dispatch(Envelope { request_id, packet: PacketId::Server_DropConnection { reason, message: message.clone() } });
+                break DisconnectReason::Dropped { reason, message };
+            }
+            Ok(Some(packet)) => shared.dispatch(packet.map(PacketId::from)),
+            Ok(None) => break DisconnectReason::Closed,
+            Err(e) => break DisconnectReason::Error(e.to_string()),
+        }
+    };
+
+    shared.disconnect(reason);
+}
+
+async fn write_loop<W: AsyncWrite + Unpin>(mut stream: W, protocol: Protocol, mut outgoing: mpsc::UnboundedReceiver<Envelope<PacketId>>, shared: Arc<Shared>) {
+    // reused for every packet. writers are kept out of the awaits, since their sink isnt Send
+    let mut buffer = Vec::new();
+
+    while let Some(packet) = outgoing.recv().await {
+        buffer = {
+            let mut writer = SerializationWriter::from_vec(std::mem::take(&mut buffer)).with_protocol(protocol);
+            writer.write(&packet);
+            writer.data()
+        };
+
+        if let Err(e) = async {
+            stream.write_all(&buffer).await?;
+            stream.flush().await
+        }.await {
+            shared.disconnect(DisconnectReason::Error(e.to_string()));
+            break;
+        }
+
+        // nothing gets sent after a logout
+        if matches!(packet.packet, PacketId::Client_LogOut) { break }
+    }
+
+    let _ = stream.shutdown().await;
+}
+
+
+/// the server side of a connection, for testing the client against
+#[cfg(test)]
+struct TestServer {
+    stream: tokio::io::DuplexStream,
+    decoder: PacketDecoder<Envelope<PacketId>>,
+    protocol: Protocol,
+}
+#[cfg(test)]
+impl TestServer {
+    /// start a client logging in, and read its login
+    async fn start(options: ClientOptions) -> (Self, tokio::task::JoinHandle<ClientResult<TatakuClient>>) {
+        let (client, stream) = tokio::io::duplex(64 * 1024);
+        let login = tokio::spawn(options.login(client, "user", "pass", "tataku\n1.0"));
+        let mut server = Self { stream, decoder: PacketDecoder::new(), protocol: Protocol::LEGACY };
+
+        let Some(PacketId::Client_UserLogin { protocol_version, username, .. }) = server.read().await else { panic!("expected a login") };
+        assert_eq!(username, "user");
+        server.protocol = protocol_version.into();
+        server.decoder.set_protocol(server.protocol);
+        (server, login)
+    }
+
+    async fn read(&mut self) -> Option<PacketId> {
+        self.read_request().await.map(|e| e.packet)
+    }
+    async fn read_request(&mut self) -> Option<Envelope<PacketId>> {
+        self.decoder.read_packet_async(&mut self.stream).await.unwrap()
+    }
+
+    async fn send(&mut self, packet: PacketId) {
+        self.send_envelope(Envelope::new(packet)).await
+    }
+    /// answer a request read with [`Self::read_request`]
+    async fn reply(&mut self, request: &Envelope<PacketId>, packet: impl Into<PacketId>) {
+        self.send_envelope(Envelope { request_id: request.request_id, packet: packet.into() }).await
+    }
+    async fn send_envelope(&mut self, envelope: Envelope<PacketId>) {
+        let mut writer = SerializationWriter::new().with_protocol(self.protocol);
+        writer.write(&envelope);
+        self.stream.write_all(&writer.data()).await.unwrap();
+    }
+}
+
+#[tokio::test]
+async fn test_client() {
+    let (mut server, login) = TestServer::start(ClientOptions::default()).await;
+    assert_eq!(server.protocol, Protocol::CURRENT);
+    server.send(PacketId::Server_LoginResponse { status: LoginStatus::Ok, user_id: 5 }).await;
+    let client = login.await.unwrap().unwrap();
+    assert_eq!(client.user_id(), 5);
+
+    // pings are answered
+    server.send(PacketId::Ping).await;
+    assert!(matches!(server.read().await, Some(PacketId::Pong)));
+
+    // packets go to their subsystem's stream
+    let mut chat_events = client.chat_events();
+    let mut events = client.events();
+    server.send(ChatPacket::Server_SendMessage { sender_id: 2, channel: "#general".to_owned(), message: "hi".to_owned() }.into()).await;
+    server.send(PacketId::Server_UserLeft { user_id: 2 }).await;
+    assert!(matches!(chat_events.recv().await, Ok(ChatPacket::Server_SendMessage { sender_id: 2, .. })));
+    assert!(matches!(events.recv().await, Ok(PacketId::Server_UserLeft { user_id: 2 })));
+
+    // requests get their response
+    let mut lobby_events = client.multiplayer_events();
+    let lobby = FullLobbyInfo { id: 3, name: "lobby".to_owned(), host: 2, ..Default::default() };
+    let (joined, _) = tokio::join!(client.join_lobby(3, "pass"), async {
+        let request = server.read_request().await.unwrap();
+        let PacketId::Multiplayer_Packet { packet: MultiplayerPacket::Client_JoinLobby { lobby_id: 3, password } } = &request.packet else { panic!("expected a join") };
+        assert_eq!(password, "pass");
+        server.reply(&request, MultiplayerPacket::Server_JoinLobby { success: true, lobby: Some(lobby.clone()) }).await;
+    });
+    assert_eq!(joined.unwrap(), lobby);
+    assert!(matches!(lobby_events.recv().await, Ok(MultiplayerPacket::Server_JoinLobby { success: true, .. })));
+
+    let (joined, _) = tokio::join!(client.join_lobby(4, ""), async {
+        let request = server.read_request().await.unwrap();
+        server.reply(&request, MultiplayerPacket::Server_JoinLobby { success: false, lobby: None }).await;
+    });
+    assert!(matches!(joined, Err(ClientError::Rejected)));
+
+    // logging out closes the stream once the logout is sent
+    client.logout().await.unwrap();
+    assert!(matches!(server.read().await, Some(PacketId::Client_LogOut)));
+    assert!(server.read().await.is_none());
+}
+
+#[tokio::test]
+async fn test_client_request_ids() {
+    let (mut server, login) = TestServer::start(ClientOptions::default()).await;
+    server.send(PacketId::Server_LoginResponse { status: LoginStatus::Ok, user_id: 1 }).await;
+    let client = login.await.unwrap().unwrap();
+    let history = |message: &str| vec![ChatHistoryMessage { user_id: 2, username: "other".to_owned(), time: 0, message: message.to_owned() }];
+
+    // replies come back in a different order than the requests went out
+    let (general, osu, _) = tokio::join!(client.join_channel("#general", ""), client.join_channel("#osu", ""), async {
+        let first = server.read_request().await.unwrap();
+        let second = server.read_request().await.unwrap();
+        assert!(first.request_id.is_some() && first.request_id != second.request_id);
+
+        // a reply to nothing doesnt answer either request
+        server.send(ChatPacket::Server_JoinChannel { channel: "#lobby".to_owned(), previous_messages: Vec::new() }.into()).await;
+        for request in [second, first] {
+            let PacketId::Chat_Packet { packet: ChatPacket::Client_JoinChannel { channel, .. } } = &request.packet else { panic!("expected a join") };
+            let previous_messages = history(channel);
+            server.reply(&request, ChatPacket::Server_JoinChannel { channel: channel.clone(), previous_messages }).await;
+        }
+    });
+    assert_eq!(general.unwrap()[0].message, "#general");
+    assert_eq!(osu.unwrap()[0].message, "#osu");
+
+    // errors for a request fail it
+    let (result, _) = tokio::join!(client.spectate(2), async {
+        let request = server.read_request().await.unwrap();
+        server.reply(&request, PacketId::Server_Error { code: ServerErrorCode::CantSpectate, error: "no".to_owned() }).await;
+    });
+    assert!(matches!(result, Err(ClientError::Server { code: ServerErrorCode::CantSpectate, .. })));
+
+    // without request ids, the first matching packet is the reply
+    let (mut server, login) = TestServer::start(ClientOptions::default().with_protocol(Protocol::COMPACT)).await;
+    server.send(PacketId::Server_LoginResponse { status: LoginStatus::Ok, user_id: 1 }).await;
+    let client = login.await.unwrap().unwrap();
+    let (joined, _) = tokio::join!(client.join_channel("#general", ""), async {
+        let request = server.read_request().await.unwrap();
+        assert_eq!(request.request_id, None);
+        server.send(ChatPacket::Server_JoinChannel { channel: "#general".to_owned(), previous_messages: history("hi") }.into()).await;
+    });
+    assert_eq!(joined.unwrap()[0].message, "hi");
+}
+
+#[tokio::test]
+async fn test_client_login_failed() {
+    let (mut server, login) = TestServer::start(ClientOptions::default().with_protocol(Protocol::FRAMED)).await;
+    assert_eq!(server.protocol, Protocol::FRAMED);
+    server.send(PacketId::Server_LoginResponse { status: LoginStatus::BadPassword, user_id: 0 }).await;
+    assert!(matches!(login.await.unwrap(), Err(ClientError::Login(LoginStatus::BadPassword))));
+}
+
+#[tokio::test]
+async fn test_client_disconnect() {
+    let options = ClientOptions::default().with_request_timeout(Duration::from_millis(50));
+    let (mut server, login) = TestServer::start(options).await;
+    server.send(PacketId::Server_LoginResponse { status: LoginStatus::Ok, user_id: 1 }).await;
+    let client = login.await.unwrap().unwrap();
+
+    // the server never answers
+    assert!(matches!(client.lobby_list().await, Err(ClientError::Timeout)));
+    assert!(matches!(server.read().await, Some(PacketId::Multiplayer_Packet { packet: MultiplayerPacket::Client_LobbyList })));
+
+    server.send(PacketId::Server_DropConnection { reason: ServerDropReason::ServerClosing, message: "bye".to_owned() }).await;
+    assert!(matches!(client.disconnected().await, DisconnectReason::Dropped { reason: ServerDropReason::ServerClosing, .. }));
+    assert!(!client.is_connected());
+    assert!(matches!(client.friends().await, Err(ClientError::Disconnected(DisconnectReason::Dropped { .. }))));
+}
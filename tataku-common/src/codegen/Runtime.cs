@@ -0,0 +1,185 @@
+// ======= runtime =======
+// everything is little endian
+
+public class ProtocolException : Exception {
+    public ProtocolException(string message) : base(message) {}
+}
+
+public readonly record struct WireDuration(ulong Secs, uint Nanos);
+/// <summary>seconds since the unix epoch (negative if before it), nanoseconds always count forward</summary>
+public readonly record struct WireTime(long Secs, uint Nanos);
+public abstract record WireResult<T, E> {
+    public sealed record Ok(T Value) : WireResult<T, E>;
+    public sealed record Err(E Value) : WireResult<T, E>;
+}
+
+public sealed class TatakuReader {
+    private readonly byte[] data;
+    private readonly Stack<int> frames = new();
+    public int Offset;
+    /// <summary>should be true once protocol 2 or newer has been negotiated</summary>
+    public bool Framed;
+
+    public TatakuReader(byte[] data, bool framed = true) {
+        this.data = data;
+        Framed = framed;
+    }
+
+    /// <summary>where the readable data ends, either the end of the current frame or the end of the data</summary>
+    private int End => frames.Count > 0 ? frames.Peek() : data.Length;
+    private ReadOnlySpan<byte> Take(int len) {
+        if (len > End - Offset) throw new ProtocolException($"not enough data to read {len} bytes at offset {Offset}");
+        var span = data.AsSpan(Offset, len);
+        Offset += len;
+        return span;
+    }
+
+    public byte U8() => Take(1)[0];
+    public sbyte I8() => (sbyte)Take(1)[0];
+    public ushort U16() => BinaryPrimitives.ReadUInt16LittleEndian(Take(2));
+    public short I16() => BinaryPrimitives.ReadInt16LittleEndian(Take(2));
+    public uint U32() => BinaryPrimitives.ReadUInt32LittleEndian(Take(4));
+    public int I32() => BinaryPrimitives.ReadInt32LittleEndian(Take(4));
+    public ulong U64() => BinaryPrimitives.ReadUInt64LittleEndian(Take(8));
+    public long I64() => BinaryPrimitives.ReadInt64LittleEndian(Take(8));
+    public UInt128 U128() => BinaryPrimitives.ReadUInt128LittleEndian(Take(16));
+    public Int128 I128() => BinaryPrimitives.ReadInt128LittleEndian(Take(16));
+    public Half F16() => BinaryPrimitives.ReadHalfLittleEndian(Take(2));
+    public float F32() => BinaryPrimitives.ReadSingleLittleEndian(Take(4));
+    public double F64() => BinaryPrimitives.ReadDoubleLittleEndian(Take(8));
+    public bool Bool() => U8() != 0;
+    public string Char() => char.ConvertFromUtf32((int)U32());
+    public object? Unit() => null;
+    public string String() => Encoding.UTF8.GetString(Take(Len()));
+    public WireDuration Duration() => new(U64(), U32());
+    public WireTime SystemTime() => new(I64(), U32());
+    public IPAddress Ipv4Addr() => new(Take(4));
+    public IPAddress Ipv6Addr() => new(Take(16));
+    public IPAddress IpAddr() => U8() switch {
+        0 => Ipv4Addr(),
+        1 => Ipv6Addr(),
+        var id => throw new ProtocolException($"unknown IpAddr variant {id}"),
+    };
+
+    /// <summary>a length or item count, which is written as a u64</summary>
+    public int Len() {
+        var len = U64();
+        if (len > (ulong)(End - Offset)) throw new ProtocolException($"length {len} is longer than the remaining data");
+        return (int)len;
+    }
+    public List<T> List<T>(Func<T> item) {
+        var len = Len();
+        var list = new List<T>(len);
+        for (var i = 0; i < len; i++) list.Add(item());
+        return list;
+    }
+    public T[] Array<T>(int len, Func<T> item) {
+        var array = new T[len];
+        for (var i = 0; i < len; i++) array[i] = item();
+        return array;
+    }
+    public Dictionary<K, V> Map<K, V>(Func<K> key, Func<V> value) where K : notnull {
+        var len = Len();
+        var map = new Dictionary<K, V>(len);
+        for (var i = 0; i < len; i++) {
+            var k = key();
+            map[k] = value();
+        }
+        return map;
+    }
+    public WireResult<T, E> Result<T, E>(Func<T> ok, Func<E> err) =>
+        U8() == 0 ? new WireResult<T, E>.Ok(ok()) : new WireResult<T, E>.Err(err());
+
+    /// <summary>read the payload length of a packet frame, if the protocol is framed</summary>
+    public int? BeginFrame() {
+        if (!Framed) return null;
+        var end = Offset + Len();
+        frames.Push(end);
+        return end;
+    }
+    /// <summary>skip anything left in the frame (ie fields added by a newer protocol)</summary>
+    public void EndFrame(int? end) {
+        if (end is not int e) return;
+        frames.Pop();
+        Offset = e;
+    }
+}
+
+public sealed class TatakuWriter {
+    private readonly MemoryStream data = new();
+    /// <summary>should be true once protocol 2 or newer has been negotiated</summary>
+    public bool Framed;
+
+    public TatakuWriter(bool framed = true) {
+        Framed = framed;
+    }
+
+    /// <summary>the written data</summary>
+    public byte[] Finish() => data.ToArray();
+
+    private void Put(ReadOnlySpan<byte> bytes) => data.Write(bytes);
+
+    public void U8(byte v) => data.WriteByte(v);
+    public void I8(sbyte v) => data.WriteByte((byte)v);
+    public void U16(ushort v) { Span<byte> b = stackalloc byte[2]; BinaryPrimitives.WriteUInt16LittleEndian(b, v); Put(b); }
+    public void I16(short v) { Span<byte> b = stackalloc byte[2]; BinaryPrimitives.WriteInt16LittleEndian(b, v); Put(b); }
+    public void U32(uint v) { Span<byte> b = stackalloc byte[4]; BinaryPrimitives.WriteUInt32LittleEndian(b, v); Put(b); }
+    public void I32(int v) { Span<byte> b = stackalloc byte[4]; BinaryPrimitives.WriteInt32LittleEndian(b, v); Put(b); }
+    public void U64(ulong v) { Span<byte> b = stackalloc byte[8]; BinaryPrimitives.WriteUInt64LittleEndian(b, v); Put(b); }
+    public void I64(long v) { Span<byte> b = stackalloc byte[8]; BinaryPrimitives.WriteInt64LittleEndian(b, v); Put(b); }
+    public void U128(UInt128 v) { Span<byte> b = stackalloc byte[16]; BinaryPrimitives.WriteUInt128LittleEndian(b, v); Put(b); }
+    public void I128(Int128 v) { Span<byte> b = stackalloc byte[16]; BinaryPrimitives.WriteInt128LittleEndian(b, v); Put(b); }
+    public void F16(Half v) { Span<byte> b = stackalloc byte[2]; BinaryPrimitives.WriteHalfLittleEndian(b, v); Put(b); }
+    public void F32(float v) { Span<byte> b = stackalloc byte[4]; BinaryPrimitives.WriteSingleLittleEndian(b, v); Put(b); }
+    public void F64(double v) { Span<byte> b = stackalloc byte[8]; BinaryPrimitives.WriteDoubleLittleEndian(b, v); Put(b); }
+    public void Bool(bool v) => U8(v ? (byte)1 : (byte)0);
+    public void Char(string v) => U32((uint)char.ConvertToUtf32(v, 0));
+    public void Unit(object? v) {}
+    public void String(string v) {
+        var bytes = Encoding.UTF8.GetBytes(v);
+        U64((ulong)bytes.Length);
+        Put(bytes);
+    }
+    public void Duration(WireDuration v) { U64(v.Secs); U32(v.Nanos); }
+    public void SystemTime(WireTime v) { I64(v.Secs); U32(v.Nanos); }
+    public void Ipv4Addr(IPAddress v) => Put(v.GetAddressBytes());
+    public void Ipv6Addr(IPAddress v) => Put(v.GetAddressBytes());
+    public void IpAddr(IPAddress v) {
+        U8(v.AddressFamily == AddressFamily.InterNetworkV6 ? (byte)1 : (byte)0);
+        Put(v.GetAddressBytes());
+    }
+
+    public void List<T>(ICollection<T> v, Action<T> item) {
+        U64((ulong)v.Count);
+        foreach (var i in v) item(i);
+    }
+    public void Array<T>(T[] v, Action<T> item) {
+        foreach (var i in v) item(i);
+    }
+    public void Map<K, V>(Dictionary<K, V> v, Action<K> key, Action<V> value) where K : notnull {
+        U64((ulong)v.Count);
+        foreach (var (k, val) in v) { key(k); value(val); }
+    }
+    public void Result<T, E>(WireResult<T, E> v, Action<T> ok, Action<E> err) {
+        switch (v) {
+            case WireResult<T, E>.Ok o: U8(0); ok(o.Value); break;
+            case WireResult<T, E>.Err e: U8(1); err(e.Value); break;
+        }
+    }
+
+    /// <summary>reserve space for the payload length of a packet frame, if the protocol is framed</summary>
+    public long? BeginFrame() {
+        if (!Framed) return null;
+        var start = data.Position;
+        U64(0);
+        return start;
+    }
+    /// <summary>fill in the payload length reserved by BeginFrame</summary>
+    public void EndFrame(long? start) {
+        if (start is not long s) return;
+        var end = data.Position;
+        data.Position = s;
+        U64((ulong)(end - s - 8));
+        data.Position = end;
+    }
+}
@@ -0,0 +1,329 @@
+use std::fmt::Write;
+use std::collections::HashSet;
+use super::*;
+
+const RUNTIME: &str = include_str!("Runtime.cs");
+
+/// generate c# types, plus `Protocol.ReadX(r)`/`Protocol.WriteX(w, value)` for each of them, for `roots` and every type they use
+///
+/// needs .net 7 or newer
+pub fn generate_csharp(roots: &[SchemaFn], namespace: &str) -> CodegenResult<String> {
+    let types = named_types(roots)?;
+    let mut out = String::new();
+    let _ = writeln!(out, "// generated from the tataku-common protocol schema, don't edit by hand");
+    let _ = writeln!(out, "#nullable enable");
+    for using in ["System", "System.Buffers.Binary", "System.Collections.Generic", "System.IO", "System.Net", "System.Net.Sockets", "System.Text"] {
+        let _ = writeln!(out, "using {using};");
+    }
+    let _ = writeln!(out, "\nnamespace {namespace};\n");
+    out.push_str(RUNTIME);
+
+    let mut generator = Generator { namespace, shadowed: HashSet::new() };
+
+    // type definitions
+    for ty in types.iter() {
+        out.push('\n');
+        match &ty.kind {
+            SchemaKind::Struct { versioned, current_version, fields } => generator.struct_type(&mut out, &ty.name, *versioned && current_version.is_none(), fields)?,
+            SchemaKind::Enum { id_type, variants, .. } => generator.enum_type(&mut out, &ty.name, id_type, variants)?,
+            SchemaKind::Bitmask { id_type, flags } => {
+                let _ = writeln!(out, "[Flags]\npublic enum {} : {} {{", ty.name, primitive_type(id_type)?);
+                let _ = writeln!(out, "    None = 0,");
+                for (flag, value) in flags {
+                    let _ = writeln!(out, "    {flag} = {value},");
+                }
+                let _ = writeln!(out, "}}");
+            }
+            _ => {}
+        }
+    }
+
+    // readers and writers
+    let _ = writeln!(out, "\npublic static class Protocol {{");
+    for ty in types.iter() {
+        generator.shadowed.clear();
+        match &ty.kind {
+            SchemaKind::Struct { versioned, current_version, fields } => generator.struct_fns(&mut out, &ty.name, *versioned, *current_version, fields)?,
+            SchemaKind::Enum { id_type, framed, strict, fallback, variants } => generator.enum_fns(&mut out, &ty.name, id_type, *framed, fallback_variant(*strict, *fallback, variants), variants)?,
+            SchemaKind::Bitmask { id_type, .. } => {
+                let cs_type = primitive_type(id_type)?;
+                let method = pascal_case(primitive_method(id_type)?);
+                let name = &ty.name;
+                let _ = writeln!(out, "    public static {name} Read{name}(TatakuReader r) => ({name})r.{method}();");
+                let _ = writeln!(out, "    public static void Write{name}(TatakuWriter w, {name} value) => w.{method}(({cs_type})value);");
+            }
+            _ => {}
+        }
+    }
+    let _ = writeln!(out, "}}");
+
+    Ok(out)
+}
+
+struct Generator<'a> {
+    namespace: &'a str,
+    /// type names which are hidden by a nested variant record with the same name
+    shadowed: HashSet<String>,
+}
+impl Generator<'_> {
+    fn struct_type(&mut self, out: &mut String, name: &str, keep_version: bool, fields: &[FieldSchema]) -> CodegenResult<()> {
+        let _ = writeln!(out, "public sealed class {name} {{");
+        if keep_version { let _ = writeln!(out, "    public ushort Version {{ get; set; }}"); }
+        for f in fields.iter().filter(|f| is_current(f)) {
+            out.push_str(&docs(f.docs, "    "));
+            let schema = (f.ty)();
+            let ty = self.cs_type(&schema)?;
+            // fields added later arent read from older versions, so they need a default which can be written back
+            let default = default_value(&schema).unwrap_or("default!");
+            let _ = writeln!(out, "    public {ty} {} {{ get; set; }} = {default};", member_name(f.name, name));
+        }
+        let _ = writeln!(out, "}}");
+        Ok(())
+    }
+
+    fn struct_fns(&mut self, out: &mut String, name: &str, versioned: bool, current_version: Option<u16>, fields: &[FieldSchema]) -> CodegenResult<()> {
+        let keep_version = versioned && current_version.is_none();
+
+        // read
+        let _ = writeln!(out, "    public static {name} Read{name}(TatakuReader r) {{");
+        if versioned {
+            let _ = writeln!(out, "        var version = r.U16();");
+        } else if fields.iter().any(|f| live_condition(f).is_some()) {
+            let _ = writeln!(out, "        const ushort version = 0;");
+        }
+        let _ = writeln!(out, "        var value = new {name}();");
+        if keep_version { let _ = writeln!(out, "        value.Version = version;"); }
+        for f in fields {
+            let read = self.read(&(f.ty)())?;
+            // old layouts are read to skip them, since they cant be converted
+            let statement = if is_current(f) { format!("value.{} = {read};", member_name(f.name, name)) } else { format!("_ = {read}; // old {}", f.name) };
+            match live_condition(f) {
+                Some(condition) => { let _ = writeln!(out, "        if ({condition}) {statement}"); }
+                None => { let _ = writeln!(out, "        {statement}"); }
+            }
+        }
+        let _ = writeln!(out, "        return value;");
+        let _ = writeln!(out, "    }}");
+
+        // write
+        let _ = writeln!(out, "    public static void Write{name}(TatakuWriter w, {name} value) {{");
+        match current_version {
+            Some(current) => { let _ = writeln!(out, "        const ushort version = {current};\n        w.U16(version);"); }
+            None if versioned => { let _ = writeln!(out, "        var version = value.Version;\n        w.U16(version);"); }
+            None => {}
+        }
+        for f in fields.iter().filter(|f| is_current(f)) {
+            let write = self.write(&(f.ty)(), &format!("value.{}", member_name(f.name, name)), 0)?;
+            match live_condition(f).filter(|_| keep_version) {
+                Some(condition) => { let _ = writeln!(out, "        if ({condition}) {write}"); }
+                None => { let _ = writeln!(out, "        {write}"); }
+            }
+        }
+        let _ = writeln!(out, "    }}");
+        Ok(())
+    }
+
+    fn enum_type(&mut self, out: &mut String, name: &str, id_type: &str, variants: &[VariantSchema]) -> CodegenResult<()> {
+        // plain enums are a c# enum, others are a record per variant
+        if is_plain_enum(variants) {
+            let _ = writeln!(out, "public enum {name} : {} {{", primitive_type(id_type)?);
+            for v in variants {
+                out.push_str(&docs(v.docs, "    "));
+                let _ = writeln!(out, "    {} = {},", v.name, v.id);
+            }
+            let _ = writeln!(out, "}}");
+            return Ok(())
+        }
+
+        self.shadowed = variants.iter().map(|v| v.name.to_owned()).collect();
+        let _ = writeln!(out, "public abstract record {name} {{");
+        for v in variants {
+            out.push_str(&docs(v.docs, "    "));
+            let fields = v.fields.iter()
+                .map(|f| Ok(format!("{} {}", self.cs_type(&(f.ty)())?, member_name(f.name, v.name))))
+                .collect::<CodegenResult<Vec<_>>>()?;
+            let _ = writeln!(out, "    public sealed record {}({}) : {name};", v.name, fields.join(", "));
+        }
+        let _ = writeln!(out, "}}");
+        self.shadowed.clear();
+        Ok(())
+    }
+
+    fn enum_fns(
+        &mut self,
+        out: &mut String,
+        name: &str,
+        id_type: &str,
+        framed: bool,
+        fallback: Option<&VariantSchema>,
+        variants: &[VariantSchema]
+    ) -> CodegenResult<()> {
+        let plain = is_plain_enum(variants);
+        let id_method = pascal_case(primitive_method(id_type)?);
+        let id_type = primitive_type(id_type)?;
+        let value = |this: &mut Self, v: &VariantSchema| -> CodegenResult<String> {
+            if plain { return Ok(format!("{name}.{}", v.name)) }
+            let fields = v.fields.iter().map(|f| this.read(&(f.ty)())).collect::<CodegenResult<Vec<_>>>()?;
+            Ok(format!("new {name}.{}({})", v.name, fields.join(", ")))
+        };
+
+        // read
+        let _ = writeln!(out, "    public static {name} Read{name}(TatakuReader r) {{");
+        let _ = writeln!(out, "        var id = r.{id_method}();");
+        if framed { let _ = writeln!(out, "        var frame = r.BeginFrame();"); }
+        let _ = writeln!(out, "        {name} value;");
+        let _ = writeln!(out, "        switch (id) {{");
+        for v in variants {
+            let _ = writeln!(out, "            case {}: value = {}; break;", v.id, value(self, v)?);
+        }
+            match fallback {
+            Some(v) => { let _ = writeln!(out, "            default: value = {}; break;", value(self, v)?); }
+            None => { let _ = writeln!(out, "            default: throw new ProtocolException($\"unknown {name} variant {{id}}\");"); }
+        }
+        let _ = writeln!(out, "        }}");
+        if framed { let _ = writeln!(out, "        r.EndFrame(frame);"); }
+        let _ = writeln!(out, "        return value;");
+        let _ = writeln!(out, "    }}");
+
+        // write
+        let _ = writeln!(out, "    public static void Write{name}(TatakuWriter w, {name} value) {{");
+        let _ = writeln!(out, "        switch (value) {{");
+        for v in variants {
+            if plain {
+                let _ = writeln!(out, "            case {name}.{}: {{", v.name);
+            } else {
+                let _ = writeln!(out, "            case {name}.{} v: {{", v.name);
+            }
+            let _ = writeln!(out, "                w.{id_method}(({id_type}){});", v.id);
+            if framed { let _ = writeln!(out, "                var frame = w.BeginFrame();"); }
+            for f in v.fields.iter() {
+                let _ = writeln!(out, "                {}", self.write(&(f.ty)(), &format!("v.{}", member_name(f.name, v.name)), 0)?);
+            }
+            if framed { let _ = writeln!(out, "                w.EndFrame(frame);"); }
+            let _ = writeln!(out, "                break;");
+            let _ = writeln!(out, "            }}");
+        }
+        let _ = writeln!(out, "        }}");
+        let _ = writeln!(out, "    }}");
+        Ok(())
+    }
+
+
+    fn cs_type(&self, ty: &TypeSchema) -> CodegenResult<String> {
+        Ok(match &ty.kind {
+            SchemaKind::Primitive { .. } => primitive_type(&ty.name)?.to_owned(),
+            SchemaKind::List { item } => format!("List<{}>", self.cs_type(&item())?),
+            SchemaKind::Array { item, .. } => format!("{}[]", self.cs_type(&item())?),
+            SchemaKind::Map { key, value } => format!("Dictionary<{}, {}>", self.cs_type(&key())?, self.cs_type(&value())?),
+            SchemaKind::Option { item } => format!("{}?", self.cs_type(&item())?),
+            SchemaKind::Result { ok, err } => format!("WireResult<{}, {}>", self.cs_type(&ok())?, self.cs_type(&err())?),
+            SchemaKind::Tuple { items } => format!("({})", items.iter().map(|i| self.cs_type(&i())).collect::<CodegenResult<Vec<_>>>()?.join(", ")),
+            SchemaKind::Struct { .. } | SchemaKind::Enum { .. } | SchemaKind::Bitmask { .. } => self.type_name(&ty.name),
+        })
+    }
+
+    /// variant records can have the same name as a type, so those types need their full name
+    fn type_name(&self, name: &str) -> String {
+        if self.shadowed.contains(name) { format!("global::{}.{name}", self.namespace) } else { name.to_owned() }
+    }
+
+    /// an expression which reads `ty` from `r`
+    fn read(&self, ty: &TypeSchema) -> CodegenResult<String> {
+        Ok(match &ty.kind {
+            SchemaKind::Primitive { .. } => format!("r.{}()", pascal_case(primitive_method(&ty.name)?)),
+            SchemaKind::List { item } => format!("r.List(() => {})", self.read(&item())?),
+            SchemaKind::Array { item, len } => format!("r.Array({len}, () => {})", self.read(&item())?),
+            SchemaKind::Map { key, value } => format!("r.Map(() => {}, () => {})", self.read(&key())?, self.read(&value())?),
+            SchemaKind::Option { item } => {
+                let item = item();
+                format!("(r.Bool() ? ({}?){} : null)", self.cs_type(&item)?, self.read(&item)?)
+            }
+            SchemaKind::Result { ok, err } => format!("r.Result(() => {}, () => {})", self.read(&ok())?, self.read(&err())?),
+            SchemaKind::Tuple { items } => format!("({})", items.iter().map(|i| self.read(&i())).collect::<CodegenResult<Vec<_>>>()?.join(", ")),
+            SchemaKind::Struct { .. } | SchemaKind::Enum { .. } | SchemaKind::Bitmask { .. } => format!("Protocol.Read{}(r)", ty.name),
+        })
+    }
+
+    /// a statement which writes `value` (of type `ty`) to `w`
+    fn write(&self, ty: &TypeSchema, value: &str, depth: usize) -> CodegenResult<String> {
+        let x = format!("x{depth}");
+        let inner = |item: &SchemaFn, value: &str| self.write(&item(), value, depth + 1);
+
+        Ok(match &ty.kind {
+            SchemaKind::Primitive { .. } => format!("w.{}({value});", pascal_case(primitive_method(&ty.name)?)),
+            SchemaKind::List { item } => format!("w.List({value}, {x} => {{ {} }});", inner(item, &x)?),
+            SchemaKind::Array { item, .. } => format!("w.Array({value}, {x} => {{ {} }});", inner(item, &x)?),
+            SchemaKind::Map { key, value: v } => format!(
+                "w.Map({value}, k{depth} => {{ {} }}, v{depth} => {{ {} }});",
+                inner(key, &format!("k{depth}"))?,
+                inner(v, &format!("v{depth}"))?
+            ),
+            // braces so the pattern variable doesnt leak into the rest of the method
+            SchemaKind::Option { item } => format!(
+                "{{ if ({value} is {{}} {x}) {{ w.Bool(true); {} }} else {{ w.Bool(false); }} }}",
+                inner(item, &x)?
+            ),
+            SchemaKind::Result { ok, err } => format!(
+                "w.Result({value}, ok{depth} => {{ {} }}, err{depth} => {{ {} }});",
+                inner(ok, &format!("ok{depth}"))?,
+                inner(err, &format!("err{depth}"))?
+            ),
+            SchemaKind::Tuple { items } => {
+                let writes = items.iter().enumerate()
+                    .map(|(i, item)| inner(item, &format!("{x}.Item{}", i + 1)))
+                    .collect::<CodegenResult<Vec<_>>>()?;
+                format!("{{ var {x} = {value}; {} }}", writes.join(" "))
+            }
+            SchemaKind::Struct { .. } | SchemaKind::Enum { .. } | SchemaKind::Bitmask { .. } => format!("Protocol.Write{}(w, {value});", ty.name),
+        })
+    }
+}
+
+/// c# members cant have the same name as their type
+fn member_name(field: &str, owner: &str) -> String {
+    let name = pascal_case(field);
+    if name == owner { name + "Value" } else { name }
+}
+
+/// the default value of `ty`, if `default` would be null
+fn default_value(ty: &TypeSchema) -> Option<&'static str> {
+    match &ty.kind {
+        SchemaKind::Primitive { .. } => match primitive_method(&ty.name).ok()? {
+            "string" => Some("\"\""),
+            "ipAddr" | "ipv4Addr" => Some("IPAddress.Any"),
+            "ipv6Addr" => Some("IPAddress.IPv6Any"),
+            _ => None,
+        },
+        SchemaKind::List { .. } | SchemaKind::Map { .. } | SchemaKind::Struct { .. } => Some("new()"),
+        _ => None,
+    }
+}
+
+fn primitive_type(name: &str) -> CodegenResult<&'static str> {
+    Ok(match primitive_method(name)? {
+        "u8" => "byte",
+        "i8" => "sbyte",
+        "u16" => "ushort",
+        "i16" => "short",
+        "u32" => "uint",
+        "i32" => "int",
+        "u64" => "ulong",
+        "i64" => "long",
+        "u128" => "UInt128",
+        "i128" => "Int128",
+        "f16" => "Half",
+        "f32" => "float",
+        "f64" => "double",
+        "bool" => "bool",
+        "unit" => "object?",
+        "duration" => "WireDuration",
+        "systemTime" => "WireTime",
+        "ipAddr" | "ipv4Addr" | "ipv6Addr" => "IPAddress",
+        _ => "string",
+    })
+}
+
+fn docs(docs: &str, indent: &str) -> String {
+    if docs.is_empty() { return String::new() }
+    format!("{indent}/// <summary>\n{}{indent}/// </summary>\n", doc_lines(&docs.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;"), indent, "/// "))
+}
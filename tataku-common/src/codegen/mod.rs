@@ -0,0 +1,157 @@
+//! generates readers and writers for other languages from the [`WireSchema`] of each type
+//!
+//! ```no_run
+//! let ts = tataku_common::codegen::generate_typescript(tataku_common::packets::PROTOCOL_SCHEMA).unwrap();
+//! std::fs::write("protocol.ts", ts).unwrap();
+//! ```
+//!
+//! or run the `tataku-codegen` binary
+mod csharp;
+mod typescript;
+
+pub use csharp::*;
+pub use typescript::*;
+
+use crate::serialization::*;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum CodegenError {
+    /// a primitive type the runtime doesn't know how to read or write
+    UnsupportedType(String),
+}
+impl core::fmt::Display for CodegenError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::UnsupportedType(name) => write!(f, "don't know how to generate code for type {name}"),
+        }
+    }
+}
+impl std::error::Error for CodegenError {}
+
+pub type CodegenResult<T> = Result<T, CodegenError>;
+
+
+/// the runtime method which reads/writes a primitive (in camelCase)
+fn primitive_method(name: &str) -> CodegenResult<&'static str> {
+    Ok(match name {
+        "u8" => "u8",
+        "i8" => "i8",
+        "u16" => "u16",
+        "i16" => "i16",
+        "u32" => "u32",
+        "i32" => "i32",
+        "u64" | "usize" => "u64",
+        "i64" => "i64",
+        "u128" => "u128",
+        "i128" => "i128",
+        "f16" => "f16",
+        "f32" => "f32",
+        "f64" => "f64",
+        "bool" => "bool",
+        "char" => "char",
+        "()" => "unit",
+        "Duration" => "duration",
+        "SystemTime" => "systemTime",
+        "IpAddr" => "ipAddr",
+        "Ipv4Addr" => "ipv4Addr",
+        "Ipv6Addr" => "ipv6Addr",
+        // these are written as strings
+        "String" | "Md5Hash" | "MapGame" => "string",
+        _ => return Err(CodegenError::UnsupportedType(name.to_owned())),
+    })
+}
+
+/// the types which get their own definition (structs, enums and bitmasks)
+///
+/// errors if any primitive isn't supported by the runtime
+fn named_types(roots: &[SchemaFn]) -> CodegenResult<Vec<TypeSchema>> {
+    let types = collect_schemas(roots);
+    for t in types.iter().filter(|t| matches!(t.kind, SchemaKind::Primitive { .. })) {
+        primitive_method(&t.name)?;
+    }
+
+    Ok(types.into_iter()
+        .filter(|t| matches!(t.kind, SchemaKind::Struct { .. } | SchemaKind::Enum { .. } | SchemaKind::Bitmask { .. }))
+        .collect())
+}
+
+/// the variant unknown ids are read as, if there is one
+fn fallback_variant<'a>(strict: bool, fallback: Option<&str>, variants: &'a [VariantSchema]) -> Option<&'a VariantSchema> {
+    if strict { return None }
+    // variants with fields cant be made up
+    fallback.and_then(|f| variants.iter().find(|v| v.name == f)).filter(|v| v.fields.is_empty())
+}
+
+/// condition for a field being in `version`. the syntax is the same in every language we generate
+fn live_condition(field: &FieldSchema) -> Option<String> {
+    match (field.added, field.removed) {
+        (0, None) => None,
+        (0, Some(removed)) => Some(format!("version < {removed}")),
+        (added, None) => Some(format!("version >= {added}")),
+        (added, Some(removed)) => Some(format!("version >= {added} && version < {removed}")),
+    }
+}
+
+/// split a struct's fields into the ones on the struct, and the layouts which are only read to skip them
+fn is_current(field: &FieldSchema) -> bool {
+    field.removed.is_none()
+}
+
+/// do none of the enum's variants have fields
+fn is_plain_enum(variants: &[VariantSchema]) -> bool {
+    variants.iter().all(|v| v.fields.is_empty())
+}
+
+fn pascal_case(name: &str) -> String {
+    name.split('_')
+        .filter(|s| !s.is_empty())
+        .map(|s| {
+            let mut c = s.chars();
+            c.next().map(|f| f.to_uppercase().chain(c).collect::<String>()).unwrap_or_default()
+        })
+        .collect()
+}
+
+/// doc comment lines for `docs`, with `prefix` in front of each line
+fn doc_lines(docs: &str, indent: &str, prefix: &str) -> String {
+    docs.lines().map(|l| format!("{indent}{prefix}{l}").trim_end().to_owned() + "\n").collect()
+}
+
+
+#[test]
+fn test_codegen() {
+    use crate::packets::PROTOCOL_SCHEMA;
+
+    let ts = generate_typescript(PROTOCOL_SCHEMA).unwrap();
+    assert!(ts.contains("export type PacketId ="));
+    assert!(ts.contains("{ type: \"Client_UserLogin\"; protocol_version: number; username: string; password: string; game: string }"));
+    assert!(ts.contains("case 100:"));
+    assert!(ts.contains("export interface Score {"));
+    assert!(ts.contains("time?: bigint;"));
+    // a score read from an old version doesnt have the newer fields, but can still be written as the current version
+    assert!(ts.contains("if (version < 9) r.f64(); // old accuracy"));
+    assert!(ts.contains("w.u64(value.time ?? 0n);"));
+    assert!(ts.contains("w.f32(value.accuracy ?? 0);"));
+    assert!(ts.contains("w.map(value.judgments ?? new Map(), "));
+    assert!(ts.contains("w.option(value.replay, "));
+    assert!(!ts.contains("!)"));
+    assert!(ts.contains("export type ServerPermissions = (\"Bot\" | \"Donator\" | \"Moderator\" | \"ChatOnly\")[];"));
+
+    let cs = generate_csharp(PROTOCOL_SCHEMA, "Tataku.Protocol").unwrap();
+    assert!(cs.contains("namespace Tataku.Protocol;"));
+    assert!(cs.contains("public abstract record PacketId {"));
+    assert!(cs.contains("public sealed record Client_UserLogin(ushort ProtocolVersion, string Username, string Password, string Game) : PacketId;"));
+    assert!(cs.contains("[Flags]\npublic enum ServerPermissions : ushort {"));
+    assert!(cs.contains("public enum LoginStatus : byte {"));
+    assert!(cs.contains("if (version < 9) _ = r.F64(); // old accuracy"));
+    assert!(cs.contains("public Dictionary<string, ushort> Judgments { get; set; } = new();"));
+    assert!(cs.contains("public List<ModDefinition> Mods { get; set; } = new();"));
+    assert!(cs.contains("public string Username { get; set; } = \"\";"));
+
+    // every primitive needs to be known by the runtime
+    struct Custom;
+    impl WireSchema for Custom {
+        fn schema() -> TypeSchema { TypeSchema::primitive("Custom", "") }
+    }
+    assert_eq!(generate_typescript(&[<Vec<Option<Custom>>>::schema]), Err(CodegenError::UnsupportedType("Custom".to_owned())));
+}
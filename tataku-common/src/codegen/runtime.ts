@@ -0,0 +1,223 @@
+// ======= runtime =======
+// everything is little endian
+
+export class ProtocolError extends Error {}
+
+export interface Duration { secs: bigint; nanos: number }
+/** seconds since the unix epoch (negative if before it), nanoseconds always count forward */
+export interface SystemTime { secs: bigint; nanos: number }
+export type Result<T, E> = { ok: T } | { err: E };
+
+export class Reader {
+    offset = 0;
+    private view: DataView;
+    private frames: number[] = [];
+
+    /** `framed` should be true once protocol 2 or newer has been negotiated */
+    constructor(public data: Uint8Array, public framed = true) {
+        this.view = new DataView(data.buffer, data.byteOffset, data.byteLength);
+    }
+
+    /** where the readable data ends, either the end of the current frame or the end of the data */
+    private end(): number {
+        return this.frames.length ? this.frames[this.frames.length - 1] : this.data.length;
+    }
+    private take(len: number): number {
+        const offset = this.offset;
+        if (offset + len > this.end()) throw new ProtocolError(`not enough data to read ${len} bytes at offset ${offset}`);
+        this.offset += len;
+        return offset;
+    }
+
+    u8(): number { return this.view.getUint8(this.take(1)); }
+    i8(): number { return this.view.getInt8(this.take(1)); }
+    u16(): number { return this.view.getUint16(this.take(2), true); }
+    i16(): number { return this.view.getInt16(this.take(2), true); }
+    u32(): number { return this.view.getUint32(this.take(4), true); }
+    i32(): number { return this.view.getInt32(this.take(4), true); }
+    u64(): bigint { return this.view.getBigUint64(this.take(8), true); }
+    i64(): bigint { return this.view.getBigInt64(this.take(8), true); }
+    u128(): bigint { const lo = this.u64(); return lo | (this.u64() << 64n); }
+    i128(): bigint { return BigInt.asIntN(128, this.u128()); }
+    f32(): number { return this.view.getFloat32(this.take(4), true); }
+    f64(): number { return this.view.getFloat64(this.take(8), true); }
+    f16(): number {
+        const bits = this.u16();
+        const sign = bits & 0x8000 ? -1 : 1;
+        const exp = (bits >> 10) & 0x1f;
+        const frac = bits & 0x3ff;
+        if (exp === 0) return sign * frac * 2 ** -24;
+        if (exp === 0x1f) return frac ? NaN : sign * Infinity;
+        return sign * (1 + frac / 1024) * 2 ** (exp - 15);
+    }
+    bool(): boolean { return this.u8() !== 0; }
+    char(): string { return String.fromCodePoint(this.u32()); }
+    unit(): null { return null; }
+    string(): string {
+        const len = this.len();
+        const offset = this.take(len);
+        return new TextDecoder().decode(this.data.subarray(offset, offset + len));
+    }
+    duration(): Duration { return { secs: this.u64(), nanos: this.u32() }; }
+    systemTime(): SystemTime { return { secs: this.i64(), nanos: this.u32() }; }
+    ipv4Addr(): string { return Array.from({ length: 4 }, () => this.u8()).join("."); }
+    ipv6Addr(): string {
+        return Array.from({ length: 8 }, () => {
+            const hi = this.u8();
+            return ((hi << 8) | this.u8()).toString(16);
+        }).join(":");
+    }
+    ipAddr(): string {
+        const id = this.u8();
+        if (id === 0) return this.ipv4Addr();
+        if (id === 1) return this.ipv6Addr();
+        throw new ProtocolError(`unknown IpAddr variant ${id}`);
+    }
+
+    /** a length or item count, which is written as a u64 */
+    len(): number {
+        const len = Number(this.u64());
+        if (len > this.end() - this.offset) throw new ProtocolError(`length ${len} is longer than the remaining data`);
+        return len;
+    }
+    list<T>(item: () => T): T[] {
+        return Array.from({ length: this.len() }, item);
+    }
+    array<T>(len: number, item: () => T): T[] {
+        return Array.from({ length: len }, item);
+    }
+    map<K, V>(key: () => K, value: () => V): Map<K, V> {
+        const map = new Map<K, V>();
+        for (let i = this.len(); i > 0; i--) {
+            const k = key();
+            map.set(k, value());
+        }
+        return map;
+    }
+    option<T>(item: () => T): T | undefined {
+        return this.bool() ? item() : undefined;
+    }
+    result<T, E>(ok: () => T, err: () => E): Result<T, E> {
+        return this.u8() === 0 ? { ok: ok() } : { err: err() };
+    }
+
+    /** read the payload length of a packet frame, if the protocol is framed */
+    beginFrame(): number | undefined {
+        if (!this.framed) return undefined;
+        const len = this.len();
+        const end = this.offset + len;
+        this.frames.push(end);
+        return end;
+    }
+    /** skip anything left in the frame (ie fields added by a newer protocol) */
+    endFrame(end: number | undefined) {
+        if (end === undefined) return;
+        this.frames.pop();
+        this.offset = end;
+    }
+}
+
+export class Writer {
+    private data = new Uint8Array(64);
+    private view = new DataView(this.data.buffer);
+    length = 0;
+
+    /** `framed` should be true once protocol 2 or newer has been negotiated */
+    constructor(public framed = true) {}
+
+    private reserve(len: number): number {
+        if (this.length + len > this.data.length) {
+            const data = new Uint8Array(Math.max(this.data.length * 2, this.length + len));
+            data.set(this.data);
+            this.data = data;
+            this.view = new DataView(data.buffer);
+        }
+        const offset = this.length;
+        this.length += len;
+        return offset;
+    }
+
+    /** the written data */
+    finish(): Uint8Array { return this.data.slice(0, this.length); }
+
+    u8(v: number) { this.view.setUint8(this.reserve(1), v); }
+    i8(v: number) { this.view.setInt8(this.reserve(1), v); }
+    u16(v: number) { this.view.setUint16(this.reserve(2), v, true); }
+    i16(v: number) { this.view.setInt16(this.reserve(2), v, true); }
+    u32(v: number) { this.view.setUint32(this.reserve(4), v, true); }
+    i32(v: number) { this.view.setInt32(this.reserve(4), v, true); }
+    u64(v: bigint) { this.view.setBigUint64(this.reserve(8), v, true); }
+    i64(v: bigint) { this.view.setBigInt64(this.reserve(8), v, true); }
+    u128(v: bigint) { this.u64(BigInt.asUintN(64, v)); this.u64(BigInt.asUintN(64, v >> 64n)); }
+    i128(v: bigint) { this.u128(BigInt.asUintN(128, v)); }
+    f32(v: number) { this.view.setFloat32(this.reserve(4), v, true); }
+    f64(v: number) { this.view.setFloat64(this.reserve(8), v, true); }
+    f16(v: number) {
+        const f32 = new DataView(new ArrayBuffer(4));
+        f32.setFloat32(0, v);
+        const bits = f32.getUint32(0);
+        const sign = (bits >>> 16) & 0x8000;
+        const exp = ((bits >>> 23) & 0xff) - 127 + 15;
+        const frac = bits & 0x7fffff;
+        if (Number.isNaN(v)) return this.u16(0x7e00);
+        if (exp >= 0x1f) return this.u16(sign | 0x7c00);
+        if (exp <= 0) return this.u16(exp < -10 ? sign : sign | ((frac | 0x800000) >> (14 - exp)));
+        this.u16(sign | (exp << 10) | (frac >> 13));
+    }
+    bool(v: boolean) { this.u8(v ? 1 : 0); }
+    char(v: string) { this.u32(v.codePointAt(0) ?? 0); }
+    unit(_v: null) {}
+    string(v: string) {
+        const bytes = new TextEncoder().encode(v);
+        this.u64(BigInt(bytes.length));
+        this.data.set(bytes, this.reserve(bytes.length));
+    }
+    duration(v: Duration) { this.u64(v.secs); this.u32(v.nanos); }
+    systemTime(v: SystemTime) { this.i64(v.secs); this.u32(v.nanos); }
+    ipv4Addr(v: string) { v.split(".").forEach(b => this.u8(Number(b))); }
+    ipv6Addr(v: string) {
+        // expand `::` into the missing groups
+        const [head, tail] = v.split("::");
+        const headGroups = head ? head.split(":") : [];
+        const tailGroups = tail ? tail.split(":") : [];
+        const groups = tail === undefined ? headGroups : [...headGroups, ...Array(8 - headGroups.length - tailGroups.length).fill("0"), ...tailGroups];
+        groups.forEach(g => { const n = parseInt(g, 16); this.u8(n >> 8); this.u8(n & 0xff); });
+    }
+    ipAddr(v: string) {
+        if (v.includes(":")) { this.u8(1); this.ipv6Addr(v); }
+        else { this.u8(0); this.ipv4Addr(v); }
+    }
+
+    list<T>(v: Iterable<T>, item: (v: T) => void) {
+        const items = Array.from(v);
+        this.u64(BigInt(items.length));
+        items.forEach(item);
+    }
+    array<T>(v: T[], item: (v: T) => void) {
+        v.forEach(item);
+    }
+    map<K, V>(v: Map<K, V>, key: (k: K) => void, value: (v: V) => void) {
+        this.u64(BigInt(v.size));
+        v.forEach((val, k) => { key(k); value(val); });
+    }
+    option<T>(v: T | undefined, item: (v: T) => void) {
+        if (v === undefined) return this.u8(0);
+        this.u8(1);
+        item(v);
+    }
+    result<T, E>(v: Result<T, E>, ok: (v: T) => void, err: (v: E) => void) {
+        if ("ok" in v) { this.u8(0); ok(v.ok); }
+        else { this.u8(1); err(v.err); }
+    }
+
+    /** reserve space for the payload length of a packet frame, if the protocol is framed */
+    beginFrame(): number | undefined {
+        if (!this.framed) return undefined;
+        return this.reserve(8);
+    }
+    /** fill in the payload length reserved by `beginFrame` */
+    endFrame(start: number | undefined) {
+        if (start === undefined) return;
+        this.view.setBigUint64(start, BigInt(this.length - start - 8), true);
+    }
+}
@@ -0,0 +1,297 @@
+use std::fmt::Write;
+use super::*;
+
+const RUNTIME: &str = include_str!("runtime.ts");
+
+/// generate typescript types, plus `readX(r)`/`writeX(w, value)` for each of them, for `roots` and every type they use
+pub fn generate_typescript(roots: &[SchemaFn]) -> CodegenResult<String> {
+    let mut out = String::new();
+    let _ = writeln!(out, "// generated from the tataku-common protocol schema, don't edit by hand\n");
+    out.push_str(RUNTIME);
+
+    for ty in named_types(roots)? {
+        out.push('\n');
+        match &ty.kind {
+            SchemaKind::Struct { versioned, current_version, fields } => write_struct(&mut out, &ty.name, *versioned, *current_version, fields)?,
+            SchemaKind::Enum { id_type, framed, strict, fallback, variants } => write_enum(&mut out, &ty.name, id_type, *framed, fallback_variant(*strict, *fallback, variants), variants)?,
+            SchemaKind::Bitmask { id_type, flags } => write_bitmask(&mut out, &ty.name, id_type, flags)?,
+            _ => {}
+        }
+    }
+
+    Ok(out)
+}
+
+fn write_struct(out: &mut String, name: &str, versioned: bool, current_version: Option<u16>, fields: &[FieldSchema]) -> CodegenResult<()> {
+    // without a current version, the version that was read is kept so it can be written again
+    let keep_version = versioned && current_version.is_none();
+
+    let _ = writeln!(out, "export interface {name} {{");
+    if keep_version { let _ = writeln!(out, "    version: number;"); }
+    for f in fields.iter().filter(|f| is_current(f)) {
+        out.push_str(&docs(f.docs, "    "));
+        let optional = if f.added > 0 { "?" } else { "" };
+        let _ = writeln!(out, "    {}{optional}: {};", f.name, ts_type(&(f.ty)())?);
+    }
+    let _ = writeln!(out, "}}");
+
+    // read
+    let _ = writeln!(out, "export function read{name}(r: Reader): {name} {{");
+    if versioned {
+        let _ = writeln!(out, "    const version = r.u16();");
+    } else if fields.iter().any(|f| live_condition(f).is_some()) {
+        let _ = writeln!(out, "    const version = 0;");
+    }
+    let _ = writeln!(out, "    const value = {{}} as {name};");
+    if keep_version { let _ = writeln!(out, "    value.version = version;"); }
+    for f in fields {
+        let read = read(&(f.ty)())?;
+        // old layouts are read to skip them, since they cant be converted
+        let statement = if is_current(f) { format!("value.{} = {read};", f.name) } else { format!("{read}; // old {}", f.name) };
+        match live_condition(f) {
+            Some(condition) => { let _ = writeln!(out, "    if ({condition}) {statement}"); }
+            None => { let _ = writeln!(out, "    {statement}"); }
+        }
+    }
+    let _ = writeln!(out, "    return value;");
+    let _ = writeln!(out, "}}");
+
+    // write
+    let _ = writeln!(out, "export function write{name}(w: Writer, value: {name}) {{");
+    match current_version {
+        Some(current) => { let _ = writeln!(out, "    const version = {current};\n    w.u16(version);"); }
+        None if versioned => { let _ = writeln!(out, "    const version = value.version;\n    w.u16(version);"); }
+        None => {}
+    }
+    for f in fields.iter().filter(|f| is_current(f)) {
+        let ty = (f.ty)();
+        // fields added later arent there if the value was read from an older version, so the default is written instead
+        let value = match default_value(&ty)? {
+            _ if f.added == 0 || matches!(ty.kind, SchemaKind::Option { .. }) => format!("value.{}", f.name),
+            Some(default) => format!("value.{} ?? {default}", f.name),
+            None => format!("value.{}!", f.name),
+        };
+        let write = write(&ty, &value, 0)?;
+        match live_condition(f).filter(|_| keep_version) {
+            Some(condition) => { let _ = writeln!(out, "    if ({condition}) {write}"); }
+            None => { let _ = writeln!(out, "    {write}"); }
+        }
+    }
+    let _ = writeln!(out, "}}");
+
+    Ok(())
+}
+
+fn write_enum(
+    out: &mut String,
+    name: &str,
+    id_type: &str,
+    framed: bool,
+    fallback: Option<&VariantSchema>,
+    variants: &[VariantSchema]
+) -> CodegenResult<()> {
+    let plain = is_plain_enum(variants);
+    let id_method = primitive_method(id_type)?;
+    let id = |id: u64| id_literal(id, id_type);
+
+    // plain enums are just their variant names, others are tagged with `type`
+    let _ = writeln!(out, "export type {name} =");
+    for v in variants {
+        out.push_str(&docs(v.docs, "    "));
+        if plain {
+            let _ = writeln!(out, "    | \"{}\"", v.name);
+        } else {
+            let mut fields = vec![format!("type: \"{}\"", v.name)];
+            for f in v.fields.iter() {
+                fields.push(format!("{}: {}", f.name, ts_type(&(f.ty)())?));
+            }
+            let _ = writeln!(out, "    | {{ {} }}", fields.join("; "));
+        }
+    }
+    let _ = writeln!(out, "    ;");
+
+    // read
+    let _ = writeln!(out, "export function read{name}(r: Reader): {name} {{");
+    let _ = writeln!(out, "    const id = r.{id_method}();");
+    if framed { let _ = writeln!(out, "    const frame = r.beginFrame();"); }
+    let _ = writeln!(out, "    let value: {name};");
+    let _ = writeln!(out, "    switch (id) {{");
+    for v in variants {
+        let _ = writeln!(out, "        case {}: value = {}; break;", id(v.id), variant_value(v, plain)?);
+    }
+    match fallback {
+        Some(v) => { let _ = writeln!(out, "        default: value = {}; break;", variant_value(v, plain)?); }
+        None => { let _ = writeln!(out, "        default: throw new ProtocolError(`unknown {name} variant ${{id}}`);"); }
+    }
+    let _ = writeln!(out, "    }}");
+    if framed { let _ = writeln!(out, "    r.endFrame(frame);"); }
+    let _ = writeln!(out, "    return value;");
+    let _ = writeln!(out, "}}");
+
+    // write
+    let _ = writeln!(out, "export function write{name}(w: Writer, value: {name}) {{");
+    let tag = if plain { "value" } else { "value.type" };
+    let _ = writeln!(out, "    switch ({tag}) {{");
+    for v in variants {
+        let _ = writeln!(out, "        case \"{}\": {{", v.name);
+        let _ = writeln!(out, "            w.{id_method}({});", id(v.id));
+        if framed { let _ = writeln!(out, "            const frame = w.beginFrame();"); }
+        for f in v.fields.iter() {
+            let _ = writeln!(out, "            {}", write(&(f.ty)(), &format!("value.{}", f.name), 0)?);
+        }
+        if framed { let _ = writeln!(out, "            w.endFrame(frame);"); }
+        let _ = writeln!(out, "            break;");
+        let _ = writeln!(out, "        }}");
+    }
+    let _ = writeln!(out, "    }}");
+    let _ = writeln!(out, "}}");
+
+    Ok(())
+}
+
+fn write_bitmask(out: &mut String, name: &str, id_type: &str, flags: &[(&str, u64)]) -> CodegenResult<()> {
+    let method = primitive_method(id_type)?;
+    let names = flags.iter().map(|(f, _)| format!("\"{f}\"")).collect::<Vec<_>>().join(" | ");
+    let values = flags.iter().map(|(f, v)| format!("{f}: {}", id_literal(*v, id_type))).collect::<Vec<_>>().join(", ");
+    let zero = id_literal(0, id_type);
+
+    let _ = writeln!(out, "export type {name} = ({names})[];");
+    let _ = writeln!(out, "const {name}Flags = {{ {values} }};");
+    let _ = writeln!(out, "export function read{name}(r: Reader): {name} {{");
+    let _ = writeln!(out, "    const mask = r.{method}();");
+    let _ = writeln!(out, "    return (Object.keys({name}Flags) as {name}).filter(f => (mask & {name}Flags[f]) !== {zero});");
+    let _ = writeln!(out, "}}");
+    let _ = writeln!(out, "export function write{name}(w: Writer, value: {name}) {{");
+    let _ = writeln!(out, "    w.{method}(value.reduce((mask, f) => mask | {name}Flags[f], {zero}));");
+    let _ = writeln!(out, "}}");
+
+    Ok(())
+}
+
+
+/// an object literal which reads the variant
+fn variant_value(v: &VariantSchema, plain: bool) -> CodegenResult<String> {
+    if plain { return Ok(format!("\"{}\"", v.name)) }
+
+    let mut fields = vec![format!("type: \"{}\"", v.name)];
+    for f in v.fields.iter() {
+        fields.push(format!("{}: {}", f.name, read(&(f.ty)())?));
+    }
+    Ok(format!("{{ {} }}", fields.join(", ")))
+}
+
+/// u64s and bigger are bigints
+fn id_literal(id: u64, id_type: &str) -> String {
+    match primitive_type(id_type) {
+        Ok("bigint") => format!("{id}n"),
+        _ => id.to_string(),
+    }
+}
+
+fn primitive_type(name: &str) -> CodegenResult<&'static str> {
+    Ok(match primitive_method(name)? {
+        "u64" | "i64" | "u128" | "i128" => "bigint",
+        "bool" => "boolean",
+        "unit" => "null",
+        "duration" => "Duration",
+        "systemTime" => "SystemTime",
+        "char" | "string" | "ipAddr" | "ipv4Addr" | "ipv6Addr" => "string",
+        _ => "number",
+    })
+}
+
+fn ts_type(ty: &TypeSchema) -> CodegenResult<String> {
+    // unions need brackets inside of arrays
+    let item = |item: &SchemaFn| ts_type(&item()).map(|t| if t.contains(' ') { format!("({t})") } else { t });
+
+    Ok(match &ty.kind {
+        SchemaKind::Primitive { .. } => primitive_type(&ty.name)?.to_owned(),
+        SchemaKind::List { item: i } | SchemaKind::Array { item: i, .. } => format!("{}[]", item(i)?),
+        SchemaKind::Map { key, value } => format!("Map<{}, {}>", ts_type(&key())?, ts_type(&value())?),
+        SchemaKind::Option { item } => format!("{} | undefined", ts_type(&item())?),
+        SchemaKind::Result { ok, err } => format!("Result<{}, {}>", ts_type(&ok())?, ts_type(&err())?),
+        SchemaKind::Tuple { items } => format!("[{}]", items.iter().map(|i| ts_type(&i())).collect::<CodegenResult<Vec<_>>>()?.join(", ")),
+        SchemaKind::Struct { .. } | SchemaKind::Enum { .. } | SchemaKind::Bitmask { .. } => ty.name.clone(),
+    })
+}
+
+/// the default value of `ty`, if it has one which doesnt need to be generated
+fn default_value(ty: &TypeSchema) -> CodegenResult<Option<String>> {
+    Ok(match &ty.kind {
+        SchemaKind::Primitive { .. } => Some(match primitive_method(&ty.name)? {
+            "ipAddr" | "ipv4Addr" => "\"0.0.0.0\"",
+            "ipv6Addr" => "\"::\"",
+            "duration" | "systemTime" => "{ secs: 0n, nanos: 0 }",
+            _ => match primitive_type(&ty.name)? {
+                "bigint" => "0n",
+                "boolean" => "false",
+                "null" => "null",
+                "string" => "\"\"",
+                _ => "0",
+            }
+        }.to_owned()),
+        SchemaKind::List { .. } | SchemaKind::Bitmask { .. } => Some("[]".to_owned()),
+        SchemaKind::Array { item, len } => default_value(&item())?.map(|d| format!("Array.from({{ length: {len} }}, () => {d})")),
+        SchemaKind::Map { .. } => Some("new Map()".to_owned()),
+        SchemaKind::Option { .. } => Some("undefined".to_owned()),
+        SchemaKind::Tuple { items } => items.iter()
+            .map(|i| default_value(&i()))
+            .collect::<CodegenResult<Option<Vec<_>>>>()?
+            .map(|items| format!("[{}]", items.join(", "))),
+        SchemaKind::Result { .. } | SchemaKind::Struct { .. } | SchemaKind::Enum { .. } => None,
+    })
+}
+
+/// an expression which reads `ty` from `r`
+fn read(ty: &TypeSchema) -> CodegenResult<String> {
+    let inner = |item: &SchemaFn| read(&item());
+
+    Ok(match &ty.kind {
+        SchemaKind::Primitive { .. } => format!("r.{}()", primitive_method(&ty.name)?),
+        SchemaKind::List { item } => format!("r.list(() => {})", inner(item)?),
+        SchemaKind::Array { item, len } => format!("r.array({len}, () => {})", inner(item)?),
+        SchemaKind::Map { key, value } => format!("r.map(() => {}, () => {})", inner(key)?, inner(value)?),
+        SchemaKind::Option { item } => format!("r.option(() => {})", inner(item)?),
+        SchemaKind::Result { ok, err } => format!("r.result(() => {}, () => {})", inner(ok)?, inner(err)?),
+        SchemaKind::Tuple { items } => format!("[{}]", items.iter().map(inner).collect::<CodegenResult<Vec<_>>>()?.join(", ")),
+        SchemaKind::Struct { .. } | SchemaKind::Enum { .. } | SchemaKind::Bitmask { .. } => format!("read{}(r)", ty.name),
+    })
+}
+
+/// a statement which writes `value` (of type `ty`) to `w`
+fn write(ty: &TypeSchema, value: &str, depth: usize) -> CodegenResult<String> {
+    let x = format!("x{depth}");
+    let inner = |item: &SchemaFn, value: &str| write(&item(), value, depth + 1);
+
+    Ok(match &ty.kind {
+        SchemaKind::Primitive { .. } => format!("w.{}({value});", primitive_method(&ty.name)?),
+        SchemaKind::List { item } => format!("w.list({value}, ({x}) => {{ {} }});", inner(item, &x)?),
+        SchemaKind::Array { item, .. } => format!("w.array({value}, ({x}) => {{ {} }});", inner(item, &x)?),
+        SchemaKind::Map { key, value: v } => format!(
+            "w.map({value}, (k{depth}) => {{ {} }}, (v{depth}) => {{ {} }});",
+            inner(key, &format!("k{depth}"))?,
+            inner(v, &format!("v{depth}"))?
+        ),
+        SchemaKind::Option { item } => format!("w.option({value}, ({x}) => {{ {} }});", inner(item, &x)?),
+        SchemaKind::Result { ok, err } => format!(
+            "w.result({value}, (ok{depth}) => {{ {} }}, (err{depth}) => {{ {} }});",
+            inner(ok, &format!("ok{depth}"))?,
+            inner(err, &format!("err{depth}"))?
+        ),
+        SchemaKind::Tuple { items } => {
+            let writes = items.iter().enumerate()
+                .map(|(i, item)| inner(item, &format!("{x}[{i}]")))
+                .collect::<CodegenResult<Vec<_>>>()?;
+            format!("{{ const {x} = {value}; {} }}", writes.join(" "))
+        }
+        SchemaKind::Struct { .. } | SchemaKind::Enum { .. } | SchemaKind::Bitmask { .. } => format!("write{}(w, {value});", ty.name),
+    })
+}
+
+fn docs(docs: &str, indent: &str) -> String {
+    match docs.lines().count() {
+        0 => String::new(),
+        1 => format!("{indent}/** {docs} */\n"),
+        _ => format!("{indent}/**\n{}{indent} */\n", doc_lines(docs, indent, " * ")),
+    }
+}
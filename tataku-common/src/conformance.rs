@@ -0,0 +1,400 @@
+//! golden wire-format corpus, so the exact bytes of every packet can't change by accident
+//!
+//! the corpus is checked in at `corpus/` in the repo root:
+//! - `packets/v{protocol}/{enum}/{variant}.bin`: one of every packet (and sub-packet) variant, for each protocol version.
+//!   protocols with request ids have each packet in an [`Envelope`] with a request id
+//! - `{type}/v{version}.bin`: a [`Score`], [`Replay`] or [`ModDefinition`] written as each of its versions
+//!
+//! [`check_corpus`] reads every fixture, writes it again and compares the bytes.
+//! other implementations can check their readers and writers against the same files
+use std::path::{ Path, PathBuf };
+
+use crate::packets::*;
+use crate::serialization::*;
+use crate::types::{ Score, ModDefinition, replays::Replay };
+
+/// protocol versions which have packet fixtures
+pub const CORPUS_PROTOCOLS: &[Protocol] = &[Protocol::LEGACY, Protocol::FRAMED, Protocol::COMPACT, Protocol::REQUEST_IDS];
+
+/// (type name, oldest version, current version) of the types with versioned fixtures
+const VERSIONED_TYPES: &[(&str, u16, u16)] = &[
+    ("Score", 1, Score::CURRENT_VERSION),
+    // v1 replays can only be read on their own, newer ones are read with their score
+    ("Replay", 1, Replay::CURRENT_VERSION),
+    ("ModDefinition", 0, ModDefinition::CURRENT_VERSION),
+];
+
+/// one file in the corpus
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum FixtureId {
+    /// a packet variant, written with `protocol`
+    Packet {
+        protocol: Protocol,
+        packet: &'static str,
+        variant: &'static str,
+    },
+    /// a versioned type, written as `version`
+    Versioned {
+        type_name: &'static str,
+        version: u16,
+    },
+}
+impl FixtureId {
+    /// where this fixture is, relative to the corpus directory
+    pub fn path(&self) -> PathBuf {
+        match self {
+            Self::Packet { protocol, packet, variant } => format!("packets/v{}/{packet}/{variant}.bin", protocol.version()).into(),
+            Self::Versioned { type_name, version } => format!("{type_name}/v{version}.bin").into(),
+        }
+    }
+}
+impl std::fmt::Display for FixtureId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.path().display())
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ConformanceError {
+    /// the fixture isnt in the corpus
+    Missing,
+    /// a file in the corpus which isnt a fixture (ie it was renamed or removed)
+    Unexpected,
+    /// the fixture couldnt be read
+    Read(String),
+    /// a packet fixture was read as a different variant (ie its id changed)
+    WrongVariant {
+        expected: &'static str,
+        found: Option<&'static str>,
+    },
+    /// a versioned fixture was read as a different version
+    WrongVersion {
+        expected: u16,
+        found: u16,
+    },
+    /// writing the fixture again gave different bytes
+    Mismatch {
+        /// the first byte which is different
+        offset: usize,
+        expected_len: usize,
+        len: usize,
+    },
+}
+impl std::fmt::Display for ConformanceError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Missing => write!(f, "fixture is missing"),
+            Self::Unexpected => write!(f, "file isn't a fixture"),
+            Self::Read(e) => write!(f, "error reading fixture: {e}"),
+            Self::WrongVariant { expected, found } => write!(f, "expected variant {expected}, found {}", found.unwrap_or("an unknown id")),
+            Self::WrongVersion { expected, found } => write!(f, "expected version {expected}, found {found}"),
+            Self::Mismatch { offset, expected_len, len } => write!(f, "bytes differ at offset {offset} (expected {expected_len} bytes, wrote {len})"),
+        }
+    }
+}
+impl std::error::Error for ConformanceError {}
+impl From<SerializationError> for ConformanceError {
+    fn from(e: SerializationError) -> Self { Self::Read(e.to_string()) }
+}
+
+
+macro_rules! packet_enums {
+    ($($t:ident),+) => {
+        /// ids of every packet enum with fixtures
+        const PACKET_ENUMS: &[VariantIds] = &[ $( <$t as HasVariantIds>::VARIANT_IDS ),+ ];
+
+        /// read a packet and write it again
+        fn rewrite_packet(packet: &str, data: &[u8], protocol: Protocol) -> SerializationResult<Vec<u8>> {
+            match packet {
+                $( stringify!($t) => rewrite::<$t>(data, protocol), )+
+                _ => unreachable!("{packet} isn't a packet enum"),
+            }
+        }
+
+        /// write a packet variant, with fields from `seed`
+        #[cfg(feature="test")]
+        fn sample_packet(packet: &str, variant: &str, protocol: Protocol, seed: u64) -> Vec<u8> {
+            match packet {
+                $( stringify!($t) => sample_variant::<$t>(variant, protocol, seed), )+
+                _ => unreachable!("{packet} isn't a packet enum"),
+            }
+        }
+    }
+}
+packet_enums!(PacketId, ChatPacket, SpectatorPacket, MultiplayerPacket);
+
+fn rewrite<T: Serializable>(data: &[u8], protocol: Protocol) -> SerializationResult<Vec<u8>> {
+    if protocol.has_request_ids() {
+        rewrite_value::<Envelope<T>>(data, protocol)
+    } else {
+        rewrite_value::<T>(data, protocol)
+    }
+}
+
+fn rewrite_value<T: Serializable>(data: &[u8], protocol: Protocol) -> SerializationResult<Vec<u8>> {
+    let mut reader = SerializationReader::from_slice(data).with_protocol(protocol);
+    let value = reader.read::<T>("fixture")?;
+    reader.finish()?;
+
+    let mut writer = SerializationWriter::new().with_protocol(protocol).canonical();
+    writer.write(&value);
+    Ok(writer.data())
+}
+
+/// read a versioned type and write it again as the same version
+fn rewrite_versioned(type_name: &str, version: u16, data: &[u8]) -> Result<Vec<u8>, ConformanceError> {
+    let mut reader = SerializationReader::from_slice(data);
+    let mut writer = SerializationWriter::new().canonical();
+
+    match type_name {
+        "Score" => {
+            let score = reader.read::<Score>("fixture")?;
+            if score.version != version {
+                return Err(ConformanceError::WrongVersion { expected: version, found: score.version });
+            }
+            score.write_versioned(&mut writer, version);
+        }
+        "Replay" if version < 2 => {
+            reader.read::<Replay>("fixture")?.write_versioned(&mut writer, version);
+        }
+        "Replay" => {
+            let found = reader.peek::<u16>("version")?;
+            // v6+ replays are written as the score, which has its own version
+            if version < Replay::CURRENT_VERSION && found != version {
+                return Err(ConformanceError::WrongVersion { expected: version, found });
+            }
+
+            let score = Replay::try_read_replay(&mut reader).map_err(|e| ConformanceError::Read(format!("{e:?}")))?;
+            Replay::write_with_score(&score, &mut writer, version);
+        }
+        "ModDefinition" => {
+            reader.read::<ModDefinition>("fixture")?.write_versioned(&mut writer, version);
+        }
+        _ => unreachable!("{type_name} isn't versioned"),
+    }
+
+    reader.finish()?;
+    Ok(writer.data())
+}
+
+/// the variant a packet was written as, from its id
+fn written_variant(ids: &VariantIds, data: &[u8], protocol: Protocol) -> Option<&'static str> {
+    // ids are varints in the compact protocol
+    let mut reader = SerializationReader::from_slice(data).with_protocol(protocol);
+    // and come after the request id, if there is one
+    if protocol.has_request_ids() { reader.read::<Option<u32>>("request_id").ok()?; }
+    let id = match ids.id_size {
+        1 => reader.read::<u8>("id").ok()? as u64,
+        2 => reader.read::<u16>("id").ok()? as u64,
+        4 => reader.read::<u32>("id").ok()? as u64,
+        _ => reader.read::<u64>("id").ok()?,
+    };
+    ids.variant_of(id)
+}
+
+
+/// every fixture the corpus should have
+pub fn expected_fixtures() -> Vec<FixtureId> {
+    let mut fixtures = Vec::new();
+
+    for &protocol in CORPUS_PROTOCOLS {
+        for ids in PACKET_ENUMS {
+            fixtures.extend(ids.ids.iter().map(|(variant, _)| FixtureId::Packet { protocol, packet: ids.enum_name, variant }));
+        }
+    }
+
+    for &(type_name, oldest, current) in VERSIONED_TYPES {
+        fixtures.extend((oldest..=current).map(|version| FixtureId::Versioned { type_name, version }));
+    }
+
+    fixtures
+}
+
+/// read a fixture, write it again, and check the bytes are the same
+pub fn check_fixture(fixture: &FixtureId, data: &[u8]) -> Result<(), ConformanceError> {
+    let written = match *fixture {
+        FixtureId::Packet { protocol, packet, variant } => {
+            let ids = PACKET_ENUMS.iter().find(|ids| ids.enum_name == packet).expect("fixture for an unknown packet enum");
+            let found = written_variant(ids, data, protocol);
+            if found != Some(variant) {
+                return Err(ConformanceError::WrongVariant { expected: variant, found });
+            }
+
+            rewrite_packet(packet, data, protocol)?
+        }
+        FixtureId::Versioned { type_name, version } => rewrite_versioned(type_name, version, data)?,
+    };
+
+    if written != data {
+        let offset = written.iter().zip(data).position(|(a, b)| a != b).unwrap_or(written.len().min(data.len()));
+        return Err(ConformanceError::Mismatch { offset, expected_len: data.len(), len: written.len() });
+    }
+
+    Ok(())
+}
+
+/// check every fixture in the corpus at `dir`
+///
+/// returns every fixture which failed, and any files which aren't fixtures
+pub fn check_corpus(dir: impl AsRef<Path>) -> Result<(), Vec<(PathBuf, ConformanceError)>> {
+    let dir = dir.as_ref();
+    let expected = expected_fixtures();
+    let mut errors = Vec::new();
+
+    for fixture in expected.iter() {
+        let path = fixture.path();
+        let result = match std::fs::read(dir.join(&path)) {
+            Ok(data) => check_fixture(fixture, &data),
+            Err(_) => Err(ConformanceError::Missing),
+        };
+        if let Err(e) = result { errors.push((path, e)) }
+    }
+
+    let expected_paths = expected.iter().map(FixtureId::path).collect::<Vec<_>>();
+    for path in corpus_files(dir, Path::new("")) {
+        if !expected_paths.contains(&path) {
+            errors.push((path, ConformanceError::Unexpected));
+        }
+    }
+
+    if errors.is_empty() { Ok(()) } else { Err(errors) }
+}
+
+/// every `.bin` file in `dir`, relative to the corpus root
+fn corpus_files(root: &Path, dir: &Path) -> Vec<PathBuf> {
+    let Ok(entries) = std::fs::read_dir(root.join(dir)) else { return Vec::new() };
+
+    let mut files = Vec::new();
+    for entry in entries.flatten() {
+        let path = dir.join(entry.file_name());
+        if entry.file_type().is_ok_and(|t| t.is_dir()) {
+            files.extend(corpus_files(root, &path));
+        } else if path.extension().is_some_and(|e| e == "bin") {
+            files.push(path);
+        }
+    }
+    files
+}
+
+
+/// write every fixture. packet fields are random, but always the same for each variant
+#[cfg(feature="test")]
+pub fn generate_corpus() -> Vec<(FixtureId, Vec<u8>)> {
+    expected_fixtures().into_iter().map(|fixture| {
+        let data = match fixture {
+            FixtureId::Packet { protocol, packet, variant } => {
+                // seeded by the name, so adding a variant doesnt change the others
+                let seed = format!("{packet}::{variant}").bytes().fold(0xcbf29ce484222325u64, |h, b| (h ^ b as u64).wrapping_mul(0x100000001b3));
+                sample_packet(packet, variant, protocol, seed)
+            }
+            FixtureId::Versioned { type_name, version } => sample_versioned(type_name, version),
+        };
+        (fixture, data)
+    }).collect()
+}
+
+#[cfg(feature="test")]
+fn sample_variant<T: Serializable + HasVariantIds + RandomValue>(variant: &str, protocol: Protocol, seed: u64) -> Vec<u8> {
+    // keep trying seeds until it picks the right variant
+    for attempt in 0.. {
+        let value = T::random(&mut RandomSource::new(seed.wrapping_add(attempt)));
+        let mut writer = SerializationWriter::new().with_protocol(protocol).canonical();
+        if protocol.has_request_ids() {
+            writer.write(&Envelope::new(value).with_request_id(seed as u32));
+        } else {
+            writer.write(&value);
+        }
+        let data = writer.data();
+
+        if written_variant(&T::VARIANT_IDS, &data, protocol) == Some(variant) { return data }
+    }
+    unreachable!()
+}
+
+#[cfg(feature="test")]
+fn sample_versioned(type_name: &str, version: u16) -> Vec<u8> {
+    use crate::types::{ Md5Hash, GameSpeed, replays::* };
+
+    let mut score = Score::new(Md5Hash::from(0x8bfe194c8bd641937d61e3995872fdba), "user".to_owned(), "osu".to_owned());
+    score.time = 1_700_000_000;
+    score.score = 123456;
+    score.combo = 100;
+    score.max_combo = 150;
+    score.performance = 12.5;
+    for (j, n) in [("x50", 1), ("x100", 2), ("x300", 140), ("xgeki", 4), ("xkatu", 5), ("xmiss", 6)] {
+        score.judgments.insert(j.to_owned(), n);
+    }
+    score.accuracy = 0.75;
+    score.speed = GameSpeed::from_f32(1.5);
+    score.hit_timings = vec![-5.0, 0.0, 12.5];
+    score.mods = vec![ModDefinition::new("easy", "EZ", "Easy", true, 0.5)];
+    score.stat_data.insert("stat".to_owned(), vec![1.0, 2.0]);
+    score.replay = Some(Replay {
+        gamemode_data: [("key".to_owned(), "value".to_owned())].into(),
+        offset: 5.0,
+        frames: vec![
+            ReplayFrame::new(0.0, ReplayAction::Press(KeyPress::LeftDon)),
+            ReplayFrame::new(10.0, ReplayAction::Release(KeyPress::LeftDon)),
+            ReplayFrame::new(20.0, ReplayAction::MousePos(100.0, 200.0)),
+        ],
+    });
+
+    let mut writer = SerializationWriter::new().canonical();
+    match type_name {
+        "Score" => { score.write_versioned(&mut writer, version); }
+        "Replay" => { Replay::write_with_score(&score, &mut writer, version); }
+        "ModDefinition" => { score.mods[0].write_versioned(&mut writer, version); }
+        _ => unreachable!("{type_name} isn't versioned"),
+    }
+    writer.data()
+}
+
+
+#[cfg(test)]
+const CORPUS_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/../corpus");
+
+#[test]
+fn corpus_conforms() {
+    if let Err(errors) = check_corpus(CORPUS_DIR) {
+        let errors = errors.iter().map(|(path, e)| format!("{}: {e}", path.display())).collect::<Vec<_>>();
+        panic!("corpus doesn't conform:\n{}", errors.join("\n"));
+    }
+
+    // a packet with the wrong id
+    let fixture = FixtureId::Packet { protocol: Protocol::FRAMED, packet: "PacketId", variant: "Ping" };
+    let data = std::fs::read(Path::new(CORPUS_DIR).join(fixture.path())).unwrap();
+    let pong = FixtureId::Packet { protocol: Protocol::FRAMED, packet: "PacketId", variant: "Pong" };
+    assert_eq!(check_fixture(&pong, &data), Err(ConformanceError::WrongVariant { expected: "Pong", found: Some("Ping") }));
+
+    // a score which was written differently
+    let fixture = FixtureId::Versioned { type_name: "Score", version: Score::CURRENT_VERSION };
+    let mut data = std::fs::read(Path::new(CORPUS_DIR).join(fixture.path())).unwrap();
+    data.push(0);
+    assert!(matches!(check_fixture(&fixture, &data), Err(ConformanceError::Read(_))));
+}
+
+/// the corpus is generated, so changes to the wire format show up here.
+/// run with `UPDATE_CORPUS=1` to regenerate it (only if the change was intended!)
+#[cfg(feature="test")]
+#[test]
+fn corpus_is_up_to_date() {
+    let corpus = generate_corpus();
+    let dir = Path::new(CORPUS_DIR);
+
+    if std::env::var_os("UPDATE_CORPUS").is_some() {
+        for path in corpus_files(dir, Path::new("")) {
+            std::fs::remove_file(dir.join(path)).unwrap();
+        }
+        for (fixture, data) in corpus {
+            let path = dir.join(fixture.path());
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, data).unwrap();
+        }
+        return;
+    }
+
+    for (fixture, data) in corpus {
+        let existing = std::fs::read(dir.join(fixture.path())).unwrap_or_default();
+        assert!(existing == data, "{fixture} is out of date, run the tests with UPDATE_CORPUS=1 to regenerate the corpus");
+    }
+}
@@ -0,0 +1,144 @@
+//! fuzz targets for the decoders which read untrusted data
+//!
+//! each target reads `data` the same way a server would, and must never panic (errors are fine).
+//! they're run by cargo-fuzz from `fuzz/` (`cargo +nightly fuzz run packet`),
+//! and crash inputs saved in `fuzz/regressions/{target}/` are replayed by the regular tests on stable
+use crate::packets::PacketId;
+use crate::serialization::*;
+use crate::types::{ Score, replays::Replay };
+
+/// something which reads untrusted data, and must not panic
+pub type FuzzTarget = fn(&[u8]);
+
+/// (name, target) of every fuzz target
+pub const FUZZ_TARGETS: &[(&str, FuzzTarget)] = &[
+    ("packet", fuzz_packet),
+    ("replay", fuzz_replay),
+    ("score", fuzz_score),
+];
+
+/// read packets until the data runs out or one fails, with each protocol
+pub fn fuzz_packet(data: &[u8]) {
+    for protocol in [Protocol::LEGACY, Protocol::FRAMED, Protocol::COMPACT] {
+        let mut reader = SerializationReader::from_slice(data).with_protocol(protocol);
+        while reader.can_read() && reader.read::<PacketId>("packet").is_ok() {}
+    }
+
+    // what the client and the reference server read
+    let mut reader = SerializationReader::from_slice(data).with_protocol(Protocol::REQUEST_IDS);
+    while reader.can_read() && reader.read::<Envelope<ClientToServer<PacketId>>>("packet").is_ok() {}
+    let mut reader = SerializationReader::from_slice(data).with_protocol(Protocol::REQUEST_IDS);
+    while reader.can_read() && reader.read::<Envelope<ServerToClient<PacketId>>>("packet").is_ok() {}
+
+    // and streamed, a few bytes at a time
+    let mut decoder = PacketDecoder::<PacketId>::new().with_protocol(Protocol::FRAMED);
+    let mut envelopes = PacketDecoder::<Envelope<PacketId>>::new().with_protocol(Protocol::REQUEST_IDS);
+    for chunk in data.chunks(7) {
+        decoder.push(chunk);
+        while let Ok(Some(_)) = decoder.next_packet() {}
+        envelopes.push(chunk);
+        while let Ok(Some(_)) = envelopes.next_packet() {}
+    }
+}
+
+/// read a replay file, which could be any replay version
+pub fn fuzz_replay(data: &[u8]) {
+    let _ = Replay::try_read_replay(&mut SerializationReader::from_slice(data));
+}
+
+/// read a score, which could be any score version
+pub fn fuzz_score(data: &[u8]) {
+    let mut reader = SerializationReader::from_slice(data);
+    if let Ok(score) = reader.read::<Score>("score") {
+        // scores read from old versions are written back as the current one
+        let _ = reader.finish();
+        SerializationWriter::new().write(&score);
+    }
+}
+
+
+/// crash inputs found by cargo-fuzz are copied into here so they stay fixed
+#[cfg(test)]
+const REGRESSIONS_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/fuzz/regressions");
+
+#[test]
+fn fuzz_regressions() {
+    for (name, target) in FUZZ_TARGETS {
+        let Ok(entries) = std::fs::read_dir(std::path::Path::new(REGRESSIONS_DIR).join(name)) else { continue };
+        for entry in entries.flatten() {
+            let data = std::fs::read(entry.path()).unwrap();
+            // so a failure says which input it was
+            if std::panic::catch_unwind(|| target(&data)).is_err() {
+                panic!("{name} panicked on {}", entry.path().display());
+            }
+        }
+    }
+}
+
+/// a (very) small fuzzer, so the targets get some coverage without nightly
+///
+/// every fixture in the golden corpus is mutated a few times (or `TATAKU_FUZZ_ITERATIONS` times) and given to every target
+#[test]
+fn fuzz_mutated_corpus() {
+    let iterations = std::env::var("TATAKU_FUZZ_ITERATIONS").ok().and_then(|n| n.parse().ok()).unwrap_or(32);
+    let corpus = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("../corpus");
+    let mut state = 0x2545f4914f6cdd1du64;
+    let mut next = |n: usize| {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        (state % n.max(1) as u64) as usize
+    };
+
+    for fixture in crate::conformance::expected_fixtures() {
+        let Ok(data) = std::fs::read(corpus.join(fixture.path())) else { continue };
+
+        for _ in 0..iterations {
+            let mut data = data.clone();
+            for _ in 0..=next(4) {
+                let at = next(data.len() + 1);
+                match next(4) {
+                    // flip a byte
+                    0 if at < data.len() => data[at] ^= 1 << next(8),
+                    // a huge (or negative) length
+                    1 => { data.splice(at..at, [0xff; 8].map(|b| b >> next(2))); }
+                    // cut it short
+                    2 => data.truncate(at),
+                    // repeat a chunk
+                    _ => {
+                        let end = (at + next(16)).min(data.len());
+                        let chunk = data[at..end].to_vec();
+                        data.splice(at..at, chunk);
+                    }
+                }
+            }
+
+            for (_, target) in FUZZ_TARGETS {
+                target(&data);
+            }
+        }
+    }
+}
+
+/// old replays have a score in them, and newer scores have a replay in them,
+/// so a replay can be nested as deep as the data wants
+#[test]
+fn fuzz_nested_replays() {
+    use crate::types::Md5Hash;
+
+    // a score with no replay, without the `None`
+    let mut score = SerializationWriter::new();
+    score.write(&Score::new(Md5Hash::default(), String::new(), String::new()));
+    let mut score = score.data();
+    score.pop();
+
+    let mut data = Vec::new();
+    for _ in 0..100_000 {
+        data.extend(&score);
+        // Some(v3 replay with Some(score))
+        data.extend([1, 3, 0, 1]);
+    }
+
+    fuzz_score(&data);
+    fuzz_replay(&data[score.len() + 1..]);
+}
@@ -2,6 +2,11 @@ pub mod types;
 pub mod packets;
 pub mod reflection;
 pub mod serialization;
+pub mod codegen;
+pub mod conformance;
+pub mod fuzzing;
+#[cfg(feature="client")] pub mod client;
+#[cfg(feature="reference-server")] pub mod reference_server;
 #[cfg(feature="test")] mod tests;
 #[cfg(feature="server")] pub mod tables;
 
@@ -16,4 +21,5 @@ pub mod macros {
     pub use tataku_common_proc_macros::Serializable;
     pub(crate) use serde::{ Serialize, Deserialize };
     pub use tataku_common_proc_macros::PacketSerialization;
+    #[cfg(feature="test")] pub(crate) use tataku_common_proc_macros::RandomValue;
 }
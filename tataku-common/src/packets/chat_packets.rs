@@ -3,9 +3,11 @@ use crate::serialization::*;
 use crate::packets::PacketId;
 
 
+#[cfg_attr(feature="test", derive(crate::macros::RandomValue))]
 #[repr(u8)]
 #[allow(non_camel_case_types)]
 #[derive(PacketSerialization)]
+#[packet(framed, directional, handler = ChatHandler)]
 #[derive(Clone, Debug, Default)]
 pub enum ChatPacket {
     /// client is sending a message to the server
@@ -82,6 +84,7 @@ impl From<ChatPacket> for PacketId {
     }
 }
 
+#[cfg_attr(feature="test", derive(crate::macros::RandomValue))]
 #[derive(Serializable)]
 #[derive(Default, Clone, Debug)]
 pub struct ChatHistoryMessage {
@@ -0,0 +1,101 @@
+use crate::packets::*;
+
+/// sends each packet to the handler for its subsystem, so nobody has to match on nested packets by hand
+///
+/// packets for a subsystem without a handler are dropped.
+/// handlers can be borrowed (`with_chat(&mut handler)`) to get at them again afterwards
+#[derive(Default)]
+pub struct PacketDispatcher<'h> {
+    login: Option<Box<dyn LoginHandler + Send + 'h>>,
+    chat: Option<Box<dyn ChatHandler + Send + 'h>>,
+    spectator: Option<Box<dyn SpectatorHandler + Send + 'h>>,
+    multiplayer: Option<Box<dyn MultiplayerHandler + Send + 'h>>,
+}
+impl<'h> PacketDispatcher<'h> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// handles every packet which isnt chat, spectator or multiplayer
+    pub fn with_login(mut self, handler: impl LoginHandler + Send + 'h) -> Self {
+        self.login = Some(Box::new(handler));
+        self
+    }
+    pub fn with_chat(mut self, handler: impl ChatHandler + Send + 'h) -> Self {
+        self.chat = Some(Box::new(handler));
+        self
+    }
+    pub fn with_spectator(mut self, handler: impl SpectatorHandler + Send + 'h) -> Self {
+        self.spectator = Some(Box::new(handler));
+        self
+    }
+    pub fn with_multiplayer(mut self, handler: impl MultiplayerHandler + Send + 'h) -> Self {
+        self.multiplayer = Some(Box::new(handler));
+        self
+    }
+
+    pub fn dispatch(&mut self, packet: PacketId) {
+        match packet {
+            PacketId::Chat_Packet { packet } => if let Some(handler) = &mut self.chat { packet.dispatch(handler.as_mut()) },
+            PacketId::Spectator_Packet { host_id, packet } => if let Some(handler) = &mut self.spectator { packet.dispatch(handler.as_mut(), host_id) },
+            PacketId::Multiplayer_Packet { packet } => if let Some(handler) = &mut self.multiplayer { packet.dispatch(handler.as_mut()) },
+            packet => if let Some(handler) = &mut self.login { packet.dispatch(handler.as_mut()) },
+        }
+    }
+}
+
+
+#[test]
+fn test_dispatcher() {
+    use crate::types::network::*;
+
+    #[derive(Default)]
+    struct Handler {
+        calls: Vec<String>,
+    }
+    impl LoginHandler for Handler {
+        fn ping(&mut self) { self.calls.push("ping".to_owned()) }
+        fn server_login_response(&mut self, status: LoginStatus, user_id: u32) {
+            self.calls.push(format!("login {status:?} {user_id}"))
+        }
+    }
+    impl ChatHandler for Handler {
+        fn server_send_message(&mut self, sender_id: u32, channel: String, message: String) {
+            self.calls.push(format!("{sender_id} in {channel}: {message}"))
+        }
+    }
+    impl SpectatorHandler for Handler {
+        fn server_spectator_left(&mut self, host_id: u32, user_id: u32) {
+            self.calls.push(format!("{user_id} left {host_id}"))
+        }
+    }
+
+    let mut login = Handler::default();
+    let mut chat = Handler::default();
+    let mut dispatcher = PacketDispatcher::new()
+        .with_login(&mut login)
+        .with_chat(&mut chat);
+
+    for packet in [
+        PacketId::Ping,
+        PacketId::Server_LoginResponse { status: LoginStatus::Ok, user_id: 5 },
+        // not implemented, so nothing happens
+        PacketId::Pong,
+        ChatPacket::Server_SendMessage { sender_id: 2, channel: "#general".to_owned(), message: "hi".to_owned() }.into(),
+        ChatPacket::Client_GetFriends.into(),
+        // no spectator or multiplayer handler
+        SpectatorPacket::Server_SpectatorLeft { user_id: 3 }.with_host(4),
+        MultiplayerPacket::Server_LobbyStart.into(),
+    ] {
+        dispatcher.dispatch(packet);
+    }
+    drop(dispatcher);
+
+    assert_eq!(login.calls, ["ping", "login Ok 5"]);
+    assert_eq!(chat.calls, ["2 in #general: hi"]);
+
+    // the generated dispatch can be used without the dispatcher too
+    let mut spectator = Handler::default();
+    SpectatorPacket::Server_SpectatorLeft { user_id: 3 }.dispatch(&mut spectator, 4);
+    assert_eq!(spectator.calls, ["3 left 4"]);
+}
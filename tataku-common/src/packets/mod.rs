@@ -2,8 +2,44 @@ mod packet;
 mod chat_packets;
 mod spectator_packets;
 mod multiplayer_packets;
+mod dispatcher;
+mod routed;
 
 pub use packet::*;
 pub use chat_packets::*;
 pub use spectator_packets::*;
 pub use multiplayer_packets::*;
+pub use dispatcher::*;
+pub use routed::*;
+
+use crate::serialization::{ SchemaFn, WireSchema };
+
+/// every packet enum, for generating the protocol reference
+pub const PROTOCOL_SCHEMA: &[SchemaFn] = &[
+    PacketId::schema,
+    ChatPacket::schema,
+    SpectatorPacket::schema,
+    MultiplayerPacket::schema,
+];
+
+/// the protocol reference, as markdown
+pub fn protocol_reference() -> String {
+    crate::serialization::render_schema_markdown("Protocol reference", PROTOCOL_SCHEMA)
+}
+
+
+/// PROTOCOL.md is generated from the packet schemas.
+/// run with `UPDATE_PROTOCOL_REFERENCE=1` to regenerate it
+#[test]
+fn protocol_reference_is_up_to_date() {
+    let path = concat!(env!("CARGO_MANIFEST_DIR"), "/../PROTOCOL.md");
+    let reference = protocol_reference();
+
+    if std::env::var_os("UPDATE_PROTOCOL_REFERENCE").is_some() {
+        std::fs::write(path, &reference).unwrap();
+        return;
+    }
+
+    let existing = std::fs::read_to_string(path).unwrap_or_default();
+    assert!(existing == reference, "PROTOCOL.md is out of date, run the tests with UPDATE_PROTOCOL_REFERENCE=1 to regenerate it");
+}
@@ -7,9 +7,11 @@ use crate::types::{
 use crate::packets::PacketId;
 use std::collections::HashSet;
 
+#[cfg_attr(feature="test", derive(crate::macros::RandomValue))]
 #[repr(u8)]
 #[allow(non_camel_case_types)]
 #[derive(PacketSerialization)]
+#[packet(framed, directional, handler = MultiplayerHandler)]
 #[derive(Clone, Debug, Default)]
 #[allow(clippy::large_enum_variant)]
 pub enum MultiplayerPacket {
@@ -7,15 +7,18 @@ use crate::packets::*;
 use crate::serialization::*;
 
 
+#[cfg_attr(feature="test", derive(crate::macros::RandomValue))]
 #[allow(non_camel_case_types)]
 #[derive(PacketSerialization)]
+#[packet(framed, directional, handler = LoginHandler)]
 #[derive(Clone, Debug, Default)]
 #[allow(clippy::large_enum_variant)]
 #[repr(u16)]
 pub enum PacketId {
     // ======= Unknown =======
     /// we dont know what this packet is.
-    /// - if you get this, its probably best to stop reading the current incoming data
+    /// - if the protocol is framed, the packet's payload has already been skipped and it's safe to keep reading
+    /// - otherwise, its probably best to stop reading the current incoming data
     #[packet(id=0)]
     #[default] Unknown, 
 
@@ -33,10 +36,12 @@ pub enum PacketId {
     // ======= login/Server things =======
 
     /// Client wants to log into the server
+    /// - **NOTE**: this is always written with the legacy (unframed) protocol, since the server doesn't know which protocol the client speaks yet
     #[packet(id=100)]
     Client_UserLogin {
         /// which version of the packet protocol does this client use?
-        /// - this will help with future expandability
+        /// - see [`Protocol`] for what each version changes
+        /// - every packet after this one (in both directions) uses this protocol
         protocol_version: u16,
         /// user username
         username: String,
@@ -144,13 +149,13 @@ pub enum PacketId {
  
 
     // ======= Chat =======
-    #[packet(id=300)]
+    #[packet(id=300, no_handler, direction_from = packet)]
     Chat_Packet {
         packet: ChatPacket
     },
 
     // ======= Spectator =======
-    #[packet(id=400)]
+    #[packet(id=400, no_handler, direction_from = packet)]
     Spectator_Packet {
         /// user id of the host
         host_id: u32,
@@ -159,7 +164,7 @@ pub enum PacketId {
     },
 
     // ======= Multiplayer =======
-    #[packet(id=500)]
+    #[packet(id=500, no_handler, direction_from = packet)]
     Multiplayer_Packet {
         packet: MultiplayerPacket
     }
@@ -183,6 +188,49 @@ fn test() {
         s += &format!("{i:0x} ");
     }
     println!("{s}");
+}
+
+#[test]
+fn test_framed() {
+    let packet = PacketId::Chat_Packet { 
+        packet: ChatPacket::Client_SendMessage { 
+            channel: "#general".into(), 
+            message: "hello".into(),
+        }
+    };
+
+    let mut writer = SerializationWriter::new().with_protocol(Protocol::FRAMED);
+    writer.write(&packet);
+    // an unknown packet, which should be skipped
+    writer.write(&9999u16);
+    writer.write(&4u64);
+    writer.write(&1234u32);
+    writer.write(&PacketId::Ping);
+    let data = writer.data();
+
+    let mut reader = SerializationReader::new(data).with_protocol(Protocol::FRAMED);
+    let Ok(PacketId::Chat_Packet { packet: ChatPacket::Client_SendMessage { channel, message } }) = reader.read("packet") else { panic!("bad chat packet") };
+    assert_eq!(channel, "#general");
+    assert_eq!(message, "hello");
+
+    assert!(matches!(reader.read("packet"), Ok(PacketId::Unknown)));
+    assert!(matches!(reader.read("packet"), Ok(PacketId::Ping)));
+    assert!(!reader.can_read());
+}
+
+#[test]
+fn test_framed_trailing_fields() {
+    // a newer peer added a field to Server_UserLeft
+    let mut writer = SerializationWriter::new();
+    writer.write(&105u16);
+    writer.write(&(2 * std::mem::size_of::<u32>() as u64));
+    writer.write(&5u32);
+    writer.write(&10u32);
+    writer.write(&1u16);
+    writer.write(&0u64);
+    let data = writer.data();
 
-    let a = 100u16;
+    let mut reader = SerializationReader::new(data).with_protocol(Protocol::FRAMED);
+    assert!(matches!(reader.read("packet"), Ok(PacketId::Server_UserLeft { user_id: 5 })));
+    assert!(matches!(reader.read("packet"), Ok(PacketId::Ping)));
 }
@@ -0,0 +1,101 @@
+use crate::packets::PacketId;
+use crate::serialization::*;
+
+/// just enough of a [`PacketId`] to route it, borrowing the rest so it can be forwarded without copying or re-writing it
+///
+/// only framed protocols know how long a packet is without reading all of it, so this errors with [`Protocol::LEGACY`]
+#[derive(Copy, Clone, Debug)]
+pub struct RoutedPacket<'a> {
+    /// the [`PacketId`] variant id
+    pub id: u16,
+    /// the host a `Spectator_Packet` is for
+    pub host_id: Option<u32>,
+    /// the packet's fields, as they were written
+    pub payload: &'a [u8],
+    /// the whole packet (id and frame included), ready to be forwarded
+    pub data: &'a [u8],
+}
+impl RoutedPacket<'_> {
+    /// the name of the [`PacketId`] variant, if it's one we know about
+    pub fn variant(&self) -> Option<&'static str> {
+        <PacketId as HasVariantIds>::VARIANT_IDS.ids.iter()
+            .find(|(_, id)| *id == self.id as u64)
+            .map(|(name, _)| *name)
+    }
+
+    /// read the whole packet
+    pub fn read_packet(&self, protocol: Protocol) -> SerializationResult<PacketId> {
+        SerializationReader::from_slice(self.data).with_protocol(protocol).read("packet")
+    }
+}
+
+impl<'a> BorrowSerializable<'a> for RoutedPacket<'a> {
+    fn read_borrowed(sr: &mut SerializationReader<'a>) -> SerializationResult<Self> {
+        let start = sr.offset();
+        if !sr.is_borrowed() {
+            return Err(sr.invalid_value("RoutedPacket", "cant borrow from a reader which owns its data", start))
+        }
+
+        let id = sr.read::<u16>("packet_id")?;
+        let Some(end) = sr.begin_frame()? else {
+            return Err(sr.invalid_value("RoutedPacket", "packets can only be routed if the protocol is framed", start))
+        };
+        let payload_start = sr.offset();
+
+        let mut packet = Self { id, host_id: None, payload: &[], data: &[] };
+        if packet.variant() == Some("Spectator_Packet") {
+            packet.host_id = Some(sr.read("host_id")?);
+        }
+        sr.end_frame(Some(end));
+
+        // go back and borrow the whole thing
+        sr.unread(end - start);
+        packet.data = sr.read_borrowed_slice(end - start)?;
+        packet.payload = &packet.data[payload_start - start..];
+        Ok(packet)
+    }
+}
+
+
+#[test]
+fn test_routed_packet() {
+    use crate::packets::*;
+
+    let packets = [
+        ("Ping", PacketId::Ping),
+        ("Server_UserLeft", PacketId::Server_UserLeft { user_id: 10 }),
+        ("Spectator_Packet", SpectatorPacket::Server_SpectatorFrames { frames: Vec::new() }.with_host(5)),
+        ("Chat_Packet", ChatPacket::Client_SendMessage { channel: "#general".to_owned(), message: "hi".to_owned() }.into()),
+    ];
+
+    for protocol in [Protocol::FRAMED, Protocol::COMPACT, Protocol::REQUEST_IDS] {
+        let mut writer = SerializationWriter::new().with_protocol(protocol);
+        packets.iter().for_each(|(_, p)| writer.write(&Envelope::new(p.clone()).with_request_id(3)));
+        let data = writer.data();
+
+        let mut reader = SerializationReader::from_slice(&data).with_protocol(protocol);
+        for (variant, packet) in &packets {
+            let routed = reader.read_borrowed::<Envelope<RoutedPacket>>("packet").unwrap();
+            assert_eq!(routed.request_id, if protocol.has_request_ids() { Some(3) } else { None });
+
+            let routed = routed.packet;
+            assert_eq!(routed.variant(), Some(*variant));
+            assert_eq!(routed.host_id, if let PacketId::Spectator_Packet { host_id, .. } = packet { Some(*host_id) } else { None });
+
+            // forwarded as is
+            let mut plain = SerializationWriter::new().with_protocol(protocol);
+            plain.write(packet);
+            let plain = plain.data();
+            assert_eq!(routed.data, plain);
+            assert!(routed.data.ends_with(routed.payload));
+            assert_eq!(format!("{:?}", routed.read_packet(protocol).unwrap()), format!("{packet:?}"));
+        }
+        assert!(!reader.can_read());
+    }
+
+    // legacy packets dont say how long they are
+    let mut writer = SerializationWriter::new().with_protocol(Protocol::LEGACY);
+    writer.write(&PacketId::Ping);
+    let data = writer.data();
+    assert!(SerializationReader::from_slice(&data).with_protocol(Protocol::LEGACY).read_borrowed::<RoutedPacket>("packet").is_err());
+}
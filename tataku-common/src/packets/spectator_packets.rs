@@ -3,9 +3,11 @@ use crate::serialization::*;
 use crate::packets::PacketId;
 use crate::types::network::spectator::*;
 
+#[cfg_attr(feature="test", derive(crate::macros::RandomValue))]
 #[repr(u8)]
 #[allow(non_camel_case_types)]
 #[derive(PacketSerialization)]
+#[packet(framed, directional, handler = SpectatorHandler, handler_context(host_id: u32))]
 #[derive(Clone, Debug, Default)]
 pub enum SpectatorPacket {
     /// client wants to spectate someone
@@ -0,0 +1,71 @@
+use std::time::{ SystemTime, UNIX_EPOCH };
+use crate::packets::*;
+use crate::types::network::*;
+use super::state::State;
+
+/// how many messages each channel remembers for people who join later
+const HISTORY_LENGTH: usize = 100;
+
+impl State {
+    pub(super) fn handle_chat(&mut self, user_id: u32, packet: ChatPacket) {
+        match packet {
+            // channels dont have passwords here, any channel starting with # can be joined
+            ChatPacket::Client_JoinChannel { channel, .. } => {
+                if !channel.starts_with('#') {
+                    return self.reply(user_id, PacketId::Server_Error { code: ServerErrorCode::Unknown, error: format!("{channel} is not a channel") });
+                }
+                let Some(user) = self.users.get_mut(&user_id) else { return };
+                user.channels.insert(channel.clone());
+
+                let previous_messages = self.channels.entry(channel.clone()).or_default().clone();
+                self.reply(user_id, ChatPacket::Server_JoinChannel { channel, previous_messages });
+            }
+
+            ChatPacket::Client_SendMessage { channel, message } if channel.starts_with('#') => {
+                let Some(user) = self.users.get(&user_id) else { return };
+                if !user.channels.contains(&channel) {
+                    return self.reply(user_id, PacketId::Server_Error { code: ServerErrorCode::Unknown, error: format!("you are not in {channel}") });
+                }
+
+                let history = self.channels.entry(channel.clone()).or_default();
+                history.push(ChatHistoryMessage {
+                    user_id,
+                    username: user.username.clone(),
+                    time: SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_millis() as u64,
+                    message: message.clone(),
+                });
+                if history.len() > HISTORY_LENGTH { history.remove(0); }
+
+                let members = self.users.iter()
+                    .filter(|(id, u)| **id != user_id && u.channels.contains(&channel))
+                    .map(|(id, _)| *id)
+                    .collect::<Vec<_>>();
+                self.send_all(members, ChatPacket::Server_SendMessage { sender_id: user_id, channel, message });
+            }
+
+            // anything else is a dm, where the channel is the other user's username.
+            // the recipient sees the sender's username as the channel
+            ChatPacket::Client_SendMessage { channel, message } => {
+                let Some(sender) = self.users.get(&user_id) else { return };
+                let Some((&target, _)) = self.users.iter().find(|(_, u)| u.username == channel) else {
+                    return self.reply(user_id, PacketId::Server_Error { code: ServerErrorCode::Unknown, error: format!("{channel} is offline") });
+                };
+                self.send(target, ChatPacket::Server_SendMessage { sender_id: user_id, channel: sender.username.clone(), message });
+            }
+
+            ChatPacket::Client_GetFriends => {
+                let mut friend_ids = self.friends.get(&user_id).map(|f| f.iter().copied().collect::<Vec<_>>()).unwrap_or_default();
+                friend_ids.sort();
+                self.reply(user_id, ChatPacket::Server_FriendsList { friend_ids });
+            }
+
+            ChatPacket::Client_UpdateFriend { friend_id, is_friend } => {
+                let friends = self.friends.entry(user_id).or_default();
+                if is_friend { friends.insert(friend_id); } else { friends.remove(&friend_id); }
+                self.reply(user_id, ChatPacket::Server_UpdateFriend { friend_id, is_friend });
+            }
+
+            _ => {}
+        }
+    }
+}
@@ -0,0 +1,304 @@
+//! a reference server which keeps everything in memory, for testing clients without the real server (and its database)
+//!
+//! it handles logins (with pluggable [`Credentials`]), presence, chat channels, spectating and multiplayer lobbies.
+//! connections can be anything which is `AsyncRead + AsyncWrite`
+//!
+//! ```no_run
+//! # async fn run() -> std::io::Result<()> {
+//! use tataku_common::reference_server::ReferenceServer;
+//! let listener = tokio::net::TcpListener::bind("127.0.0.1:7270").await?;
+//! ReferenceServer::new().listen(listener).await
+//! # }
+//! ```
+mod state;
+mod chat;
+mod spectator;
+mod multiplayer;
+
+use state::*;
+use std::collections::HashMap;
+use std::sync::{ Arc, Mutex, MutexGuard };
+use tokio::io::{ AsyncRead, AsyncWrite, AsyncWriteExt };
+use tokio::sync::mpsc;
+use crate::packets::*;
+use crate::serialization::*;
+use crate::types::network::*;
+
+/// decides who can log in
+pub trait Credentials: Send + Sync + 'static {
+    /// check a login, and get the user's id
+    fn check(&self, username: &str, password: &str) -> Result<u32, LoginStatus>;
+}
+impl<F: Fn(&str, &str) -> Result<u32, LoginStatus> + Send + Sync + 'static> Credentials for F {
+    fn check(&self, username: &str, password: &str) -> Result<u32, LoginStatus> {
+        self(username, password)
+    }
+}
+
+/// lets anyone in with any password. each username gets its own id, starting from 1
+#[derive(Default)]
+pub struct AcceptAll {
+    ids: Mutex<HashMap<String, u32>>,
+}
+impl Credentials for AcceptAll {
+    fn check(&self, username: &str, _password: &str) -> Result<u32, LoginStatus> {
+        let mut ids = self.ids.lock().unwrap();
+        let next = ids.len() as u32 + 1;
+        Ok(*ids.entry(username.to_owned()).or_insert(next))
+    }
+}
+
+
+/// the server. clones share the same state
+#[derive(Clone)]
+pub struct ReferenceServer {
+    credentials: Arc<dyn Credentials>,
+    state: Arc<Mutex<State>>,
+}
+impl ReferenceServer {
+    /// a server which lets anyone in
+    pub fn new() -> Self {
+        Self::with_credentials(AcceptAll::default())
+    }
+    pub fn with_credentials(credentials: impl Credentials) -> Self {
+        Self {
+            credentials: Arc::new(credentials),
+            state: Default::default(),
+        }
+    }
+
+    fn state(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap()
+    }
+
+    /// ids of the users who are online
+    pub fn online_users(&self) -> Vec<u32> {
+        let mut users = self.state().users.keys().copied().collect::<Vec<_>>();
+        users.sort();
+        users
+    }
+
+    /// drop a user's connection, ie to test how a client handles it
+    pub fn drop_user(&self, user_id: u32, reason: ServerDropReason, message: impl Into<String>) {
+        self.state().drop_user(user_id, reason, message.into());
+    }
+
+    /// drop everyone with [`ServerDropReason::ServerClosing`]
+    pub fn close(&self) {
+        let mut state = self.state();
+        for user_id in state.users.keys().copied().collect::<Vec<_>>() {
+            state.drop_user(user_id, ServerDropReason::ServerClosing, "server closing".to_owned());
+        }
+    }
+
+    /// accept tcp connections forever
+    pub async fn listen(&self, listener: tokio::net::TcpListener) -> std::io::Result<()> {
+        loop {
+            let (stream, _) = listener.accept().await?;
+            stream.set_nodelay(true)?;
+            self.accept(stream);
+        }
+    }
+
+    /// serve a connection in the background
+    pub fn accept<S: AsyncRead + AsyncWrite + Send + 'static>(&self, stream: S) -> tokio::task::JoinHandle<Result<(), DecodeError>> {
+        let server = self.clone();
+        tokio::spawn(async move { server.serve(stream).await })
+    }
+
+    /// an in-memory connection to this server, for a client to use
+    pub fn connect_local(&self) -> tokio::io::DuplexStream {
+        let (client, server) = tokio::io::duplex(64 * 1024);
+        self.accept(server);
+        client
+    }
+
+    /// serve a connection until it logs out or closes
+    pub async fn serve<S: AsyncRead + AsyncWrite + Send + 'static>(&self, stream: S) -> Result<(), DecodeError> {
+        let (mut read, mut write) = tokio::io::split(stream);
+        // only packets clients are allowed to send get through
+        let mut decoder = PacketDecoder::<Envelope<ClientToServer<PacketId>>>::new().with_limits(DecodeLimits::untrusted());
+
+        // the login is always legacy
+        let Some(Envelope { packet: PacketIdClientToServer::Client_UserLogin { protocol_version, username, password, game }, .. }) = decoder.read_packet_async(&mut read).await? else { return Ok(()) };
+        let protocol = Protocol::from_version(protocol_version);
+
+        // cant answer in a protocol we dont speak
+        if protocol < Protocol::LEGACY || protocol > Protocol::CURRENT {
+            write.write_all(&encode(Protocol::LEGACY, &PacketId::Server_LoginResponse { status: LoginStatus::UnknownError, user_id: 0 })).await?;
+            return Ok(())
+        }
+        decoder.set_protocol(protocol);
+
+        let user_id = match self.credentials.check(&username, &password) {
+            Ok(user_id) => user_id,
+            Err(status) => {
+                write.write_all(&encode(protocol, &Envelope::new(PacketId::Server_LoginResponse { status, user_id: 0 }))).await?;
+                return Ok(())
+            }
+        };
+
+        let (sender, outgoing) = mpsc::unbounded_channel();
+        let writer = tokio::spawn(write_loop(write, protocol, outgoing));
+        let connection = self.state().login(user_id, username, game, sender.clone());
+
+        let result = loop {
+            tokio::select! {
+                packet = decoder.read_packet_async(&mut read) => match packet {
+                    Ok(Some(Envelope { packet: PacketIdClientToServer::Client_LogOut, .. }) | None) => break Ok(()),
+                    Ok(Some(packet)) => self.state().handle(user_id, packet.map(PacketId::from)),
+                    Err(e) => {
+                        self.state().drop_connection(user_id, connection, ServerDropReason::BadPacket, e.to_string());
+                        break Err(e)
+                    }
+                },
+                // we were dropped (or the writer failed)
+                _ = sender.closed() => break Ok(()),
+            }
+        };
+
+        self.state().logout(user_id, connection);
+        drop(sender);
+        let _ = writer.await;
+        result
+    }
+}
+impl Default for ReferenceServer {
+    fn default() -> Self { Self::new() }
+}
+
+fn encode(protocol: Protocol, packet: &impl Serializable) -> Vec<u8> {
+    let mut writer = SerializationWriter::new().with_protocol(protocol);
+    writer.write(packet);
+    writer.data()
+}
+
+async fn write_loop<W: AsyncWrite + Unpin>(mut stream: W, protocol: Protocol, mut outgoing: mpsc::UnboundedReceiver<Envelope<PacketId>>) {
+    while let Some(packet) = outgoing.recv().await {
+        if stream.write_all(&encode(protocol, &packet)).await.is_err() || stream.flush().await.is_err() { break }
+
+        // nothing gets sent after the connection is dropped
+        if matches!(packet.packet, PacketId::Server_DropConnection { .. }) { break }
+    }
+
+    let _ = stream.shutdown().await;
+}
+
+
+#[cfg(all(test, feature = "client"))]
+async fn test_client(server: &ReferenceServer, username: &str) -> crate::client::TatakuClient {
+    crate::client::TatakuClient::login(server.connect_local(), username, "password", "tataku\n1.0").await.unwrap()
+}
+
+#[cfg(feature = "client")]
+#[tokio::test]
+async fn test_reference_server() {
+    use crate::client::*;
+    use crate::types::network::multiplayer::*;
+    let server = ReferenceServer::with_credentials(|username: &str, password: &str| match (username, password) {
+        ("host", _) => Ok(1),
+        ("player", "password") => Ok(2),
+        _ => Err(LoginStatus::BadPassword),
+    });
+
+    let bad = TatakuClient::login(server.connect_local(), "player", "wrong", "tataku").await;
+    assert!(matches!(bad, Err(ClientError::Login(LoginStatus::BadPassword))));
+
+    let host = test_client(&server, "host").await;
+    let mut host_events = host.events();
+    let player = test_client(&server, "player").await;
+    assert_eq!(player.user_id(), 2);
+    assert!(matches!(host_events.recv().await, Ok(PacketId::Server_UserJoined { user_id: 2, .. })));
+    assert_eq!(server.online_users(), [1, 2]);
+
+    // chat
+    let mut player_chat = player.chat_events();
+    host.join_channel("#general", "").await.unwrap();
+    // replies go to the request they're for
+    let (joined, not_a_channel) = tokio::join!(player.join_channel("#general", ""), player.join_channel("general", ""));
+    assert!(joined.is_ok());
+    assert!(matches!(not_a_channel, Err(ClientError::Server { code: ServerErrorCode::Unknown, .. })));
+    assert!(matches!(player_chat.recv().await, Ok(ChatPacket::Server_JoinChannel { .. })));
+    host.send_message("#general", "hello").unwrap();
+    assert!(matches!(player_chat.recv().await, Ok(ChatPacket::Server_SendMessage { sender_id: 1, message, .. }) if message == "hello"));
+
+    // lobbies
+    let lobby = host.create_lobby("lobby", "secret", false, 4).await.unwrap();
+    assert_eq!(lobby.host, 1);
+    assert!(matches!(player.join_lobby(lobby.id, "wrong").await, Err(ClientError::Rejected)));
+    let mut host_lobby = host.multiplayer_events();
+    let joined = player.join_lobby(lobby.id, "secret").await.unwrap();
+    assert_eq!(joined.players.len(), 2);
+    assert!(matches!(host_lobby.recv().await, Ok(MultiplayerPacket::Server_LobbyUserJoined { user_id: 2, .. })));
+    assert_eq!(player.lobby_list().await.unwrap().len(), 1);
+
+    // a round
+    let mut player_lobby = player.multiplayer_events();
+    host.send(MultiplayerPacket::Client_LobbyStart.into()).unwrap();
+    assert!(matches!(player_lobby.recv().await, Ok(MultiplayerPacket::Server_LobbyStart)));
+    for client in [&host, &player] {
+        client.send(MultiplayerPacket::Client_LobbyMapLoaded.into()).unwrap();
+    }
+    loop {
+        match player_lobby.recv().await.unwrap() {
+            MultiplayerPacket::Server_LobbyBeginRound => break,
+            MultiplayerPacket::Server_LobbyStateChange { new_state: LobbyState::Playing, .. } => {}
+            other => panic!("unexpected {other:?}"),
+        }
+    }
+
+    // spectating
+    let mut host_spec = host.spectator_events();
+    let result = player.request(
+        SpectatorPacket::Client_Spectate.with_host(1),
+        |p| matches!(p, PacketId::Spectator_Packet { packet: SpectatorPacket::Server_SpectateResult { .. }, .. })
+    ).await.unwrap();
+    assert!(matches!(result, PacketId::Spectator_Packet { host_id: 1, packet: SpectatorPacket::Server_SpectateResult { result: crate::types::network::spectator::SpectateResult::Ok } }));
+    assert!(matches!(host_spec.recv().await, Ok(SpectatorEvent { host_id: 1, packet: SpectatorPacket::Server_SpectatorJoined { user_id: 2, .. } })));
+    let mut player_spec = player.spectator_events();
+    host.send(SpectatorPacket::Client_SpectatorFrames { frames: Vec::new() }.with_host(1)).unwrap();
+    assert!(matches!(player_spec.recv().await, Ok(SpectatorEvent { host_id: 1, packet: SpectatorPacket::Server_SpectatorFrames { .. } })));
+
+    // leaving
+    player.logout().await.unwrap();
+    loop {
+        if let Ok(PacketId::Server_UserLeft { user_id: 2 }) = host_events.recv().await { break }
+    }
+    assert_eq!(server.online_users(), [1]);
+
+    server.close();
+    assert!(matches!(host.disconnected().await, DisconnectReason::Dropped { reason: ServerDropReason::ServerClosing, .. }));
+}
+
+#[cfg(feature = "client")]
+#[tokio::test]
+async fn test_reference_server_other_login() {
+    use crate::client::*;
+    let server = ReferenceServer::new();
+    let first = test_client(&server, "user").await;
+    let second = test_client(&server, "user").await;
+    assert_eq!(first.user_id(), second.user_id());
+    assert!(matches!(first.disconnected().await, DisconnectReason::Dropped { reason: ServerDropReason::OtherLogin, .. }));
+    assert!(second.is_connected());
+    assert_eq!(server.online_users(), [second.user_id()]);
+
+    // a bad packet drops the connection
+    let mut stream = server.connect_local();
+    let mut login = SerializationWriter::new();
+    login.write(&PacketId::Client_UserLogin { protocol_version: 2, username: "bad".to_owned(), password: String::new(), game: String::new() });
+    stream.write_all(&login.data()).await.unwrap();
+    // clients can't send server packets
+    let mut permissions = SerializationWriter::new().with_protocol(Protocol::FRAMED);
+    permissions.write(&PacketId::Server_Permissions { user_id: 1, permissions: vec![ServerPermissions::Moderator] });
+    stream.write_all(&permissions.data()).await.unwrap();
+
+    let mut decoder = PacketDecoder::<PacketId>::new().with_protocol(Protocol::FRAMED);
+    assert!(matches!(decoder.read_packet_async(&mut stream).await, Ok(Some(PacketId::Server_LoginResponse { status: LoginStatus::Ok, .. }))));
+    loop {
+        match decoder.read_packet_async(&mut stream).await.unwrap() {
+            Some(PacketId::Server_DropConnection { reason: ServerDropReason::BadPacket, .. }) => break,
+            Some(_) => {}
+            None => panic!("connection closed without a reason"),
+        }
+    }
+}
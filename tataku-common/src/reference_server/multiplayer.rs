@@ -0,0 +1,306 @@
+use std::collections::{ HashMap, HashSet };
+use crate::packets::*;
+use crate::types::network::multiplayer::*;
+use super::state::State;
+
+pub(super) struct Lobby {
+    pub info: FullLobbyInfo,
+    pub password: String,
+    pub private: bool,
+    /// invited users dont need the password
+    pub invited: HashSet<u32>,
+
+    /// who is playing the current round
+    pub playing: HashSet<u32>,
+    /// who has loaded the map this round
+    pub loaded: HashSet<u32>,
+    /// who has finished the map this round
+    pub complete: HashSet<u32>,
+}
+impl Lobby {
+    /// what's shown in the lobby list
+    pub fn summary(&self) -> LobbyInfo {
+        LobbyInfo {
+            id: self.info.id,
+            name: self.info.name.clone(),
+            has_password: !self.password.is_empty(),
+            host: self.info.host,
+            players: self.members(),
+            current_beatmap: self.info.current_beatmap.as_ref().map(|map| map.title.clone()),
+            state: self.info.state,
+        }
+    }
+
+    pub fn members(&self) -> Vec<u32> {
+        self.info.players.iter().map(|p| p.user_id).collect()
+    }
+
+    fn player(&mut self, user_id: u32) -> Option<&mut LobbyUser> {
+        self.info.players.iter_mut().find(|p| p.user_id == user_id)
+    }
+
+    /// the first free slot
+    fn free_slot(&self) -> Option<u8> {
+        let mut slots = self.info.slots.iter().filter(|(_, s)| s.is_free()).map(|(i, _)| *i).collect::<Vec<_>>();
+        slots.sort();
+        slots.first().copied()
+    }
+
+    fn slot_of(&self, user_id: u32) -> Option<u8> {
+        self.info.slots.iter().find(|(_, s)| **s == LobbySlot::Filled { user: user_id }).map(|(i, _)| *i)
+    }
+}
+
+impl State {
+    /// members of a lobby, and (unless it's private) everyone listening for lobby updates
+    fn lobby_audience(&self, lobby: &Lobby) -> HashSet<u32> {
+        let mut users = lobby.members().into_iter().collect::<HashSet<_>>();
+        if !lobby.private { users.extend(&self.lobby_listeners) }
+        users
+    }
+
+    /// the lobby `user_id` is in, if they're its host
+    fn hosted_lobby(&self, user_id: u32) -> Option<u32> {
+        let lobby_id = self.users.get(&user_id)?.lobby?;
+        (self.lobbies.get(&lobby_id)?.info.host == user_id).then_some(lobby_id)
+    }
+
+    pub(super) fn handle_multiplayer(&mut self, user_id: u32, packet: MultiplayerPacket) {
+        let lobby_id = self.users.get(&user_id).and_then(|u| u.lobby);
+
+        match packet {
+            MultiplayerPacket::Client_LobbyList => {
+                let mut lobbies = self.lobbies.values().filter(|l| !l.private).map(Lobby::summary).collect::<Vec<_>>();
+                lobbies.sort_by_key(|l| l.id);
+                self.reply(user_id, MultiplayerPacket::Server_LobbyList { lobbies });
+            }
+            MultiplayerPacket::Client_AddLobbyListener => { self.lobby_listeners.insert(user_id); }
+            MultiplayerPacket::Client_RemoveLobbyListener => { self.lobby_listeners.remove(&user_id); }
+
+            MultiplayerPacket::Client_CreateLobby { name, password, private, players } => {
+                if players == 0 {
+                    return self.reply(user_id, MultiplayerPacket::Server_CreateLobby { success: false, lobby: None });
+                }
+                self.leave_lobby(user_id);
+
+                self.next_lobby_id += 1;
+                let id = self.next_lobby_id;
+                let mut slots = (0..players).map(|i| (i, LobbySlot::Empty)).collect::<HashMap<_, _>>();
+                slots.insert(0, LobbySlot::Filled { user: user_id });
+
+                let lobby = Lobby {
+                    info: FullLobbyInfo {
+                        id,
+                        name,
+                        host: user_id,
+                        state: LobbyState::Idle,
+                        players: vec![LobbyUser { user_id, state: LobbyUserState::NoMap, ..Default::default() }],
+                        slots,
+                        current_beatmap: None,
+                    },
+                    password,
+                    private,
+                    invited: HashSet::new(),
+                    playing: HashSet::new(),
+                    loaded: HashSet::new(),
+                    complete: HashSet::new(),
+                };
+                if let Some(user) = self.users.get_mut(&user_id) { user.lobby = Some(id) }
+                self.reply(user_id, MultiplayerPacket::Server_CreateLobby { success: true, lobby: Some(lobby.info.clone()) });
+                if !private {
+                    let listeners = self.lobby_listeners.iter().copied().collect::<Vec<_>>();
+                    self.send_all(listeners, MultiplayerPacket::Server_LobbyCreated { lobby: lobby.summary() });
+                }
+                self.lobbies.insert(id, lobby);
+            }
+
+            MultiplayerPacket::Client_LobbyInvite { user_id: invited } => {
+                let Some(lobby) = lobby_id.and_then(|id| self.lobbies.get_mut(&id)) else { return };
+                lobby.invited.insert(invited);
+                let lobby = lobby.summary();
+                self.send(invited, MultiplayerPacket::Server_LobbyInvite { inviter_id: user_id, lobby });
+            }
+
+            MultiplayerPacket::Client_JoinLobby { lobby_id: join_id, password } => {
+                if lobby_id == Some(join_id) { return }
+
+                let can_join = self.lobbies.get(&join_id).is_some_and(|lobby| {
+                    (lobby.password.is_empty() || lobby.password == password || lobby.invited.contains(&user_id))
+                    && lobby.free_slot().is_some()
+                });
+                if !can_join {
+                    return self.reply(user_id, MultiplayerPacket::Server_JoinLobby { success: false, lobby: None });
+                }
+                self.leave_lobby(user_id);
+
+                let Some(lobby) = self.lobbies.get_mut(&join_id) else { return };
+                let Some(slot) = lobby.free_slot() else { return };
+                let new_status = LobbySlot::Filled { user: user_id };
+                lobby.info.slots.insert(slot, new_status);
+                lobby.info.players.push(LobbyUser { user_id, state: LobbyUserState::NoMap, ..Default::default() });
+                lobby.invited.remove(&user_id);
+                let info = lobby.info.clone();
+                if let Some(user) = self.users.get_mut(&user_id) { user.lobby = Some(join_id) }
+
+                let lobby = &self.lobbies[&join_id];
+                let members = lobby.members().into_iter().filter(|id| *id != user_id).collect::<Vec<_>>();
+                self.reply(user_id, MultiplayerPacket::Server_JoinLobby { success: true, lobby: Some(info) });
+                self.send_all(self.lobby_audience(lobby).into_iter().filter(|id| *id != user_id), MultiplayerPacket::Server_LobbyUserJoined { lobby_id: join_id, user_id });
+                self.send_all(members, MultiplayerPacket::Server_LobbySlotChange { slot, new_status });
+            }
+
+            MultiplayerPacket::Client_LeaveLobby => self.leave_lobby(user_id),
+
+            MultiplayerPacket::Client_LobbyMapChange { new_map } => {
+                let Some(lobby_id) = self.hosted_lobby(user_id) else { return };
+                let Some(lobby) = self.lobbies.get_mut(&lobby_id) else { return };
+                lobby.info.current_beatmap = Some(new_map.clone());
+
+                let lobby = &self.lobbies[&lobby_id];
+                self.send_all(self.lobby_audience(lobby), MultiplayerPacket::Server_LobbyMapChange { lobby_id, new_map });
+            }
+
+            // the host can lock and unlock empty slots
+            MultiplayerPacket::Client_LobbySlotChange { slot, new_status } => {
+                let Some(lobby_id) = self.hosted_lobby(user_id) else { return };
+                let Some(lobby) = self.lobbies.get_mut(&lobby_id) else { return };
+                let Some(current) = lobby.info.slots.get_mut(&slot) else { return };
+                if !matches!((*current, new_status), (LobbySlot::Empty, LobbySlot::Locked) | (LobbySlot::Locked, LobbySlot::Empty)) { return }
+                *current = new_status;
+
+                let members = lobby.members();
+                self.send_all(members, MultiplayerPacket::Server_LobbySlotChange { slot, new_status });
+            }
+
+            MultiplayerPacket::Client_LobbyUserState { new_state } => {
+                let Some(lobby) = lobby_id.and_then(|id| self.lobbies.get_mut(&id)) else { return };
+                let Some(player) = lobby.player(user_id) else { return };
+                player.state = new_state;
+
+                let members = lobby.members();
+                self.send_all(members, MultiplayerPacket::Server_LobbyUserState { user_id, new_state });
+            }
+
+            MultiplayerPacket::Client_LobbyUserModsChanged { mods, speed } => {
+                let Some(lobby) = lobby_id.and_then(|id| self.lobbies.get_mut(&id)) else { return };
+                let Some(player) = lobby.player(user_id) else { return };
+                player.mods = mods.clone();
+                player.speed = speed;
+
+                let members = lobby.members();
+                self.send_all(members, MultiplayerPacket::Server_LobbyUserModsChanged { user_id, mods, speed });
+            }
+
+            MultiplayerPacket::Client_LobbyChangeHost { new_host } => {
+                let Some(lobby_id) = self.hosted_lobby(user_id) else { return };
+                let Some(lobby) = self.lobbies.get_mut(&lobby_id) else { return };
+                if lobby.player(new_host).is_none() { return }
+                lobby.info.host = new_host;
+
+                let members = lobby.members();
+                self.send_all(members, MultiplayerPacket::Server_LobbyChangeHost { new_host });
+            }
+
+            // everyone in the lobby plays the round
+            MultiplayerPacket::Client_LobbyStart => {
+                let Some(lobby_id) = self.hosted_lobby(user_id) else { return };
+                let Some(lobby) = self.lobbies.get_mut(&lobby_id) else { return };
+                if lobby.info.state == LobbyState::Playing { return }
+                lobby.info.state = LobbyState::Playing;
+                lobby.playing = lobby.members().into_iter().collect();
+                lobby.loaded.clear();
+                lobby.complete.clear();
+
+                let lobby = &self.lobbies[&lobby_id];
+                self.send_all(lobby.members(), MultiplayerPacket::Server_LobbyStart);
+                self.send_all(self.lobby_audience(lobby), MultiplayerPacket::Server_LobbyStateChange { lobby_id, new_state: LobbyState::Playing });
+            }
+
+            MultiplayerPacket::Client_LobbyMapLoaded => {
+                let Some(lobby_id) = lobby_id else { return };
+                let Some(lobby) = self.lobbies.get_mut(&lobby_id) else { return };
+                if !lobby.playing.contains(&user_id) { return }
+                lobby.loaded.insert(user_id);
+                self.check_round(lobby_id);
+            }
+
+            MultiplayerPacket::Client_LobbyScoreUpdate { score } => {
+                let Some(lobby) = lobby_id.and_then(|id| self.lobbies.get(&id)) else { return };
+                let others = lobby.members().into_iter().filter(|id| *id != user_id).collect::<Vec<_>>();
+                self.send_all(others, MultiplayerPacket::Server_LobbyScoreUpdate { user_id, score });
+            }
+
+            MultiplayerPacket::Client_LobbyMapComplete { score } => {
+                let Some(lobby_id) = lobby_id else { return };
+                let Some(lobby) = self.lobbies.get_mut(&lobby_id) else { return };
+                if !lobby.playing.contains(&user_id) { return }
+                lobby.complete.insert(user_id);
+
+                let members = lobby.members();
+                self.send_all(members, MultiplayerPacket::Server_LobbyPlayerMapComplete { user_id, score });
+                self.check_round(lobby_id);
+            }
+
+            _ => {}
+        }
+    }
+
+    /// start the round once everyone has loaded, and end it once everyone has finished
+    fn check_round(&mut self, lobby_id: u32) {
+        let Some(lobby) = self.lobbies.get_mut(&lobby_id) else { return };
+        if lobby.info.state != LobbyState::Playing { return }
+
+        if lobby.complete.is_superset(&lobby.playing) {
+            lobby.info.state = LobbyState::Idle;
+            lobby.playing.clear();
+
+            let lobby = &self.lobbies[&lobby_id];
+            self.send_all(lobby.members(), MultiplayerPacket::Server_LobbyRoundComplete);
+            self.send_all(self.lobby_audience(lobby), MultiplayerPacket::Server_LobbyStateChange { lobby_id, new_state: LobbyState::Idle });
+        } else if !lobby.loaded.is_empty() && lobby.loaded.is_superset(&lobby.playing) {
+            // only sent once
+            lobby.loaded.clear();
+            let members = lobby.members();
+            self.send_all(members, MultiplayerPacket::Server_LobbyBeginRound);
+        }
+    }
+
+    /// take `user_id` out of their lobby. if they were the host, someone else is, and empty lobbies are deleted
+    pub(super) fn leave_lobby(&mut self, user_id: u32) {
+        let Some(lobby_id) = self.users.get_mut(&user_id).and_then(|u| u.lobby.take()) else { return };
+        let Some(lobby) = self.lobbies.get(&lobby_id) else { return };
+        // the user leaving hears about it too
+        self.send_all(self.lobby_audience(lobby), MultiplayerPacket::Server_LobbyUserLeft { lobby_id, user_id });
+
+        let Some(lobby) = self.lobbies.get_mut(&lobby_id) else { return };
+        lobby.info.players.retain(|p| p.user_id != user_id);
+        lobby.playing.remove(&user_id);
+        lobby.loaded.remove(&user_id);
+        lobby.complete.remove(&user_id);
+        let slot = lobby.slot_of(user_id);
+        if let Some(slot) = slot { lobby.info.slots.insert(slot, LobbySlot::Empty); }
+
+        let members = lobby.members();
+        let Some(&next_host) = members.first() else {
+            let private = lobby.private;
+            self.lobbies.remove(&lobby_id);
+            if !private {
+                let listeners = self.lobby_listeners.iter().copied().collect::<Vec<_>>();
+                self.send_all(listeners, MultiplayerPacket::Server_LobbyDeleted { lobby_id });
+            }
+            return
+        };
+
+        let host_left = lobby.info.host == user_id;
+        if host_left { lobby.info.host = next_host }
+        if let Some(slot) = slot {
+            self.send_all(members.clone(), MultiplayerPacket::Server_LobbySlotChange { slot, new_status: LobbySlot::Empty });
+        }
+        if host_left {
+            self.send_all(members, MultiplayerPacket::Server_LobbyChangeHost { new_host: next_host });
+        }
+
+        // they might have been the last one the round was waiting for
+        self.check_round(lobby_id);
+    }
+}
@@ -0,0 +1,50 @@
+use crate::packets::*;
+use crate::types::network::spectator::SpectateResult;
+use super::state::State;
+
+impl State {
+    pub(super) fn handle_spectator(&mut self, user_id: u32, host_id: u32, packet: SpectatorPacket) {
+        match packet {
+            SpectatorPacket::Client_Spectate => {
+                let result = if host_id == user_id {
+                    SpectateResult::Error_SpectatingYourself
+                } else if !self.users.contains_key(&host_id) {
+                    SpectateResult::Error_HostOffline
+                } else {
+                    SpectateResult::Ok
+                };
+                self.reply(user_id, SpectatorPacket::Server_SpectateResult { result }.with_host(host_id));
+                if !matches!(result, SpectateResult::Ok) { return }
+
+                self.stop_spectating(user_id);
+                let Some(user) = self.users.get_mut(&user_id) else { return };
+                user.spectating = Some(host_id);
+                let username = user.username.clone();
+
+                if let Some(host) = self.users.get_mut(&host_id) { host.spectators.insert(user_id); }
+                self.send(host_id, SpectatorPacket::Server_SpectatorJoined { user_id, username }.with_host(host_id));
+            }
+
+            SpectatorPacket::Client_LeaveSpectator => self.stop_spectating(user_id),
+
+            // frames are only relayed from the host themselves
+            SpectatorPacket::Client_SpectatorFrames { frames } => {
+                let Some(user) = self.users.get(&user_id) else { return };
+                let spectators = user.spectators.iter().copied().collect::<Vec<_>>();
+                self.send_all(spectators, SpectatorPacket::Server_SpectatorFrames { frames }.with_host(user_id));
+            }
+
+            _ => {}
+        }
+    }
+
+    /// stop `user_id` spectating whoever they're spectating
+    pub(super) fn stop_spectating(&mut self, user_id: u32) {
+        let Some(host_id) = self.users.get_mut(&user_id).and_then(|u| u.spectating.take()) else { return };
+        if let Some(host) = self.users.get_mut(&host_id) { host.spectators.remove(&user_id); }
+
+        let left = SpectatorPacket::Server_SpectatorLeft { user_id }.with_host(host_id);
+        self.send(host_id, left.clone());
+        self.send(user_id, left);
+    }
+}
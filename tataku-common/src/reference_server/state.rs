@@ -0,0 +1,167 @@
+use std::collections::{ HashMap, HashSet };
+use tokio::sync::mpsc;
+use crate::packets::*;
+use crate::serialization::Envelope;
+use crate::types::UserAction;
+use crate::types::network::*;
+use super::multiplayer::Lobby;
+
+/// everything the server knows about
+#[derive(Default)]
+pub(super) struct State {
+    pub users: HashMap<u32, User>,
+    /// message history of each channel, oldest first
+    pub channels: HashMap<String, Vec<ChatHistoryMessage>>,
+    /// friends of each user, kept after they log out
+    pub friends: HashMap<u32, HashSet<u32>>,
+    pub lobbies: HashMap<u32, Lobby>,
+    /// users who want to know about lobby updates
+    pub lobby_listeners: HashSet<u32>,
+    pub next_lobby_id: u32,
+    next_connection: u64,
+    /// the request being handled, which [`Self::reply`] answers
+    request_id: Option<u32>,
+}
+
+/// someone who is online
+pub(super) struct User {
+    /// which connection this user is on, so an old connection closing doesnt log out a new one
+    pub connection: u64,
+    pub username: String,
+    pub game: String,
+    sender: mpsc::UnboundedSender<Envelope<PacketId>>,
+
+    pub action: UserAction,
+    pub action_text: String,
+    pub mode: String,
+
+    /// chat channels this user has joined
+    pub channels: HashSet<String>,
+    /// who this user is spectating
+    pub spectating: Option<u32>,
+    /// who is spectating this user
+    pub spectators: HashSet<u32>,
+    pub lobby: Option<u32>,
+}
+
+impl State {
+    /// send a packet to a user, if they're online
+    pub fn send(&self, user_id: u32, packet: impl Into<PacketId>) {
+        if let Some(user) = self.users.get(&user_id) {
+            // the connection is closing, it'll be cleaned up by its own task
+            let _ = user.sender.send(Envelope::new(packet.into()));
+        }
+    }
+
+    /// answer the packet being handled, so the client knows which request this is for
+    pub fn reply(&self, user_id: u32, packet: impl Into<PacketId>) {
+        if let Some(user) = self.users.get(&user_id) {
+            let _ = user.sender.send(Envelope { request_id: self.request_id, packet: packet.into() });
+        }
+    }
+
+    /// send a packet to each of `users`
+    pub fn send_all(&self, users: impl IntoIterator<Item = u32>, packet: impl Into<PacketId>) {
+        let packet = packet.into();
+        users.into_iter().for_each(|user_id| self.send(user_id, packet.clone()));
+    }
+
+    /// everyone online except `user_id`
+    pub fn others(&self, user_id: u32) -> Vec<u32> {
+        self.users.keys().copied().filter(|id| *id != user_id).collect()
+    }
+
+    /// a user has logged in. if they were already online, their old connection is dropped
+    pub fn login(&mut self, user_id: u32, username: String, game: String, sender: mpsc::UnboundedSender<Envelope<PacketId>>) -> u64 {
+        self.drop_user(user_id, ServerDropReason::OtherLogin, "logged in from somewhere else".to_owned());
+
+        let _ = sender.send(PacketId::Server_LoginResponse { status: LoginStatus::Ok, user_id }.into());
+        // tell them who is already here
+        for (&id, user) in &self.users {
+            let _ = sender.send(PacketId::Server_UserJoined { user_id: id, username: user.username.clone(), game: user.game.clone() }.into());
+            let _ = sender.send(PacketId::Server_UserStatusUpdate { user_id: id, action: user.action, action_text: user.action_text.clone(), mode: user.mode.clone() }.into());
+        }
+        self.send_all(self.others(user_id), PacketId::Server_UserJoined { user_id, username: username.clone(), game: game.clone() });
+
+        self.next_connection += 1;
+        self.users.insert(user_id, User {
+            connection: self.next_connection,
+            username,
+            game,
+            sender,
+            action: UserAction::Idle,
+            action_text: String::new(),
+            mode: String::new(),
+            channels: HashSet::new(),
+            spectating: None,
+            spectators: HashSet::new(),
+            lobby: None,
+        });
+        self.next_connection
+    }
+
+    /// a connection has closed. does nothing if the user has logged in again since
+    pub fn logout(&mut self, user_id: u32, connection: u64) {
+        if self.users.get(&user_id).is_some_and(|u| u.connection == connection) {
+            self.remove_user(user_id);
+        }
+    }
+
+    /// tell a user why they're being dropped, and remove them
+    pub fn drop_user(&mut self, user_id: u32, reason: ServerDropReason, message: String) {
+        if !self.users.contains_key(&user_id) { return }
+        self.send(user_id, PacketId::Server_DropConnection { reason, message });
+        self.remove_user(user_id);
+    }
+
+    /// [`Self::drop_user`], but only if the user is still on `connection`
+    pub fn drop_connection(&mut self, user_id: u32, connection: u64, reason: ServerDropReason, message: String) {
+        if self.users.get(&user_id).is_some_and(|u| u.connection == connection) {
+            self.drop_user(user_id, reason, message);
+        }
+    }
+
+    fn remove_user(&mut self, user_id: u32) {
+        self.leave_lobby(user_id);
+        self.stop_spectating(user_id);
+        self.lobby_listeners.remove(&user_id);
+
+        // anyone spectating this user has nothing to watch anymore
+        let spectators = self.users.get(&user_id).map(|u| u.spectators.clone()).unwrap_or_default();
+        for spectator in spectators {
+            self.send(spectator, SpectatorPacket::Server_SpectatorLeft { user_id: spectator }.with_host(user_id));
+            if let Some(user) = self.users.get_mut(&spectator) { user.spectating = None }
+        }
+
+        self.users.remove(&user_id);
+        self.send_all(self.others(user_id), PacketId::Server_UserLeft { user_id });
+    }
+
+    /// handle a packet from a logged in user
+    pub fn handle(&mut self, user_id: u32, Envelope { request_id, packet }: Envelope<PacketId>) {
+        self.request_id = request_id;
+        self.handle_packet(user_id, packet);
+        self.request_id = None;
+    }
+
+    fn handle_packet(&mut self, user_id: u32, packet: PacketId) {
+        match packet {
+            PacketId::Ping => self.reply(user_id, PacketId::Pong),
+
+            PacketId::Client_StatusUpdate { action, action_text, mode } => {
+                let Some(user) = self.users.get_mut(&user_id) else { return };
+                user.action = action;
+                user.action_text = action_text.clone();
+                user.mode = mode.clone();
+                self.send_all(self.others(user_id), PacketId::Server_UserStatusUpdate { user_id, action, action_text, mode });
+            }
+
+            PacketId::Chat_Packet { packet } => self.handle_chat(user_id, packet),
+            PacketId::Spectator_Packet { host_id, packet } => self.handle_spectator(user_id, host_id, packet),
+            PacketId::Multiplayer_Packet { packet } => self.handle_multiplayer(user_id, packet),
+
+            // there are no scores to update, and pongs need no answer
+            _ => {}
+        }
+    }
+}
@@ -0,0 +1,103 @@
+use std::borrow::Cow;
+use crate::serialization::*;
+
+/// like [`Serializable::read`], but the value can borrow from the reader's data instead of copying it
+/// 
+/// useful for read-only paths (ie packet routing), where copying every string out of the data is wasted effort.
+/// fields that dont borrow can still be read with [`SerializationReader::read`]
+pub trait BorrowSerializable<'a>: core::fmt::Debug + Sized {
+    fn read_borrowed(sr: &mut SerializationReader<'a>) -> SerializationResult<Self>;
+}
+
+// written the same as a String
+impl<'a> BorrowSerializable<'a> for &'a str {
+    fn read_borrowed(sr: &mut SerializationReader<'a>) -> SerializationResult<Self> {
+        let len = usize::read(sr)?;
+        sr.check_string_length(len)?;
+
+        let offset = sr.offset();
+        let bytes = sr.read_borrowed_slice(len)?;
+        std::str::from_utf8(bytes).map_err(|e| sr.invalid_value("&str", e, offset))
+    }
+}
+
+// written the same as a Vec<u8>
+impl<'a> BorrowSerializable<'a> for &'a [u8] {
+    fn read_borrowed(sr: &mut SerializationReader<'a>) -> SerializationResult<Self> {
+        let len = usize::read(sr)?;
+        sr.check_collection_length(len)?;
+        sr.read_borrowed_slice(len)
+    }
+}
+
+/// borrows if the reader's data is borrowed, otherwise copies
+impl<'a> BorrowSerializable<'a> for Cow<'a, str> {
+    fn read_borrowed(sr: &mut SerializationReader<'a>) -> SerializationResult<Self> {
+        if sr.is_borrowed() {
+            <&str>::read_borrowed(sr).map(Cow::Borrowed)
+        } else {
+            String::read(sr).map(Cow::Owned)
+        }
+    }
+}
+
+impl<'a, T: BorrowSerializable<'a>> BorrowSerializable<'a> for Option<T> {
+    fn read_borrowed(sr: &mut SerializationReader<'a>) -> SerializationResult<Self> {
+        if bool::read(sr)? {
+            Ok(Some(T::read_borrowed(sr)?))
+        } else {
+            Ok(None)
+        }
+    }
+}
+
+impl<'a, T: BorrowSerializable<'a>> BorrowSerializable<'a> for Vec<T> {
+    fn read_borrowed(sr: &mut SerializationReader<'a>) -> SerializationResult<Self> {
+        let count = usize::read(sr)?;
+        sr.check_collection_length(count)?;
+
+        let mut out = Vec::with_capacity(sr.capacity_for::<T>(count));
+        for n in 0..count { out.push(sr.read_borrowed(ItemName("Vec item", n))?) }
+        Ok(out)
+    }
+}
+
+
+#[test]
+fn test_borrowed() {
+    #[derive(Debug)]
+    struct Message<'a> {
+        sender_id: u32,
+        channel: &'a str,
+        message: Cow<'a, str>,
+        tags: Vec<&'a str>,
+    }
+    impl<'a> BorrowSerializable<'a> for Message<'a> {
+        fn read_borrowed(sr: &mut SerializationReader<'a>) -> SerializationResult<Self> {
+            Ok(Self {
+                sender_id: sr.read("sender_id")?,
+                channel: sr.read_borrowed("channel")?,
+                message: sr.read_borrowed("message")?,
+                tags: sr.read_borrowed("tags")?,
+            })
+        }
+    }
+
+    let data = SimpleWriter::new()
+        .write::<u32>(5)
+        .write::<String>("#general".to_owned())
+        .write::<String>("hello".to_owned())
+        .write::<Vec<String>>(vec!["a".to_owned(), "b".to_owned()])
+        .done();
+
+    let message: Message = SerializationReader::from_slice(&data).read_borrowed("message").unwrap();
+    assert_eq!(message.sender_id, 5);
+    assert_eq!(message.channel, "#general");
+    assert!(matches!(message.message, Cow::Borrowed("hello")));
+    assert_eq!(message.tags, ["a", "b"]);
+
+    // owned data can still be read with a Cow, but not with a &str
+    let mut reader = SerializationReader::new(data);
+    let _ = reader.read::<u32>("sender_id");
+    assert!(reader.read_borrowed::<&str>("channel").is_err());
+}
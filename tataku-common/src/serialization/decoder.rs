@@ -0,0 +1,238 @@
+use std::marker::PhantomData;
+use crate::packets::PacketId;
+use crate::serialization::*;
+
+/// how many bytes to try to read from a stream at once
+const READ_CHUNK_SIZE: usize = 8 * 1024;
+
+/// incremental decoder, for when data arrives in chunks (ie from a socket)
+///
+/// bytes are buffered until a whole value is available,
+/// so partially received packets are kept until the rest of them arrive.
+/// once a framed packet's length has been read, nothing is decoded again until all of it is here
+pub struct PacketDecoder<T = PacketId> {
+    buffer: Vec<u8>,
+    /// how much data the last attempt ran out at, so it isnt decoded again until at least this much is buffered
+    needed: usize,
+    protocol: Protocol,
+    limits: DecodeLimits,
+    _type: PhantomData<T>,
+}
+impl<T: Serializable> PacketDecoder<T> {
+    pub fn new() -> Self {
+        Self {
+            buffer: Vec::new(),
+            needed: 0,
+            protocol: Protocol::default(),
+            limits: DecodeLimits::default(),
+            _type: PhantomData,
+        }
+    }
+    pub fn with_protocol(mut self, protocol: Protocol) -> Self {
+        self.protocol = protocol;
+        self
+    }
+
+    /// limits for each decoded value
+    /// 
+    /// a value going over `max_total_bytes` will error before all of it has been buffered
+    pub fn with_limits(mut self, limits: DecodeLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// change the protocol, ie once it has been negotiated at login
+    pub fn set_protocol(&mut self, protocol: Protocol) {
+        self.protocol = protocol;
+        self.needed = 0;
+    }
+
+    /// how many bytes are waiting to be decoded
+    pub fn buffered(&self) -> usize {
+        self.buffer.len()
+    }
+
+    /// add received bytes to the buffer
+    pub fn push(&mut self, bytes: &[u8]) {
+        self.buffer.extend_from_slice(bytes);
+    }
+
+    /// try to decode the next value from the buffered data
+    ///
+    /// returns `Ok(None)` if more data is needed.
+    ///
+    /// an error means the data is corrupt, and it's probably best to drop the connection
+    pub fn next_packet(&mut self) -> SerializationResult<Option<T>> {
+        // the same data would run out in the same place again
+        if self.buffer.is_empty() || self.buffer.len() < self.needed { return Ok(None) }
+
+        let mut reader = SerializationReader::from_slice(&self.buffer)
+            .with_protocol(self.protocol)
+            .with_limits(self.limits);
+        let result = reader.read::<T>("packet");
+        let offset = reader.offset();
+        let needed = reader.needed;
+
+        match result {
+            Ok(packet) => {
+                self.buffer.drain(..offset);
+                self.needed = 0;
+                Ok(Some(packet))
+            }
+            Err(e) if matches!(e.inner, SerializationErrorEnum::Incomplete { .. }) => {
+                self.needed = needed;
+                Ok(None)
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    /// read from `reader` until a whole value has been decoded
+    ///
+    /// returns `Ok(None)` if the stream ended cleanly between values
+    pub fn read_packet<R: std::io::Read>(&mut self, reader: &mut R) -> Result<Option<T>, DecodeError> {
+        let mut chunk = [0u8; READ_CHUNK_SIZE];
+
+        loop {
+            if let Some(packet) = self.next_packet()? { return Ok(Some(packet)) }
+
+            match reader.read(&mut chunk) {
+                Ok(0) if self.buffer.is_empty() => return Ok(None),
+                Ok(0) => return Err(DecodeError::UnexpectedEof),
+                Ok(len) => self.push(&chunk[..len]),
+                Err(e) if e.kind() == std::io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e.into()),
+            }
+        }
+    }
+
+    /// read from `reader` until a whole value has been decoded
+    ///
+    /// returns `Ok(None)` if the stream ended cleanly between values
+    #[cfg(feature = "async")]
+    pub async fn read_packet_async<R: tokio::io::AsyncRead + Unpin>(&mut self, reader: &mut R) -> Result<Option<T>, DecodeError> {
+        use tokio::io::AsyncReadExt;
+        let mut chunk = [0u8; READ_CHUNK_SIZE];
+
+        loop {
+            if let Some(packet) = self.next_packet()? { return Ok(Some(packet)) }
+
+            match reader.read(&mut chunk).await {
+                Ok(0) if self.buffer.is_empty() => return Ok(None),
+                Ok(0) => return Err(DecodeError::UnexpectedEof),
+                Ok(len) => self.push(&chunk[..len]),
+                Err(e) if e.kind() == std::io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e.into()),
+            }
+        }
+    }
+}
+impl<T: Serializable> Default for PacketDecoder<T> {
+    fn default() -> Self { Self::new() }
+}
+
+#[derive(Debug)]
+pub enum DecodeError {
+    Io(std::io::Error),
+    /// the stream ended in the middle of a value
+    UnexpectedEof,
+    SerializationError(SerializationError),
+}
+impl From<std::io::Error> for DecodeError {
+    fn from(value: std::io::Error) -> Self {
+        Self::Io(value)
+    }
+}
+impl From<SerializationError> for DecodeError {
+    fn from(value: SerializationError) -> Self {
+        Self::SerializationError(value)
+    }
+}
+impl core::fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "io error: {e}"),
+            Self::UnexpectedEof => write!(f, "stream ended in the middle of a packet"),
+            Self::SerializationError(e) => write!(f, "{e}"),
+        }
+    }
+}
+impl std::error::Error for DecodeError {}
+
+
+#[test]
+fn test_decoder_chunks() {
+    let packets = [
+        PacketId::Ping,
+        PacketId::Server_UserLeft { user_id: 10 },
+        PacketId::Server_Notification { message: "hello".to_owned(), severity: crate::types::network::Severity::Warning },
+    ];
+
+    for protocol in [Protocol::LEGACY, Protocol::FRAMED, Protocol::COMPACT] {
+        let mut writer = SerializationWriter::new().with_protocol(protocol);
+        packets.iter().for_each(|p| writer.write(p));
+        let data = writer.data();
+
+        // feed the data one byte at a time
+        let mut decoder = PacketDecoder::<PacketId>::new().with_protocol(protocol);
+        let mut decoded = Vec::new();
+        for b in data {
+            decoder.push(&[b]);
+            while let Some(packet) = decoder.next_packet().unwrap() {
+                decoded.push(packet);
+            }
+        }
+
+        assert_eq!(decoder.buffered(), 0);
+        assert_eq!(format!("{decoded:?}"), format!("{packets:?}"));
+    }
+}
+
+#[test]
+fn test_decoder_read() {
+    let mut writer = SerializationWriter::new();
+    writer.write(&PacketId::Pong);
+    writer.write(&PacketId::Server_UserLeft { user_id: 10 });
+    let mut data = writer.data();
+
+    let mut decoder = PacketDecoder::<PacketId>::new();
+    let mut cursor = std::io::Cursor::new(data.clone());
+    assert!(matches!(decoder.read_packet(&mut cursor), Ok(Some(PacketId::Pong))));
+    assert!(matches!(decoder.read_packet(&mut cursor), Ok(Some(PacketId::Server_UserLeft { user_id: 10 }))));
+    assert!(matches!(decoder.read_packet(&mut cursor), Ok(None)));
+
+    // cut off the last byte
+    data.pop();
+    let mut decoder = PacketDecoder::<PacketId>::new();
+    let mut cursor = std::io::Cursor::new(data);
+    assert!(matches!(decoder.read_packet(&mut cursor), Ok(Some(PacketId::Pong))));
+    assert!(matches!(decoder.read_packet(&mut cursor), Err(DecodeError::UnexpectedEof)));
+}
+
+#[test]
+fn test_decoder_waits_for_frame() {
+    use crate::packets::ChatPacket;
+
+    let packet: PacketId = ChatPacket::Server_FriendsList { friend_ids: (0..50_000).collect() }.into();
+    for protocol in [Protocol::FRAMED, Protocol::COMPACT] {
+        let mut writer = SerializationWriter::new().with_protocol(protocol);
+        writer.write(&packet);
+        writer.write(&PacketId::Ping);
+        let len = writer.serialized_len(&packet);
+        let data = writer.data();
+
+        // once the frame's length is known, the decoder waits for all of it
+        let mut decoder = PacketDecoder::<PacketId>::new().with_protocol(protocol);
+        decoder.push(&data[..100]);
+        assert!(decoder.next_packet().unwrap().is_none());
+        assert_eq!(decoder.needed, len);
+
+        let mut decoded = Vec::new();
+        for chunk in data[100..].chunks(READ_CHUNK_SIZE) {
+            decoder.push(chunk);
+            while let Some(packet) = decoder.next_packet().unwrap() { decoded.push(packet) }
+        }
+        assert_eq!(format!("{decoded:?}"), format!("{:?}", [packet.clone(), PacketId::Ping]));
+        assert_eq!(decoder.buffered(), 0);
+    }
+}
@@ -0,0 +1,119 @@
+/// who sends a packet
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum PacketDirection {
+    /// only clients send this (`Client_` packets)
+    ClientToServer,
+    /// only servers send this (`Server_` packets)
+    ServerToClient,
+    /// either side can send this (ie pings)
+    Both,
+}
+impl PacketDirection {
+    /// can a client send this?
+    pub fn from_client(self) -> bool { self != Self::ServerToClient }
+    /// can a server send this?
+    pub fn from_server(self) -> bool { self != Self::ClientToServer }
+}
+
+/// packets which know who sends them
+///
+/// this is implemented by `#[packet(directional)]` on a [`PacketSerialization`](crate::macros::PacketSerialization) enum.
+/// a variant's direction comes from its `Client_`/`Server_` prefix (anything else is [`PacketDirection::Both`]),
+/// or `#[packet(direction = client/server/both)]`, or `#[packet(direction_from = field)]` for packets wrapping another packet
+///
+/// the derive also makes an enum for each direction, with only the variants which can go that way (ie `PacketIdClientToServer`).
+/// packets wrapping another packet hold the inner packet's enum for the same direction
+pub trait Directional: Sized {
+    /// the packets a client can send
+    type ClientToServer: TryFrom<Self, Error = WrongDirection<Self>> + Into<Self>;
+    /// the packets a server can send
+    type ServerToClient: TryFrom<Self, Error = WrongDirection<Self>> + Into<Self>;
+
+    fn direction(&self) -> PacketDirection;
+}
+
+/// a packet a client can send, so it's what a server reads
+///
+/// reading one errors if the packet is only sent by servers, so `PacketDecoder<ClientToServer<PacketId>>` only accepts packets from clients
+pub type ClientToServer<T> = <T as Directional>::ClientToServer;
+/// a packet a server can send, so it's what a client reads
+///
+/// reading one errors if the packet is only sent by clients
+pub type ServerToClient<T> = <T as Directional>::ServerToClient;
+
+
+/// error for a packet going the wrong way
+#[derive(Clone, Debug)]
+pub struct WrongDirection<T> {
+    pub packet: T,
+    /// who is allowed to send packets here
+    pub expected: PacketDirection,
+}
+impl<T: std::fmt::Debug> core::fmt::Display for WrongDirection<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?} can't be sent {:?}", self.packet, self.expected)
+    }
+}
+impl<T: std::fmt::Debug> std::error::Error for WrongDirection<T> {}
+
+
+#[test]
+fn test_directions() {
+    use crate::serialization::*;
+    use crate::packets::*;
+
+    assert_eq!(PacketId::Client_LogOut.direction(), PacketDirection::ClientToServer);
+    assert_eq!(PacketId::Server_UserLeft { user_id: 1 }.direction(), PacketDirection::ServerToClient);
+    assert_eq!(PacketId::Ping.direction(), PacketDirection::Both);
+    // wrapped packets go the way their inner packet does
+    let message = ChatPacket::Client_SendMessage { channel: "#general".to_owned(), message: "hi".to_owned() };
+    assert_eq!(PacketId::from(message.clone()).direction(), PacketDirection::ClientToServer);
+    assert_eq!(SpectatorPacket::Server_SpectatorFrames { frames: Vec::new() }.with_host(1).direction(), PacketDirection::ServerToClient);
+
+    // the per-direction enums only have the variants which can go that way
+    let Ok(PacketIdClientToServer::Chat_Packet { packet: ChatPacketClientToServer::Client_SendMessage { channel, .. } }) = PacketId::from(message.clone()).try_into() else { panic!("bad chat packet") };
+    assert_eq!(channel, "#general");
+    assert!(matches!(ClientToServer::<PacketId>::try_from(PacketId::Ping), Ok(PacketIdClientToServer::Ping)));
+    assert!(matches!(PacketId::from(PacketIdServerToClient::Server_UserLeft { user_id: 1 }), PacketId::Server_UserLeft { user_id: 1 }));
+    let err = ClientToServer::<PacketId>::try_from(PacketId::Server_Permissions { user_id: 1, permissions: Vec::new() }).unwrap_err();
+    assert!(matches!(err.packet, PacketId::Server_Permissions { .. }));
+    assert_eq!(err.expected, PacketDirection::ClientToServer);
+    // a wrapped packet going the wrong way gives back the whole packet
+    let err = ServerToClient::<PacketId>::try_from(PacketId::from(message)).unwrap_err();
+    assert!(matches!(err.packet, PacketId::Chat_Packet { packet: ChatPacket::Client_SendMessage { .. } }));
+
+    // a server reading a server packet from a client
+    for protocol in [Protocol::LEGACY, Protocol::FRAMED, Protocol::COMPACT] {
+        let mut writer = SerializationWriter::new().with_protocol(protocol);
+        writer.write(&PacketId::Client_NotifyScoreUpdate);
+        writer.write(&PacketId::Server_Permissions { user_id: 1, permissions: Vec::new() });
+        let data = writer.data();
+
+        let mut decoder = PacketDecoder::<ClientToServer<PacketId>>::new().with_protocol(protocol);
+        decoder.push(&data);
+        assert!(matches!(decoder.next_packet(), Ok(Some(PacketIdClientToServer::Client_NotifyScoreUpdate))));
+        let err = decoder.next_packet().unwrap_err();
+        assert!(matches!(err.inner, SerializationErrorEnum::InvalidValue { type_name: "PacketIdClientToServer", .. }), "{err:?}");
+
+        let mut reader = SerializationReader::new(data).with_protocol(protocol);
+        assert!(reader.read::<ServerToClient<PacketId>>("packet").is_err());
+
+        // theyre written the same as the full enum
+        let packet = PacketIdServerToClient::Spectator_Packet { host_id: 1, packet: SpectatorPacketServerToClient::Server_SpectatorFrames { frames: Vec::new() } };
+        let mut writer = SerializationWriter::new().with_protocol(protocol);
+        writer.write(&packet);
+        let len = packet.serialized_len(&writer);
+        let mut full = SerializationWriter::new().with_protocol(protocol);
+        full.write(&PacketId::from(packet));
+        let data = writer.data();
+        assert_eq!(data, full.data());
+        assert_eq!(data.len(), len);
+    }
+
+    // the packet inside is read like any other value, so it shows up in traces
+    let mut writer = SerializationWriter::new();
+    writer.write(&PacketId::Ping);
+    let mut reader = SerializationReader::new(writer.data()).trace();
+    reader.read::<ClientToServer<PacketId>>("packet").unwrap();
+    assert!(reader.trace_entries().iter().any(|e| e.path == "packet.packet" && e.type_name.ends_with("::PacketId")), "{:?}", reader.trace_entries());
+}
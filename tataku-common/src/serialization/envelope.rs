@@ -0,0 +1,98 @@
+use crate::serialization::*;
+
+/// a packet, and the request it belongs to
+///
+/// clients pick an id for each request they want a reply to, and servers copy it into the reply (or a `Server_Error`),
+/// so two requests of the same kind can be told apart.
+/// the id is only written if the protocol [has request ids](Protocol::has_request_ids), otherwise it's always `None` when read
+#[derive(Clone, Debug, PartialEq)]
+pub struct Envelope<T> {
+    /// the request this packet is (or is replying to), if any
+    pub request_id: Option<u32>,
+    pub packet: T,
+}
+impl<T> Envelope<T> {
+    /// a packet which isnt part of a request
+    pub fn new(packet: T) -> Self {
+        Self { request_id: None, packet }
+    }
+    pub fn with_request_id(mut self, request_id: u32) -> Self {
+        self.request_id = Some(request_id);
+        self
+    }
+
+    /// change the packet, keeping the request id
+    pub fn map<U>(self, f: impl FnOnce(T) -> U) -> Envelope<U> {
+        Envelope { request_id: self.request_id, packet: f(self.packet) }
+    }
+}
+impl<T> From<T> for Envelope<T> {
+    fn from(packet: T) -> Self { Self::new(packet) }
+}
+
+impl<T: Serializable> Serializable for Envelope<T> {
+    fn read(sr: &mut SerializationReader) -> SerializationResult<Self> {
+        let request_id = if sr.protocol.has_request_ids() { sr.read("request_id")? } else { None };
+        Ok(Self { request_id, packet: sr.read("packet")? })
+    }
+    fn write(&self, sw: &mut SerializationWriter) {
+        if sw.protocol.has_request_ids() { sw.write(&self.request_id) }
+        sw.write(&self.packet)
+    }
+    fn serialized_len(&self, sw: &SerializationWriter) -> usize {
+        let request_id = if sw.protocol.has_request_ids() { self.request_id.serialized_len(sw) } else { 0 };
+        request_id + sw.serialized_len(&self.packet)
+    }
+}
+
+impl<'a, T: BorrowSerializable<'a>> BorrowSerializable<'a> for Envelope<T> {
+    fn read_borrowed(sr: &mut SerializationReader<'a>) -> SerializationResult<Self> {
+        let request_id = if sr.protocol.has_request_ids() { sr.read("request_id")? } else { None };
+        Ok(Self { request_id, packet: sr.read_borrowed("packet")? })
+    }
+}
+
+
+#[test]
+fn test_envelope() {
+    use crate::packets::*;
+
+    let envelopes = [
+        Envelope::new(PacketId::Ping),
+        Envelope::new(ChatPacket::Client_JoinChannel { channel: "#general".to_owned(), password: String::new() }.into()).with_request_id(1),
+        Envelope::new(PacketId::Server_UserLeft { user_id: 10 }).with_request_id(u32::MAX),
+    ];
+
+    for protocol in [Protocol::LEGACY, Protocol::FRAMED, Protocol::COMPACT, Protocol::REQUEST_IDS] {
+        let mut writer = SerializationWriter::new().with_protocol(protocol);
+        envelopes.iter().for_each(|e| writer.write(e));
+        let len = envelopes.iter().map(|e| e.serialized_len(&writer)).sum::<usize>();
+        let data = writer.data();
+        assert_eq!(data.len(), len);
+
+        let mut decoder = PacketDecoder::<Envelope<PacketId>>::new().with_protocol(protocol);
+        decoder.push(&data);
+        for envelope in &envelopes {
+            let decoded = decoder.next_packet().unwrap().unwrap();
+            assert_eq!(format!("{:?}", decoded.packet), format!("{:?}", envelope.packet));
+            // older protocols have nowhere to put the id
+            let request_id = if protocol.has_request_ids() { envelope.request_id } else { None };
+            assert_eq!(decoded.request_id, request_id);
+        }
+        assert_eq!(decoder.buffered(), 0);
+    }
+
+    // without an id, it's the same as the packet on its own in older protocols
+    let mut writer = SerializationWriter::new().with_protocol(Protocol::COMPACT);
+    writer.write(&Envelope::new(PacketId::Ping));
+    let mut plain = SerializationWriter::new().with_protocol(Protocol::COMPACT);
+    plain.write(&PacketId::Ping);
+    assert_eq!(writer.data(), plain.data());
+
+    // the packet is read like any other value, so it shows up in traces
+    let mut writer = SerializationWriter::new().with_protocol(Protocol::REQUEST_IDS);
+    writer.write(&Envelope::new(PacketId::Ping).with_request_id(1));
+    let mut reader = SerializationReader::new(writer.data()).with_protocol(Protocol::REQUEST_IDS).trace();
+    reader.read::<Envelope<PacketId>>("envelope").unwrap();
+    assert!(reader.trace_entries().iter().any(|e| e.path == "envelope.packet" && e.type_name.ends_with("::PacketId")), "{:?}", reader.trace_entries());
+}
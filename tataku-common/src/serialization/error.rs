@@ -66,14 +66,113 @@ impl Eq for SerializationError {}
 
 impl core::fmt::Display for SerializationError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{:?}, stack: {}", self.inner, self.format_stack())
+        write!(f, "{}, stack: {}", self.inner, self.format_stack())
     }
 }
 
 
+/// what went wrong while reading
+/// 
+/// `offset` is where in the data the problem was found
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub enum SerializationErrorEnum {
-    OutOfBounds,
+    /// tried to read past the end of a packet frame, the data is corrupt
+    OutOfBounds {
+        offset: usize,
+    },
+    /// ran out of data before the value was finished
+    /// - when streaming, more bytes may still arrive to complete it
+    /// - when reading a complete buffer, the data was truncated
+    Incomplete {
+        offset: usize,
+    },
+    /// the data would go over one of the reader's [`DecodeLimits`]
+    LimitExceeded {
+        limit: LimitKind,
+        /// the size that was asked for
+        requested: usize,
+        offset: usize,
+    },
+    /// an enum was given an id it doesn't have a variant for
+    UnknownVariant {
+        type_name: &'static str,
+        id: u64,
+        offset: usize,
+    },
+    /// the value was read, but it isnt valid for its type
+    InvalidValue {
+        type_name: &'static str,
+        /// what was wrong with it
+        reason: String,
+        offset: usize,
+    },
+    /// a value was read, but there was more data after it
+    TrailingBytes {
+        /// how many bytes were left over
+        remaining: usize,
+        offset: usize,
+    },
     FromUtf8Error(FromUtf8Error),
     ParseIntError(ParseIntError),
 }
+impl core::fmt::Display for SerializationErrorEnum {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::OutOfBounds { offset } => write!(f, "read past the end of a packet frame at offset {offset}"),
+            Self::Incomplete { offset } => write!(f, "ran out of data at offset {offset}"),
+            Self::LimitExceeded { limit, requested, offset } => write!(f, "{limit:?} limit exceeded at offset {offset} (requested {requested})"),
+            Self::UnknownVariant { type_name, id, offset } => write!(f, "unknown {type_name} id {id} at offset {offset}"),
+            Self::InvalidValue { type_name, reason, offset } => write!(f, "invalid {type_name} at offset {offset}: {reason}"),
+            Self::TrailingBytes { remaining, offset } => write!(f, "{remaining} unread bytes left at offset {offset}"),
+            Self::FromUtf8Error(e) => write!(f, "{e}"),
+            Self::ParseIntError(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+
+#[test]
+fn test_error_offsets() {
+    use crate::types::{ network::Severity, replays::ReplayAction };
+
+    #[repr(u8)]
+    #[derive(crate::macros::PacketSerialization)]
+    #[packet(strict)]
+    #[derive(Debug, Default)]
+    enum Strict {
+        #[default]
+        #[packet(id=0)] A,
+        #[packet(id=1)] B,
+    }
+
+    // strict enums error on unknown ids
+    let data = SimpleWriter::new().write::<u32>(5).write::<u8>(10).done();
+    let mut reader = SerializationReader::new(data);
+    let _ = reader.read::<u32>("padding");
+    assert_eq!(
+        reader.read::<Strict>("strict").unwrap_err().inner, 
+        SerializationErrorEnum::UnknownVariant { type_name: "Strict", id: 10, offset: 4 }
+    );
+    assert!(matches!(SerializationReader::new(SimpleWriter::new().write::<u8>(1).done()).read::<Strict>("strict"), Ok(Strict::B)));
+
+    // lenient ones fall back to the default
+    let data = SimpleWriter::new().write::<u8>(10).done();
+    assert!(matches!(SerializationReader::new(data).read::<Severity>("severity"), Ok(Severity::Info)));
+
+    let data = SimpleWriter::new().write::<u8>(3).done();
+    assert_eq!(
+        SerializationReader::new(data).read::<ReplayAction>("action").unwrap_err().inner,
+        SerializationErrorEnum::UnknownVariant { type_name: "ReplayAction", id: 3, offset: 0 }
+    );
+
+    let data = SimpleWriter::new().write::<u8>(2).done();
+    assert!(matches!(
+        SerializationReader::new(data).read::<bool>("bool").unwrap_err().inner,
+        SerializationErrorEnum::InvalidValue { type_name: "bool", offset: 0, .. }
+    ));
+
+    let data = SimpleWriter::new().write::<u16>(1).write::<u8>(0).done();
+    let mut reader = SerializationReader::new(data);
+    let _ = reader.read::<u16>("value");
+    assert_eq!(reader.finish().unwrap_err().inner, SerializationErrorEnum::TrailingBytes { remaining: 1, offset: 2 });
+}
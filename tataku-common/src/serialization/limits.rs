@@ -0,0 +1,98 @@
+/// how deeply values can be nested by default.
+/// 
+/// nothing real gets close to this, but old replays and newer scores can contain each other,
+/// so without a limit, bad data could nest them until the stack overflows
+pub const DEFAULT_MAX_DEPTH: usize = 128;
+
+/// limits on what a [`SerializationReader`](crate::serialization::SerializationReader) will read
+///
+/// the defaults are unlimited (apart from [`DEFAULT_MAX_DEPTH`]), use [`DecodeLimits::untrusted`] (or your own values) when reading data from clients
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct DecodeLimits {
+    /// longest string (in bytes) that can be read
+    pub max_string_length: usize,
+    /// most items a `Vec`, `HashMap` or `HashSet` can have
+    pub max_collection_length: usize,
+    /// how deeply values can be nested inside each other
+    pub max_depth: usize,
+    /// most bytes that can be read in total
+    pub max_total_bytes: usize,
+}
+impl DecodeLimits {
+    pub const fn unlimited() -> Self {
+        Self {
+            max_string_length: usize::MAX,
+            max_collection_length: usize::MAX,
+            max_depth: usize::MAX,
+            max_total_bytes: usize::MAX,
+        }
+    }
+
+    /// sensible limits for data coming from somewhere we dont trust
+    /// - big enough for scores with replays, small enough that a bad packet cant take the server down
+    pub const fn untrusted() -> Self {
+        Self {
+            max_string_length: 1024 * 1024,
+            max_collection_length: 1024 * 1024,
+            max_depth: 64,
+            max_total_bytes: 32 * 1024 * 1024,
+        }
+    }
+}
+// nesting is still limited, since values nested too deep would overflow the stack
+impl Default for DecodeLimits {
+    fn default() -> Self { Self { max_depth: DEFAULT_MAX_DEPTH, ..Self::unlimited() } }
+}
+
+/// which limit was exceeded
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum LimitKind {
+    StringLength,
+    CollectionLength,
+    Depth,
+    TotalBytes,
+}
+
+
+#[test]
+fn test_limits() {
+    use crate::serialization::*;
+    fn read<T: Serializable>(data: Vec<u8>, limits: DecodeLimits) -> SerializationErrorEnum {
+        SerializationReader::new(data)
+            .with_limits(limits)
+            .read::<T>("value")
+            .expect_err("read should have failed")
+            .inner
+    }
+
+    // a collection claiming to have an absurd amount of items shouldnt allocate for them
+    let data = SimpleWriter::new().write::<u64>(u64::MAX).done();
+    assert_eq!(read::<Vec<u64>>(data.clone(), DecodeLimits::default()), SerializationErrorEnum::Incomplete { offset: 8 });
+    assert_eq!(read::<String>(data.clone(), DecodeLimits::default()), SerializationErrorEnum::Incomplete { offset: 8 });
+    assert_eq!(
+        read::<Vec<u64>>(data.clone(), DecodeLimits::untrusted()), 
+        SerializationErrorEnum::LimitExceeded { limit: LimitKind::CollectionLength, requested: usize::MAX, offset: 8 }
+    );
+    assert_eq!(
+        read::<String>(data, DecodeLimits::untrusted()), 
+        SerializationErrorEnum::LimitExceeded { limit: LimitKind::StringLength, requested: usize::MAX, offset: 8 }
+    );
+
+    let data = SimpleWriter::new().write::<Vec<Vec<Vec<u8>>>>(vec![vec![vec![1]]]).done();
+    let limits = DecodeLimits { max_depth: 2, ..Default::default() };
+    assert_eq!(read::<Vec<Vec<Vec<u8>>>>(data.clone(), limits), SerializationErrorEnum::LimitExceeded { limit: LimitKind::Depth, requested: 3, offset: 16 });
+
+    let limits = DecodeLimits { max_total_bytes: 16, ..Default::default() };
+    assert_eq!(read::<Vec<Vec<Vec<u8>>>>(data, limits), SerializationErrorEnum::LimitExceeded { limit: LimitKind::TotalBytes, requested: 24, offset: 16 });
+
+    // collections never reserve more memory than there is data left
+    let mut reader = SerializationReader::new(vec![0; 64]);
+    assert_eq!(reader.capacity_for::<u8>(usize::MAX), 64);
+    assert_eq!(reader.capacity_for::<u64>(usize::MAX), 8);
+    assert_eq!(reader.capacity_for::<()>(10), 10);
+
+    // and unreading too much stops at the start
+    let _ = reader.read::<u32>("value");
+    reader.unread(usize::MAX);
+    assert_eq!(reader.offset(), 0);
+}
@@ -1,10 +1,32 @@
 mod error;
 mod reader;
 mod writer;
+mod protocol;
+mod varint;
+mod decoder;
+mod direction;
+mod envelope;
+mod limits;
+mod borrowed;
+mod trace;
+mod serde_bridge;
+mod schema;
+#[cfg(feature="test")] mod random;
 mod serializable;
 
 
 pub use error::*;
 pub use reader::*;
 pub use writer::*;
+pub use protocol::*;
+pub use varint::*;
+pub use decoder::*;
+pub use direction::*;
+pub use envelope::*;
+pub use limits::*;
+pub use borrowed::*;
+pub use trace::*;
+pub use serde_bridge::*;
+pub use schema::*;
+#[cfg(feature="test")] pub use random::*;
 pub use serializable::*;
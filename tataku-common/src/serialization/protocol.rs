@@ -0,0 +1,49 @@
+use crate::serialization::Encoding;
+
+/// which version of the packet protocol is being spoken
+///
+/// this is negotiated through `protocol_version` in [`PacketId::Client_UserLogin`](crate::packets::PacketId::Client_UserLogin)
+/// - v1 (legacy): packets are written as `[id] [fields]`
+/// - v2 (framed): packets are written as `[id] [payload length (u64)] [fields]`,
+///   so unknown packets and unknown trailing fields can be skipped
+/// - v3 (compact): framed, but lengths and integers are written as varints (see [`Encoding::COMPACT`])
+/// - v4 (request ids): compact, and each packet starts with an optional request id (see [`Envelope`](crate::serialization::Envelope))
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Protocol(u16);
+impl Protocol {
+    /// original protocol, packets have no length prefix
+    pub const LEGACY: Self = Self(1);
+    /// every packet (and sub-packet) is prefixed with its payload length
+    pub const FRAMED: Self = Self(2);
+    /// framed, with lengths and integers written as varints
+    pub const COMPACT: Self = Self(3);
+    /// compact, with a request id before each packet, so replies can be matched to requests
+    pub const REQUEST_IDS: Self = Self(4);
+
+    /// the newest protocol version this library knows about
+    pub const CURRENT: Self = Self::REQUEST_IDS;
+
+    pub fn from_version(version: u16) -> Self { Self(version) }
+    pub fn version(&self) -> u16 { self.0 }
+
+    /// are packets written with a payload length?
+    pub fn is_framed(&self) -> bool { *self >= Self::FRAMED }
+
+    /// do packets have a request id?
+    pub fn has_request_ids(&self) -> bool { *self >= Self::REQUEST_IDS }
+
+    /// how lengths and integers are written
+    pub fn encoding(&self) -> Encoding {
+        if *self >= Self::COMPACT { Encoding::COMPACT } else { Encoding::FIXED }
+    }
+}
+// anything that doesnt negotiate a protocol is assumed to be legacy
+impl Default for Protocol {
+    fn default() -> Self { Self::LEGACY }
+}
+impl From<u16> for Protocol {
+    fn from(version: u16) -> Self { Self::from_version(version) }
+}
+impl From<Protocol> for u16 {
+    fn from(val: Protocol) -> Self { val.version() }
+}
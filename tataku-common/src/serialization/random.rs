@@ -0,0 +1,312 @@
+use std::{
+    rc::Rc,
+    sync::Arc,
+    borrow::Cow,
+    fmt::Debug,
+    hash::Hash,
+    collections::HashSet,
+    collections::HashMap,
+    collections::BTreeSet,
+    collections::BTreeMap,
+    collections::VecDeque,
+    time::{ Duration, SystemTime },
+    net::{ IpAddr, Ipv4Addr, Ipv6Addr },
+};
+use crate::serialization::*;
+
+/// the most items a generated collection will have
+const MAX_ITEMS: usize = 4;
+/// the most chars a generated string will have
+const MAX_STRING_LEN: usize = 12;
+/// chars to build strings out of, with some multi-byte ones so utf8 lengths get tested
+const STRING_CHARS: &[char] = &['a', 'b', 'z', 'A', 'Z', '0', '9', ' ', '_', ',', 'é', 'ß', '音', 'ゲ', '🥁'];
+
+/// small, seedable rng, so failing values can be reproduced
+pub struct RandomSource {
+    state: u64,
+    seed: u64,
+}
+impl RandomSource {
+    pub fn new(seed: u64) -> Self {
+        Self { state: seed, seed }
+    }
+
+    /// seeded with `TATAKU_TEST_SEED` if it's set, otherwise from the time
+    pub fn from_env() -> Self {
+        let seed = std::env::var("TATAKU_TEST_SEED").ok()
+            .and_then(|s| s.parse().ok())
+            .unwrap_or_else(|| SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap_or_default().as_nanos() as u64);
+        Self::new(seed)
+    }
+
+    /// what this was seeded with
+    pub fn seed(&self) -> u64 { self.seed }
+
+    // splitmix64
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e3779b97f4a7c15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        z ^ (z >> 31)
+    }
+
+    /// a number in `0..n`
+    pub fn below(&mut self, n: usize) -> usize {
+        (self.next_u64() % n.max(1) as u64) as usize
+    }
+
+    pub fn bool(&mut self) -> bool {
+        self.next_u64() & 1 == 1
+    }
+
+    /// how many items to put in a collection
+    pub fn collection_len(&mut self) -> usize {
+        self.below(MAX_ITEMS + 1)
+    }
+
+    pub fn pick<T: Copy>(&mut self, items: &[T]) -> T {
+        items[self.below(items.len())]
+    }
+}
+
+/// a type which can make random (well-formed) values of itself
+///
+/// `#[derive(RandomValue)]` implements this for structs and enums
+pub trait RandomValue: Sized {
+    fn random(rng: &mut RandomSource) -> Self;
+
+    /// one value for each variant, so every variant gets tested
+    fn random_variants(rng: &mut RandomSource) -> Vec<Self> {
+        vec![Self::random(rng)]
+    }
+}
+
+
+/// write `value`, read it back, then check writing the read value gives the same bytes
+///
+/// maps and sets are written canonically, so their order doesnt matter.
+/// also checks [`Serializable::serialized_len`] matches what was written
+pub fn check_roundtrip<T: Serializable + Debug>(value: &T, protocol: Protocol) -> Result<(), String> {
+    let write = |value: &T| {
+        let mut writer = SerializationWriter::new().with_protocol(protocol).canonical();
+        writer.write(value);
+        (writer.serialized_len(value), writer.data())
+    };
+
+    let (len, data) = write(value);
+    if len != data.len() {
+        return Err(format!("{value:?} was written as {} bytes, but serialized_len said {len} ({protocol:?})", data.len()));
+    }
+
+    let mut reader = SerializationReader::from_slice(&data).with_protocol(protocol);
+    let read = reader.read::<T>("value")
+        .and_then(|read| reader.finish().map(|_| read))
+        .map_err(|e| format!("failed to read {value:?} ({protocol:?}): {e}"))?;
+
+    if write(&read).1 != data {
+        return Err(format!("{value:?} was read as {read:?} ({protocol:?})"));
+    }
+
+    Ok(())
+}
+
+/// roundtrip `count` random values of every variant of `T`, with each protocol version
+///
+/// the error includes the seed, so it can be rerun with `TATAKU_TEST_SEED`
+pub fn check_random_roundtrips<T: Serializable + RandomValue + Debug>(rng: &mut RandomSource, count: usize) -> Result<(), String> {
+    for _ in 0..count {
+        for value in T::random_variants(rng) {
+            for protocol in [Protocol::LEGACY, Protocol::FRAMED, Protocol::COMPACT] {
+                check_roundtrip(&value, protocol).map_err(|e| format!("{e} (seed {})", rng.seed()))?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+
+macro_rules! impl_int {
+    ($($t:ty),+) => { $(
+        impl RandomValue for $t {
+            fn random(rng: &mut RandomSource) -> Self { rng.next_u64() as $t }
+        }
+    )+ }
+}
+impl_int!(u8, i8, u16, i16, u32, i32, u64, i64);
+impl RandomValue for u128 {
+    fn random(rng: &mut RandomSource) -> Self { (rng.next_u64() as u128) << 64 | rng.next_u64() as u128 }
+}
+impl RandomValue for i128 {
+    fn random(rng: &mut RandomSource) -> Self { u128::random(rng) as i128 }
+}
+// usize is written as a u64, so keep it in range on 32 bit targets too
+impl RandomValue for usize {
+    fn random(rng: &mut RandomSource) -> Self { rng.next_u64() as u32 as usize }
+}
+// any bit pattern, so nan and infinity are tested too
+impl RandomValue for half::f16 {
+    fn random(rng: &mut RandomSource) -> Self { half::f16::from_bits(u16::random(rng)) }
+}
+impl RandomValue for f32 {
+    fn random(rng: &mut RandomSource) -> Self { f32::from_bits(u32::random(rng)) }
+}
+impl RandomValue for f64 {
+    fn random(rng: &mut RandomSource) -> Self { f64::from_bits(rng.next_u64()) }
+}
+impl RandomValue for bool {
+    fn random(rng: &mut RandomSource) -> Self { rng.bool() }
+}
+impl RandomValue for char {
+    fn random(rng: &mut RandomSource) -> Self {
+        loop {
+            if let Some(c) = char::from_u32(rng.below(0x110000) as u32) { return c }
+        }
+    }
+}
+impl RandomValue for () {
+    fn random(_: &mut RandomSource) -> Self {}
+}
+impl RandomValue for String {
+    fn random(rng: &mut RandomSource) -> Self {
+        let len = rng.below(MAX_STRING_LEN + 1);
+        (0..len).map(|_| rng.pick(STRING_CHARS)).collect()
+    }
+}
+impl RandomValue for Cow<'_, str> {
+    fn random(rng: &mut RandomSource) -> Self { Cow::Owned(String::random(rng)) }
+}
+impl RandomValue for Duration {
+    fn random(rng: &mut RandomSource) -> Self { Duration::new(rng.next_u64(), rng.below(1_000_000_000) as u32) }
+}
+impl RandomValue for SystemTime {
+    fn random(rng: &mut RandomSource) -> Self {
+        // stay within a few thousand years of the epoch, so it fits on every platform
+        let offset = Duration::new(rng.below(100_000_000_000) as u64, rng.below(1_000_000_000) as u32);
+        if rng.bool() { SystemTime::UNIX_EPOCH + offset } else { SystemTime::UNIX_EPOCH - offset }
+    }
+}
+impl RandomValue for Ipv4Addr {
+    fn random(rng: &mut RandomSource) -> Self { Ipv4Addr::from(u32::random(rng)) }
+}
+impl RandomValue for Ipv6Addr {
+    fn random(rng: &mut RandomSource) -> Self { Ipv6Addr::from(u128::random(rng)) }
+}
+impl RandomValue for IpAddr {
+    fn random(rng: &mut RandomSource) -> Self {
+        if rng.bool() { IpAddr::V4(Ipv4Addr::random(rng)) } else { IpAddr::V6(Ipv6Addr::random(rng)) }
+    }
+}
+
+macro_rules! impl_collection {
+    ($($t:ident [$($bound:tt)*]),+) => { $(
+        impl<T: RandomValue $($bound)*> RandomValue for $t<T> {
+            fn random(rng: &mut RandomSource) -> Self {
+                let len = rng.collection_len();
+                (0..len).map(|_| T::random(rng)).collect()
+            }
+        }
+    )+ }
+}
+impl_collection!(Vec [], VecDeque [], HashSet [+ Hash + Eq], BTreeSet [+ Ord]);
+
+macro_rules! impl_map {
+    ($($t:ident [$($bound:tt)*]),+) => { $(
+        impl<K: RandomValue $($bound)*, V: RandomValue> RandomValue for $t<K, V> {
+            fn random(rng: &mut RandomSource) -> Self {
+                let len = rng.collection_len();
+                (0..len).map(|_| (K::random(rng), V::random(rng))).collect()
+            }
+        }
+    )+ }
+}
+impl_map!(HashMap [+ Hash + Eq], BTreeMap [+ Ord]);
+
+impl<T: RandomValue> RandomValue for Option<T> {
+    fn random(rng: &mut RandomSource) -> Self {
+        rng.bool().then(|| T::random(rng))
+    }
+    fn random_variants(rng: &mut RandomSource) -> Vec<Self> {
+        vec![None, Some(T::random(rng))]
+    }
+}
+impl<T: RandomValue, E: RandomValue> RandomValue for Result<T, E> {
+    fn random(rng: &mut RandomSource) -> Self {
+        if rng.bool() { Ok(T::random(rng)) } else { Err(E::random(rng)) }
+    }
+    fn random_variants(rng: &mut RandomSource) -> Vec<Self> {
+        vec![Ok(T::random(rng)), Err(E::random(rng))]
+    }
+}
+impl<T: RandomValue, const N: usize> RandomValue for [T; N] {
+    fn random(rng: &mut RandomSource) -> Self {
+        std::array::from_fn(|_| T::random(rng))
+    }
+}
+
+macro_rules! impl_tuple {
+    ($($t:ident),+) => {
+        impl<$($t: RandomValue),+> RandomValue for ($($t,)+) {
+            fn random(rng: &mut RandomSource) -> Self {
+                ($($t::random(rng),)+)
+            }
+        }
+    };
+}
+impl_tuple!(T1);
+impl_tuple!(T1, T2);
+impl_tuple!(T1, T2, T3);
+impl_tuple!(T1, T2, T3, T4);
+impl_tuple!(T1, T2, T3, T4, T5);
+impl_tuple!(T1, T2, T3, T4, T5, T6);
+impl_tuple!(T1, T2, T3, T4, T5, T6, T7);
+impl_tuple!(T1, T2, T3, T4, T5, T6, T7, T8);
+impl_tuple!(T1, T2, T3, T4, T5, T6, T7, T8, T9);
+impl_tuple!(T1, T2, T3, T4, T5, T6, T7, T8, T9, T10);
+impl_tuple!(T1, T2, T3, T4, T5, T6, T7, T8, T9, T10, T11);
+impl_tuple!(T1, T2, T3, T4, T5, T6, T7, T8, T9, T10, T11, T12);
+
+macro_rules! impl_wrapper {
+    ($($t:ident),+) => { $(
+        impl<T: RandomValue> RandomValue for $t<T> {
+            fn random(rng: &mut RandomSource) -> Self { $t::new(T::random(rng)) }
+            fn random_variants(rng: &mut RandomSource) -> Vec<Self> {
+                T::random_variants(rng).into_iter().map($t::new).collect()
+            }
+        }
+    )+ }
+}
+impl_wrapper!(Box, Rc, Arc);
+
+
+#[test]
+fn test_random_roundtrips() {
+    use crate::packets::*;
+    use crate::types::*;
+    use crate::types::replays::*;
+    use crate::types::network::spectator::*;
+    use crate::types::network::*;
+    use crate::types::network::multiplayer::*;
+
+    let mut rng = RandomSource::from_env();
+    macro_rules! check {
+        ($($t:ty),+) => { $(
+            if let Err(e) = check_random_roundtrips::<$t>(&mut rng, 50) { panic!("{}: {e}", stringify!($t)) }
+        )+ }
+    }
+
+    // packets (which include everything they contain)
+    check!(PacketId, ChatPacket, SpectatorPacket, MultiplayerPacket);
+    // and some types on their own, so every variant is covered
+    check!(Score, Replay, ModDefinition, SpectatorFrame, SpectatorAction, ReplayFrame, ReplayAction, KeyPress);
+    check!(FullLobbyInfo, LobbySlot, LobbyState, LobbyUserState, Vec<ServerPermissions>, MapGame, Md5Hash);
+
+    // std types
+    check!(
+        (u8, i8, u16, i16, u32, i32, u64, i64, u128, i128, half::f16, f32),
+        (f64, usize, bool, char, (), String, Duration, SystemTime, IpAddr),
+        HashMap<String, Vec<Option<u32>>>, BTreeMap<u16, HashSet<String>>, VecDeque<BTreeSet<i8>>,
+        Result<[u8; 3], Box<String>>
+    );
+}
@@ -1,19 +1,51 @@
+use std::borrow::Cow;
 use crate::serialization::*;
 
-pub struct SerializationReader {
-    pub(self) data: Vec<u8>,
+/// reads [`Serializable`] values from some data
+/// 
+/// the data can either be owned ([`Self::new`]) or borrowed ([`Self::from_slice`]).
+/// borrowed readers can also read [`BorrowSerializable`] values, which reference the data instead of copying it
+pub struct SerializationReader<'a> {
+    pub(self) data: Cow<'a, [u8]>,
     pub(self) offset: usize,
-    pub(self) stack: Vec<StackData>,
-    pub(self) stack_depth: usize,
+    /// what is currently being read, only formatted into a stack when there's an error
+    pub(self) crumbs: Vec<Crumb>,
+    /// end offsets of the packet frames we're currently inside of
+    pub(self) frames: Vec<usize>,
+    /// how deeply nested the value currently being read is
+    pub(self) depth: usize,
+    /// how much data the last read which ran out of data needed, so streams know how long to wait
+    pub(crate) needed: usize,
+    pub protocol: Protocol,
+    /// overrides the protocol's [`Encoding`]
+    pub encoding: Option<Encoding>,
+    pub limits: DecodeLimits,
+    /// structured trace of everything read, if tracing is enabled
+    pub(self) trace: Option<Vec<TraceEntry>>,
+    /// path of the value currently being read, only built when tracing
+    pub(self) path: Vec<String>,
     pub debug: bool,
 }
-impl SerializationReader {
+impl<'a> SerializationReader<'a> {
     pub fn new(data: Vec<u8>) -> Self {
+        Self::from_cow(Cow::Owned(data))
+    }
+    pub fn from_slice(data: &'a [u8]) -> Self {
+        Self::from_cow(Cow::Borrowed(data))
+    }
+    fn from_cow(data: Cow<'a, [u8]>) -> Self {
         Self {
             data,
             offset: 0,
-            stack: Vec::new(),
-            stack_depth: 0,
+            crumbs: Vec::new(),
+            frames: Vec::new(),
+            depth: 0,
+            needed: 0,
+            protocol: Protocol::default(),
+            encoding: None,
+            limits: DecodeLimits::default(),
+            trace: None,
+            path: Vec::new(),
             debug: false,
         }
     }
@@ -21,55 +53,237 @@ impl SerializationReader {
         self.debug = true;
         self
     }
+    /// record a [`TraceEntry`] for every value read
+    pub fn trace(mut self) -> Self {
+        self.trace = Some(Vec::new());
+        self
+    }
+    pub fn with_protocol(mut self, protocol: Protocol) -> Self {
+        self.protocol = protocol;
+        self
+    }
+    /// read data written with a different [`Encoding`] than the protocol's (ie while rolling out a new one)
+    pub fn with_encoding(mut self, encoding: Encoding) -> Self {
+        self.encoding = Some(encoding);
+        self
+    }
+    /// how lengths and integers are being read
+    pub fn encoding(&self) -> Encoding {
+        self.encoding.unwrap_or(self.protocol.encoding())
+    }
+    pub fn with_limits(mut self, limits: DecodeLimits) -> Self {
+        self.limits = limits;
+        self
+    }
 
-    pub fn push_parent(&mut self, name: impl ToString) {
-        self.stack.push(StackData {
-            name: name.to_string(),
-            entries: Vec::new(),
-            depth: self.stack_depth,
-        });
-        self.stack_depth += 1;
+    /// group the reads after this under `name` in error stacks (ie the type being read)
+    pub fn push_parent(&mut self, name: impl Into<Cow<'static, str>>) {
+        self.crumbs.push(Crumb::Parent(name.into()));
     }
     pub fn pop_parent(&mut self) {
-        self.stack_depth -= 1;
+        if let Some(i) = self.crumbs.iter().rposition(|c| matches!(c, Crumb::Parent(_))) {
+            self.crumbs.truncate(i);
+        }
     }
-    fn push_stack(&mut self, name: impl ToString, ty: &str) {
-        if self.stack.is_empty() {
-            self.stack.push(StackData::default());
+
+    /// format the breadcrumbs into a stack, for an error
+    fn stack(&self) -> Vec<StackData> {
+        let mut stack = Vec::new();
+        let mut depth = 0;
+        for crumb in &self.crumbs {
+            match crumb {
+                Crumb::Parent(name) => {
+                    stack.push(StackData { depth, name: name.to_string(), entries: Vec::new() });
+                    depth += 1;
+                }
+                Crumb::Read { label, type_name } => {
+                    if stack.is_empty() { stack.push(StackData::default()) }
+                    stack.last_mut().unwrap().entries.push(format!("{} ({type_name})", label.name()));
+                }
+            }
         }
-        self.stack.last_mut().unwrap().entries.push(format!("{} ({ty})", name.to_string()));
+        stack
+    }
+
+    /// where the readable data ends, either the end of the current frame or the end of the data
+    fn limit(&self) -> usize {
+        self.frames.last().copied().unwrap_or(self.data.len())
+    }
+
+    /// create an error with the current stack attached
+    pub fn error(&self, inner: SerializationErrorEnum) -> SerializationError {
+        SerializationError {
+            inner,
+            stack: self.stack()
+        }
+    }
+
+    fn limit_exceeded(&self, limit: LimitKind, requested: usize) -> SerializationError {
+        self.error(SerializationErrorEnum::LimitExceeded { limit, requested, offset: self.offset })
+    }
+
+    /// error for an enum that doesnt have a variant for `id`, which was read at `offset`
+    pub fn unknown_variant(&self, type_name: &'static str, id: impl Into<u64>, offset: usize) -> SerializationError {
+        self.error(SerializationErrorEnum::UnknownVariant { type_name, id: id.into(), offset })
+    }
+
+    /// error for a value that was read at `offset` but isnt valid
+    pub fn invalid_value(&self, type_name: &'static str, reason: impl ToString, offset: usize) -> SerializationError {
+        self.error(SerializationErrorEnum::InvalidValue { type_name, reason: reason.to_string(), offset })
+    }
+
+    /// make sure all the data has been read
+    pub fn finish(&self) -> SerializationResult<()> {
+        if self.can_read() {
+            let remaining = self.limit() - self.offset;
+            return Err(self.error(SerializationErrorEnum::TrailingBytes { remaining, offset: self.offset }))
+        }
+        Ok(())
+    }
+
+    /// make sure a string of `len` bytes is within the limits
+    pub fn check_string_length(&self, len: usize) -> SerializationResult<()> {
+        if len > self.limits.max_string_length {
+            return Err(self.limit_exceeded(LimitKind::StringLength, len))
+        }
+        Ok(())
+    }
+
+    /// make sure a collection of `len` items is within the limits
+    pub fn check_collection_length(&self, len: usize) -> SerializationResult<()> {
+        if len > self.limits.max_collection_length {
+            return Err(self.limit_exceeded(LimitKind::CollectionLength, len))
+        }
+        Ok(())
+    }
+
+    /// how much capacity to reserve for a collection with `len` items of type `T`.
+    /// 
+    /// `len` comes from the data, so it cant be trusted for allocating.
+    /// the capacity is limited so it never takes more memory than there is data left to read
+    pub fn capacity_for<T>(&self, len: usize) -> usize {
+        let remaining = self.limit().saturating_sub(self.offset);
+        len.min(remaining / std::mem::size_of::<T>().max(1))
     }
 
     fn check_bounds(&mut self, size: usize) -> SerializationResult<()> {
-        if self.data.len() < self.offset + size { 
+        let end = self.offset.saturating_add(size);
+        if end > self.limits.max_total_bytes {
+            return Err(self.limit_exceeded(LimitKind::TotalBytes, end))
+        }
+
+        if self.limit() < end { 
             // println!("trying to read {size} at offset {} when len is {}", self.offset, self.data.len());
-            return Err(SerializationError {
-                inner: SerializationErrorEnum::OutOfBounds,
-                stack: self.stack.clone()
-            })
+
+            // frames are bounds checked when they're started, 
+            // so if we're in one, the data inside the frame is wrong
+            let offset = self.offset;
+            let inner = if self.frames.is_empty() {
+                self.needed = end;
+                SerializationErrorEnum::Incomplete { offset }
+            } else {
+                SerializationErrorEnum::OutOfBounds { offset }
+            };
+
+            return Err(self.error(inner))
         }
 
         Ok(())
     }
 
     /// read from the data but reset the offset back to where it was before the read
-    pub fn peek<R:Serializable>(&mut self, name: impl ToString) -> SerializationResult<R> {
+    pub fn peek<R:Serializable>(&mut self, name: impl ReadName) -> SerializationResult<R> {
         let offset = self.offset;
         let read = self.read::<R>(name);
         self.offset = offset;
         read
     }
 
-    pub fn read<R:Serializable>(&mut self, name: impl ToString) -> SerializationResult<R> {
-        let type_name = std::any::type_name::<R>();
-        self.push_stack(name, type_name);
+    pub fn read<R:Serializable>(&mut self, name: impl ReadName) -> SerializationResult<R> {
         // self.check_bounds(std::mem::size_of::<R>())?; // this breaks when R is an enum with differently sized variants
-        R::read(self)
-            .map_err(|e| e.with_stack(self.stack.clone()))
-            .map(|v| { if self.debug { println!("got {v:?} ({type_name})") }; v})
+        self.read_with(name, R::read)
+    }
+
+    /// read a value with `read_fn` instead of its [`Serializable`] impl (ie for `#[serialize(with = path)]`)
+    pub fn read_with<R: core::fmt::Debug>(
+        &mut self, 
+        name: impl ReadName, 
+        read_fn: fn(&mut Self) -> SerializationResult<R>
+    ) -> SerializationResult<R> {
+        let type_name = std::any::type_name::<R>();
+        let label = name.label();
+        if self.trace.is_some() { self.path.push(label.path_segment()) }
+        let crumbs = self.crumbs.len();
+        self.crumbs.push(Crumb::Read { label, type_name });
+
+        let start = self.offset;
+        let read = if self.depth >= self.limits.max_depth {
+            Err(self.limit_exceeded(LimitKind::Depth, self.depth + 1))
+        } else {
+            self.depth += 1;
+            let read = read_fn(self);
+            self.depth -= 1;
+            read
+        };
+
+        if self.trace.is_some() { self.record_trace(type_name, start, &read) }
+
+        let read = match read {
+            Ok(v) => {
+                if self.debug { println!("got {v:?} ({type_name})") }
+                Ok(v)
+            }
+            // errors which didnt come from the reader (ie `?` on a `FromUtf8Error`) get the stack here
+            Err(e) if e.stack.is_empty() => Err(e.with_stack(self.stack())),
+            Err(e) => Err(e),
+        };
+        self.crumbs.truncate(crumbs);
+        read
     }
+
+    fn record_trace<R: core::fmt::Debug>(&mut self, type_name: &'static str, start: usize, read: &SerializationResult<R>) {
+        let path = self.path.concat();
+        self.path.pop();
+
+        let Some(trace) = &mut self.trace else { return };
+        trace.push(TraceEntry {
+            path: path.trim_start_matches('.').to_owned(),
+            type_name,
+            start,
+            end: self.offset,
+            value: match read {
+                Ok(v) => format!("{v:?}"),
+                Err(e) => format!("<error: {}>", e.inner),
+            },
+        });
+    }
+
+    /// the trace recorded so far, if tracing is enabled
+    pub fn trace_entries(&self) -> &[TraceEntry] {
+        self.trace.as_deref().unwrap_or_default()
+    }
+
+    /// an annotated hexdump of the data, using the trace recorded so far
+    pub fn hexdump(&self) -> String {
+        render_hexdump(&self.data, self.trace_entries())
+    }
+
+    /// how many bytes have been read so far
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+    /// get the underlying data back
+    /// - if the data is borrowed, this will copy it
+    pub fn into_data(self) -> Vec<u8> {
+        self.data.into_owned()
+    }
+    /// does this reader borrow its data?
+    pub fn is_borrowed(&self) -> bool {
+        matches!(self.data, Cow::Borrowed(_))
+    }
+
     pub fn can_read(&self) -> bool {
-        self.data.len() - self.offset > 0
+        self.limit() > self.offset
     }
 
     pub fn read_slice(&mut self, size: usize) -> SerializationResult<&[u8]> {
@@ -80,9 +294,150 @@ impl SerializationReader {
         Ok(slice)
     }
 
+    /// read a slice which borrows from the reader's data, instead of from the reader itself
+    /// 
+    /// errors if the reader owns its data
+    pub fn read_borrowed_slice(&mut self, size: usize) -> SerializationResult<&'a [u8]> {
+        let &Cow::Borrowed(data) = &self.data else {
+            return Err(self.invalid_value("&[u8]", "cant borrow from a reader which owns its data", self.offset))
+        };
+
+        self.check_bounds(size)?;
+        let slice = &data[self.offset..self.offset+size];
+        self.offset += size;
+
+        Ok(slice)
+    }
+
+    /// read a value which borrows from the reader's data
+    pub fn read_borrowed<R: BorrowSerializable<'a>>(&mut self, name: impl ReadName) -> SerializationResult<R> {
+        self.read_with(name, R::read_borrowed)
+    }
+
+    /// read the payload length of a packet frame, if the protocol is framed
+    ///
+    /// reads are then limited to the frame until [`Self::end_frame`] is called
+    pub fn begin_frame(&mut self) -> SerializationResult<Option<usize>> {
+        if !self.protocol.is_framed() { return Ok(None) }
+
+        let len = self.read_length()?;
+        self.check_bounds(len)?;
+        // check_bounds makes sure this cant overflow
+
+        let end = self.offset + len;
+        self.frames.push(end);
+        Ok(Some(end))
+    }
+
+    /// skip anything left in the frame (ie fields added by a newer protocol) and stop limiting reads to it
+    pub fn end_frame(&mut self, frame: Option<usize>) {
+        let Some(end) = frame else { return };
+        self.frames.pop();
+        self.offset = end;
+    }
+
     /// unread the amount of bytes provided
+    /// 
+    /// stops at the start of the data if `len` is more than what has been read
     pub fn unread(&mut self, len: usize) {
-        self.offset -= len;
-        self.stack.last_mut().map(|s| s.entries.pop());
+        self.offset = self.offset.saturating_sub(len);
+    }
+}
+
+/// something which names a value being read
+/// 
+/// names are only formatted if they end up in the stack or trace
+pub trait ReadName {
+    fn label(self) -> ReadLabel;
+}
+impl ReadName for &'static str {
+    fn label(self) -> ReadLabel { ReadLabel { name: Cow::Borrowed(self), index: None } }
+}
+impl ReadName for String {
+    fn label(self) -> ReadLabel { ReadLabel { name: Cow::Owned(self), index: None } }
+}
+
+/// name for an item in a collection
+/// 
+/// much cheaper than `format!`ing every item's name
+pub struct ItemName(pub &'static str, pub usize);
+impl ReadName for ItemName {
+    fn label(self) -> ReadLabel { ReadLabel { name: Cow::Borrowed(self.0), index: Some(self.1) } }
+}
+
+/// an unformatted [`ReadName`]
+#[derive(Clone, Debug)]
+pub struct ReadLabel {
+    name: Cow<'static, str>,
+    /// which item in a collection this is
+    index: Option<usize>,
+}
+impl ReadLabel {
+    /// name shown in the stack
+    pub fn name(&self) -> Cow<'_, str> {
+        match self.index {
+            Some(index) => Cow::Owned(format!("{} #{index}", self.name)),
+            None => Cow::Borrowed(&self.name),
+        }
     }
+    /// how this shows up in a trace path, ie `.name` or `[2]`
+    pub fn path_segment(&self) -> String {
+        match self.index {
+            Some(index) => format!("[{index}]"),
+            None => format!(".{}", self.name),
+        }
+    }
+}
+
+/// something being read, for error stacks
+#[derive(Clone, Debug)]
+enum Crumb {
+    /// the reads after this are part of a type
+    Parent(Cow<'static, str>),
+    Read {
+        label: ReadLabel,
+        type_name: &'static str,
+    },
+}
+
+
+#[test]
+fn test_error_stack() {
+    use crate::types::{ Score, Md5Hash, replays::* };
+    let frames = vec![
+        ReplayFrame::new(0.0, ReplayAction::Press(KeyPress::Left)),
+        ReplayFrame::new(1.0, ReplayAction::Release(KeyPress::Left)),
+    ];
+    let mut data = SimpleWriter::new().write::<Vec<ReplayFrame>>(&frames).done();
+    // break the second frame's action
+    let len = data.len();
+    data[len - 2] = 10;
+
+    let mut reader = SerializationReader::new(data.clone());
+    let error = reader.read::<Vec<ReplayFrame>>("frames").unwrap_err();
+    assert!(reader.crumbs.is_empty());
+
+    let mut debug_reader = SerializationReader::new(data).debug();
+    let debug_error = debug_reader.read::<Vec<ReplayFrame>>("frames").unwrap_err();
+
+    assert_eq!(error, debug_error);
+    assert_eq!(error.format_stack(), debug_error.format_stack());
+    assert!(error.format_stack().contains("Vec item #1"));
+
+    // a truncated v3 replay, through a hand-written reader
+    let mut score = Score::new(Md5Hash::default(), "user".to_owned(), "osu".to_owned());
+    score.replay = Some(Replay { frames, ..Default::default() });
+    let mut writer = SerializationWriter::new();
+    Replay::write_with_score(&score, &mut writer, 3);
+    let mut data = writer.data();
+    data.truncate(data.len() - 3);
+
+    let stack = |mut reader: SerializationReader| match Replay::try_read_replay(&mut reader) {
+        Err(ReplayLoadError::SerializationError(e)) => e.format_stack(),
+        other => panic!("expected an error, got {other:?}"),
+    };
+    let error = stack(SerializationReader::new(data.clone()));
+    assert_eq!(error, stack(SerializationReader::new(data).debug()));
+    assert!(error.starts_with("Replay (as Score)"), "{error}");
+    assert!(error.contains("frames (") && error.contains("Vec item #1"), "{error}");
 }
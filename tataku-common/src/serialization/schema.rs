@@ -0,0 +1,442 @@
+use half::f16;
+use std::{
+    rc::Rc,
+    sync::Arc,
+    borrow::Cow,
+    fmt::Write,
+    collections::HashSet,
+    collections::HashMap,
+    collections::BTreeSet,
+    collections::BTreeMap,
+    collections::VecDeque,
+    time::{ Duration, SystemTime },
+    net::{ IpAddr, Ipv4Addr, Ipv6Addr },
+};
+
+/// builds a type's schema. schemas refer to other types with these, so they're only built when needed
+pub type SchemaFn = fn() -> TypeSchema;
+
+/// describes how a type is written
+///
+/// generated by the [`Serializable`](crate::macros::Serializable) and [`PacketSerialization`](crate::macros::PacketSerialization) derives
+pub trait WireSchema {
+    fn schema() -> TypeSchema;
+}
+
+#[derive(Clone, Debug)]
+pub struct TypeSchema {
+    /// ie `u32`, `Vec<LobbyUser>`, `Score`
+    pub name: String,
+    pub kind: SchemaKind,
+}
+impl TypeSchema {
+    pub fn new(name: impl ToString, kind: SchemaKind) -> Self {
+        Self { name: name.to_string(), kind }
+    }
+    pub fn primitive(name: impl ToString, encoding: &'static str) -> Self {
+        Self::new(name, SchemaKind::Primitive { encoding })
+    }
+
+    /// the types this type is made of
+    pub fn children(&self) -> Vec<SchemaFn> {
+        match &self.kind {
+            SchemaKind::Primitive { .. } | SchemaKind::Bitmask { .. } => Vec::new(),
+            SchemaKind::List { item } | SchemaKind::Array { item, .. } | SchemaKind::Option { item } => vec![*item],
+            SchemaKind::Map { key, value } => vec![*key, *value],
+            SchemaKind::Result { ok, err } => vec![*ok, *err],
+            SchemaKind::Tuple { items } => items.clone(),
+            SchemaKind::Struct { fields, .. } => fields.iter().map(|f| f.ty).collect(),
+            SchemaKind::Enum { variants, .. } => variants.iter().flat_map(|v| v.fields.iter().map(|f| f.ty)).collect(),
+        }
+    }
+
+    /// does this type get its own section in the reference
+    fn is_named(&self) -> bool {
+        matches!(self.kind, SchemaKind::Struct { .. } | SchemaKind::Enum { .. } | SchemaKind::Bitmask { .. })
+    }
+}
+
+#[derive(Clone, Debug)]
+pub enum SchemaKind {
+    /// a value with its own encoding
+    Primitive { encoding: &'static str },
+    /// [item count (u64)] [items]
+    List { item: SchemaFn },
+    /// [items], there are always `len` items
+    Array { item: SchemaFn, len: usize },
+    /// [item count (u64)] [key1] [value1] [...]
+    Map { key: SchemaFn, value: SchemaFn },
+    /// [0 (None) or 1 (Some) as u8] [item if Some]
+    Option { item: SchemaFn },
+    /// [0 (Ok) or 1 (Err) as u8] [ok or err]
+    Result { ok: SchemaFn, err: SchemaFn },
+    /// [item 1] [item 2] [...]
+    Tuple { items: Vec<SchemaFn> },
+    /// [version (u16), if versioned] [fields live in that version]
+    Struct {
+        /// is the version written first
+        versioned: bool,
+        current_version: Option<u16>,
+        fields: Vec<FieldSchema>,
+    },
+    /// [variant id] [payload length (u64), if framed] [variant fields]
+    Enum {
+        /// the type the variant id is written as
+        id_type: &'static str,
+        /// is the payload length written (when the protocol is framed)
+        framed: bool,
+        /// do unknown ids error, instead of being read as the fallback
+        strict: bool,
+        fallback: Option<&'static str>,
+        variants: Vec<VariantSchema>,
+    },
+    /// a list of flags written as their values or'd together
+    Bitmask { id_type: &'static str, flags: Vec<(&'static str, u64)> },
+}
+
+#[derive(Clone, Debug)]
+pub struct FieldSchema {
+    pub name: &'static str,
+    pub ty: SchemaFn,
+    /// first version with this field
+    pub added: u16,
+    /// first version without this field
+    pub removed: Option<u16>,
+    pub docs: &'static str,
+}
+impl FieldSchema {
+    /// a field which is always there
+    pub fn new<T: WireSchema>(name: &'static str) -> Self {
+        Self { name, ty: T::schema, added: 0, removed: None, docs: "" }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct VariantSchema {
+    pub name: &'static str,
+    pub id: u64,
+    pub fields: Vec<FieldSchema>,
+    pub docs: &'static str,
+}
+
+
+/// every type reachable from `roots`, in the order they're first found
+pub fn collect_schemas(roots: &[SchemaFn]) -> Vec<TypeSchema> {
+    let mut seen = HashSet::new();
+    let mut types = Vec::new();
+    let mut queue = roots.iter().copied().collect::<VecDeque<_>>();
+
+    while let Some(schema) = queue.pop_front() {
+        let schema = schema();
+        if !seen.insert(schema.name.clone()) { continue }
+
+        queue.extend(schema.children());
+        types.push(schema);
+    }
+
+    types
+}
+
+/// render a markdown reference for `roots` and every type they use
+pub fn render_schema_markdown(title: &str, roots: &[SchemaFn]) -> String {
+    let types = collect_schemas(roots);
+    let mut out = String::new();
+    let _ = writeln!(out, "# {title}\n");
+    let _ = writeln!(out, "everything is little endian. this file is generated from the `WireSchema` of each type, don't edit it by hand\n");
+    let _ = writeln!(out, "in the compact protocol (v3), lengths and integers bigger than a byte are written as LEB128 varints instead, with signed integers zigzag encoded first\n");
+    let _ = writeln!(out, "from v4, each packet sent on its own (not ones inside other packets) starts with a request id, as an `Option<u32>`. servers copy the id of a request into their reply to it\n");
+
+    for ty in types.iter().filter(|t| t.is_named()) {
+        let _ = writeln!(out, "## `{}`\n", ty.name);
+
+        match &ty.kind {
+            SchemaKind::Struct { versioned, current_version, fields } => {
+                match (versioned, current_version) {
+                    (true, Some(v)) => { let _ = writeln!(out, "struct, starts with its version as a `u16` (currently {v})\n"); }
+                    (true, None) => { let _ = writeln!(out, "struct, starts with its version as a `u16`\n"); }
+                    _ => { let _ = writeln!(out, "struct\n"); }
+                }
+
+                let _ = writeln!(out, "| field | type | versions | notes |");
+                let _ = writeln!(out, "| --- | --- | --- | --- |");
+                for f in fields {
+                    let _ = writeln!(out, "| `{}` | `{}` | {} | {} |", f.name, (f.ty)().name, versions(f), table_text(f.docs));
+                }
+            }
+
+            SchemaKind::Enum { id_type, framed, strict, fallback, variants } => {
+                let _ = write!(out, "enum, the variant id is written as a `{id_type}`");
+                if *framed { let _ = write!(out, ", followed by the payload length (`u64`) when the protocol is framed"); }
+                if *strict { let _ = write!(out, ". unknown ids are an error"); }
+                else if let Some(fallback) = fallback { let _ = write!(out, ". unknown ids are read as `{fallback}`"); }
+                let _ = writeln!(out, "\n");
+
+                let _ = writeln!(out, "| id | variant | fields | notes |");
+                let _ = writeln!(out, "| --- | --- | --- | --- |");
+                for v in variants {
+                    let fields = v.fields.iter()
+                        .map(|f| format!("`{}: {}`", f.name, (f.ty)().name))
+                        .collect::<Vec<_>>()
+                        .join("<br>");
+                    let _ = writeln!(out, "| `{}` | `{}` | {fields} | {} |", v.id, v.name, table_text(v.docs));
+                }
+            }
+
+            SchemaKind::Bitmask { id_type, flags } => {
+                let _ = writeln!(out, "bitmask, written as a `{id_type}` with each flag's value or'd together\n");
+                let _ = writeln!(out, "| flag | value |");
+                let _ = writeln!(out, "| --- | --- |");
+                for (name, value) in flags {
+                    let _ = writeln!(out, "| `{name}` | `{value}` |");
+                }
+            }
+
+            _ => {}
+        }
+        out.push('\n');
+    }
+
+    // how everything else is written
+    let _ = writeln!(out, "## Encodings\n");
+    let _ = writeln!(out, "| type | encoding |");
+    let _ = writeln!(out, "| --- | --- |");
+    let mut listed = HashSet::new();
+    for ty in types.iter() {
+        // containers are listed once for any item type, ie `Vec<T>`
+        let container = ty.name.split('<').next().unwrap_or_default();
+        let (name, encoding) = match &ty.kind {
+            SchemaKind::Primitive { encoding } => (ty.name.clone(), *encoding),
+            SchemaKind::List { .. } => (format!("{container}<T>"), "[item count (u64)] [items]"),
+            SchemaKind::Array { .. } => ("[T; N]".to_owned(), "[items], there are always N items so the count isn't written"),
+            SchemaKind::Map { .. } => (format!("{container}<K, V>"), "[item count (u64)] [key1] [value1] [key2] [value2] [...]"),
+            SchemaKind::Option { .. } => ("Option<T>".to_owned(), "[0 (None) or 1 (Some) as u8] [T, only if Some]"),
+            SchemaKind::Result { .. } => ("Result<T, E>".to_owned(), "[0 (Ok) or 1 (Err) as u8] [T or E]"),
+            SchemaKind::Tuple { .. } => ("(T1, T2)".to_owned(), "[T1] [T2] [...]"),
+            _ => continue,
+        };
+
+        if !listed.insert(name.clone()) { continue }
+        let _ = writeln!(out, "| `{name}` | {} |", table_text(encoding));
+    }
+
+    out
+}
+
+/// render a json description of `roots` and every type they use
+///
+/// types refer to each other by name
+pub fn render_schema_json(roots: &[SchemaFn]) -> serde_json::Value {
+    use serde_json::json;
+    let name = |ty: &SchemaFn| ty().name;
+    let fields = |fields: &[FieldSchema]| fields.iter().map(|f| json!({
+        "name": f.name,
+        "type": name(&f.ty),
+        "added": f.added,
+        "removed": f.removed,
+        "docs": f.docs,
+    })).collect::<Vec<_>>();
+
+    let types = collect_schemas(roots).into_iter().map(|ty| {
+        let kind = match &ty.kind {
+            SchemaKind::Primitive { encoding } => json!({ "kind": "primitive", "encoding": encoding }),
+            SchemaKind::List { item } => json!({ "kind": "list", "item": name(item) }),
+            SchemaKind::Array { item, len } => json!({ "kind": "array", "item": name(item), "len": len }),
+            SchemaKind::Map { key, value } => json!({ "kind": "map", "key": name(key), "value": name(value) }),
+            SchemaKind::Option { item } => json!({ "kind": "option", "item": name(item) }),
+            SchemaKind::Result { ok, err } => json!({ "kind": "result", "ok": name(ok), "err": name(err) }),
+            SchemaKind::Tuple { items } => json!({ "kind": "tuple", "items": items.iter().map(name).collect::<Vec<_>>() }),
+            SchemaKind::Struct { versioned, current_version, fields: f } => json!({
+                "kind": "struct",
+                "versioned": versioned,
+                "current_version": current_version,
+                "fields": fields(f),
+            }),
+            SchemaKind::Enum { id_type, framed, strict, fallback, variants } => json!({
+                "kind": "enum",
+                "id_type": id_type,
+                "framed": framed,
+                "strict": strict,
+                "fallback": fallback,
+                "variants": variants.iter().map(|v| json!({
+                    "name": v.name,
+                    "id": v.id,
+                    "fields": fields(&v.fields),
+                    "docs": v.docs,
+                })).collect::<Vec<_>>(),
+            }),
+            SchemaKind::Bitmask { id_type, flags } => json!({
+                "kind": "bitmask",
+                "id_type": id_type,
+                "flags": flags.iter().map(|(name, value)| json!({ "name": name, "value": value })).collect::<Vec<_>>(),
+            }),
+        };
+
+        let mut kind = kind;
+        kind["name"] = json!(ty.name);
+        kind
+    }).collect::<Vec<_>>();
+
+    json!({ "types": types })
+}
+
+fn versions(f: &FieldSchema) -> String {
+    match (f.added, f.removed) {
+        (0, None) => String::new(),
+        (0, Some(removed)) => format!("before {removed}"),
+        (added, None) => format!("{added}+"),
+        (added, Some(removed)) => format!("{added} to {}", removed - 1),
+    }
+}
+
+/// make text safe to put in a table cell
+fn table_text(text: &str) -> String {
+    text.replace('|', "\\|").replace('\n', "<br>")
+}
+
+
+macro_rules! impl_primitive {
+    ($($t:ty => $encoding:literal),+ $(,)?) => { $(
+        impl WireSchema for $t {
+            fn schema() -> TypeSchema {
+                TypeSchema::primitive(stringify!($t), $encoding)
+            }
+        }
+    )+ }
+}
+impl_primitive! {
+    u8 => "unsigned byte",
+    i8 => "signed byte",
+    u16 => "2 byte unsigned int",
+    i16 => "2 byte signed int",
+    u32 => "4 byte unsigned int",
+    i32 => "4 byte signed int",
+    u64 => "8 byte unsigned int",
+    i64 => "8 byte signed int",
+    u128 => "16 byte unsigned int",
+    i128 => "16 byte signed int",
+    f16 => "2 byte float",
+    f32 => "4 byte float",
+    f64 => "8 byte float",
+    usize => "written as a u64",
+    bool => "0 (false) or 1 (true) as a u8",
+    char => "unicode scalar value (u32)",
+    () => "nothing is written",
+    String => "[byte count (u64)] [utf8 bytes]",
+    Duration => "[seconds (u64)] [nanoseconds (u32)]",
+    SystemTime => "[seconds since unix epoch (i64)] [nanoseconds (u32)]",
+    Ipv4Addr => "4 address bytes",
+    Ipv6Addr => "16 address bytes",
+    IpAddr => "[0 (v4) or 1 (v6) as u8] [4 (v4) or 16 (v6) address bytes]",
+}
+// written the same as a string
+impl WireSchema for Cow<'_, str> {
+    fn schema() -> TypeSchema { String::schema() }
+}
+
+macro_rules! impl_list {
+    ($($t:ident),+) => { $(
+        impl<T: WireSchema> WireSchema for $t<T> {
+            fn schema() -> TypeSchema {
+                TypeSchema::new(format!("{}<{}>", stringify!($t), T::schema().name), SchemaKind::List { item: T::schema })
+            }
+        }
+    )+ }
+}
+impl_list!(Vec, VecDeque, HashSet, BTreeSet);
+impl<T: WireSchema> WireSchema for &Vec<T> {
+    fn schema() -> TypeSchema { Vec::<T>::schema() }
+}
+
+macro_rules! impl_map {
+    ($($t:ident),+) => { $(
+        impl<K: WireSchema, V: WireSchema> WireSchema for $t<K, V> {
+            fn schema() -> TypeSchema {
+                TypeSchema::new(
+                    format!("{}<{}, {}>", stringify!($t), K::schema().name, V::schema().name),
+                    SchemaKind::Map { key: K::schema, value: V::schema }
+                )
+            }
+        }
+    )+ }
+}
+impl_map!(HashMap, BTreeMap);
+
+impl<T: WireSchema> WireSchema for Option<T> {
+    fn schema() -> TypeSchema {
+        TypeSchema::new(format!("Option<{}>", T::schema().name), SchemaKind::Option { item: T::schema })
+    }
+}
+impl<T: WireSchema, E: WireSchema> WireSchema for Result<T, E> {
+    fn schema() -> TypeSchema {
+        TypeSchema::new(
+            format!("Result<{}, {}>", T::schema().name, E::schema().name),
+            SchemaKind::Result { ok: T::schema, err: E::schema }
+        )
+    }
+}
+impl<T: WireSchema, const N: usize> WireSchema for [T; N] {
+    fn schema() -> TypeSchema {
+        TypeSchema::new(format!("[{}; {N}]", T::schema().name), SchemaKind::Array { item: T::schema, len: N })
+    }
+}
+
+macro_rules! impl_tuple {
+    ($($t:ident),+) => {
+        impl<$($t: WireSchema),+> WireSchema for ($($t,)+) {
+            fn schema() -> TypeSchema {
+                let names = [$($t::schema().name),+];
+                TypeSchema::new(format!("({})", names.join(", ")), SchemaKind::Tuple { items: vec![$($t::schema),+] })
+            }
+        }
+    };
+}
+impl_tuple!(T1);
+impl_tuple!(T1, T2);
+impl_tuple!(T1, T2, T3);
+impl_tuple!(T1, T2, T3, T4);
+impl_tuple!(T1, T2, T3, T4, T5);
+impl_tuple!(T1, T2, T3, T4, T5, T6);
+impl_tuple!(T1, T2, T3, T4, T5, T6, T7);
+impl_tuple!(T1, T2, T3, T4, T5, T6, T7, T8);
+impl_tuple!(T1, T2, T3, T4, T5, T6, T7, T8, T9);
+impl_tuple!(T1, T2, T3, T4, T5, T6, T7, T8, T9, T10);
+impl_tuple!(T1, T2, T3, T4, T5, T6, T7, T8, T9, T10, T11);
+impl_tuple!(T1, T2, T3, T4, T5, T6, T7, T8, T9, T10, T11, T12);
+
+// wrappers are written as what they wrap
+macro_rules! impl_wrapper {
+    ($($t:ident),+) => { $(
+        impl<T: WireSchema> WireSchema for $t<T> {
+            fn schema() -> TypeSchema { T::schema() }
+        }
+    )+ }
+}
+impl_wrapper!(Box, Rc, Arc);
+
+
+#[test]
+fn test_schema() {
+    use crate::packets::*;
+    use crate::types::Score;
+
+    let TypeSchema { kind: SchemaKind::Enum { id_type, variants, framed, .. }, .. } = PacketId::schema() else { panic!("PacketId isnt an enum") };
+    assert_eq!(id_type, "u16");
+    assert!(framed);
+    let login = variants.iter().find(|v| v.name == "Client_UserLogin").unwrap();
+    assert_eq!(login.id, 100);
+    assert_eq!(login.fields.iter().map(|f| f.name).collect::<Vec<_>>(), ["protocol_version", "username", "password", "game"]);
+
+    let TypeSchema { kind: SchemaKind::Struct { current_version, fields, .. }, .. } = Score::schema() else { panic!("Score isnt a struct") };
+    assert_eq!(current_version, Some(10));
+    let speed = fields.iter().filter(|f| f.name == "speed").map(|f| ((f.ty)().name, f.added, f.removed)).collect::<Vec<_>>();
+    assert_eq!(speed, [("f32".to_owned(), 2, Some(10)), ("u16".to_owned(), 10, None)]);
+
+    // nested packets and types are included
+    let names = collect_schemas(&[PacketId::schema]).into_iter().map(|t| t.name).collect::<Vec<_>>();
+    for name in ["ChatPacket", "SpectatorPacket", "MultiplayerPacket", "Score", "LobbyInfo", "Vec<ModDefinition>", "u16"] {
+        assert!(names.iter().any(|n| n == name), "{name} is missing");
+    }
+
+    let json = render_schema_json(&[PacketId::schema]);
+    assert!(json["types"].as_array().unwrap().iter().any(|t| t["name"] == "Score" && t["current_version"] == 10));
+}
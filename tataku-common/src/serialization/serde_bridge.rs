@@ -0,0 +1,719 @@
+use serde::{ ser, de, Serialize, Deserialize, de::DeserializeOwned, de::IntoDeserializer };
+use crate::serialization::*;
+use crate::types::network::multiplayer::{ LobbySlot, LobbyState, LobbyUserState };
+
+/// the ids a [`PacketSerialization`](crate::macros::PacketSerialization) enum writes for its variants
+///
+/// generated by the derive, so the serde bridge can write the enum the same way
+#[derive(Copy, Clone, Debug)]
+pub struct VariantIds {
+    pub enum_name: &'static str,
+    /// how many bytes the id is written as
+    pub id_size: usize,
+    /// (variant name, id)
+    pub ids: &'static [(&'static str, u64)],
+    /// variant unknown ids are read as, if the enum isnt strict
+    pub fallback: Option<&'static str>,
+    /// are the variant fields written in a frame (when the protocol is framed)
+    pub framed: bool,
+}
+impl VariantIds {
+    fn id_of(&self, variant: &str) -> Option<u64> {
+        self.ids.iter().find(|(name, _)| *name == variant).map(|(_, id)| *id)
+    }
+    pub(crate) fn variant_of(&self, id: u64) -> Option<&'static str> {
+        self.ids.iter().find(|(_, i)| *i == id).map(|(name, _)| *name)
+    }
+}
+
+/// implemented by the [`PacketSerialization`](crate::macros::PacketSerialization) derive
+pub trait HasVariantIds {
+    const VARIANT_IDS: VariantIds;
+}
+
+/// how the serde bridge writes enums
+///
+/// enums are matched by name. registered enums use their packet ids,
+/// anything else is written as a u8 variant index (the same as a hand-written [`Serializable`] impl would)
+#[derive(Clone, Debug)]
+pub struct SerdeOptions {
+    enums: Vec<VariantIds>,
+}
+impl SerdeOptions {
+    /// no enums registered, every enum is written with its variant index
+    pub fn empty() -> Self {
+        Self { enums: Vec::new() }
+    }
+
+    pub fn with_enum<T: HasVariantIds>(self) -> Self {
+        self.with_variant_ids(T::VARIANT_IDS)
+    }
+
+    /// use custom ids for an enum (ie for a type from another crate)
+    pub fn with_variant_ids(mut self, ids: VariantIds) -> Self {
+        self.enums.retain(|e| e.enum_name != ids.enum_name);
+        self.enums.push(ids);
+        self
+    }
+
+    fn variant_ids(&self, enum_name: &str) -> Option<&VariantIds> {
+        self.enums.iter().find(|e| e.enum_name == enum_name)
+    }
+
+    pub fn to_bytes<T: Serialize + ?Sized>(&self, value: &T) -> Result<Vec<u8>, SerdeError> {
+        let mut sw = SerializationWriter::new();
+        self.write(&mut sw, value)?;
+        Ok(sw.data())
+    }
+
+    /// errors if there's data left over
+    pub fn from_bytes<'de, T: Deserialize<'de>>(&self, data: &'de [u8]) -> Result<T, SerdeError> {
+        let mut sr = SerializationReader::from_slice(data);
+        let value = self.read(&mut sr)?;
+        sr.finish()?;
+        Ok(value)
+    }
+
+    pub fn write<T: Serialize + ?Sized>(&self, sw: &mut SerializationWriter, value: &T) -> Result<(), SerdeError> {
+        value.serialize(&mut SerdeWriter::new(sw, self))
+    }
+
+    pub fn read<'de, T: Deserialize<'de>>(&self, sr: &mut SerializationReader<'de>) -> Result<T, SerdeError> {
+        T::deserialize(&mut SerdeReader::new(sr, self))
+    }
+}
+impl Default for SerdeOptions {
+    /// every enum in this crate which has both serde and packet ids
+    fn default() -> Self {
+        Self::empty()
+            .with_enum::<LobbySlot>()
+            .with_enum::<LobbyState>()
+            .with_enum::<LobbyUserState>()
+    }
+}
+
+
+/// write any serde type with the same encoding as [`Serializable`]
+///
+/// panics when writing if the value's `Serialize` impl errors
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Serde<T>(pub T);
+impl<T: Serialize + DeserializeOwned + core::fmt::Debug> Serializable for Serde<T> {
+    fn read(sr: &mut SerializationReader) -> SerializationResult<Self> {
+        let offset = sr.offset();
+        match SerdeOptions::default().read(sr) {
+            Ok(value) => Ok(Self(value)),
+            Err(SerdeError::Serialization(e)) => Err(e),
+            Err(SerdeError::Custom(reason)) => Err(sr.invalid_value(std::any::type_name::<T>(), reason, offset)),
+        }
+    }
+
+    fn write(&self, sw: &mut SerializationWriter) {
+        if let Err(e) = SerdeOptions::default().write(sw, &self.0) {
+            panic!("error serializing {}: {e}", std::any::type_name::<T>())
+        }
+    }
+}
+
+
+#[derive(Debug)]
+pub enum SerdeError {
+    Serialization(SerializationError),
+    /// error from a serde impl, or something the format cant represent
+    Custom(String),
+}
+impl From<SerializationError> for SerdeError {
+    fn from(value: SerializationError) -> Self {
+        Self::Serialization(value)
+    }
+}
+impl core::fmt::Display for SerdeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Serialization(e) => write!(f, "{e}"),
+            Self::Custom(e) => write!(f, "{e}"),
+        }
+    }
+}
+impl std::error::Error for SerdeError {}
+impl ser::Error for SerdeError {
+    fn custom<T: std::fmt::Display>(msg: T) -> Self {
+        Self::Custom(msg.to_string())
+    }
+}
+impl de::Error for SerdeError {
+    fn custom<T: std::fmt::Display>(msg: T) -> Self {
+        Self::Custom(msg.to_string())
+    }
+}
+
+
+/// serde serializer which writes to a [`SerializationWriter`]
+pub struct SerdeWriter<'a, 'w> {
+    sw: &'a mut SerializationWriter<'w>,
+    options: &'a SerdeOptions,
+}
+impl<'a, 'w> SerdeWriter<'a, 'w> {
+    pub fn new(sw: &'a mut SerializationWriter<'w>, options: &'a SerdeOptions) -> Self {
+        Self { sw, options }
+    }
+
+    fn write_len_bytes(&mut self, bytes: &[u8]) {
+        self.sw.write_length(bytes.len());
+        self.sw.write_raw_bytes(bytes);
+    }
+
+    /// write the id of a variant, returning the frame if the enum is framed
+    fn write_variant(&mut self, enum_name: &str, index: u32, variant: &str) -> Result<Option<usize>, SerdeError> {
+        let Some(ids) = self.options.variant_ids(enum_name) else {
+            let index = u8::try_from(index).map_err(|_| SerdeError::Custom(format!("{enum_name} has too many variants to write without ids")))?;
+            self.sw.write(&index);
+            return Ok(None)
+        };
+
+        let id = ids.id_of(variant).ok_or_else(|| SerdeError::Custom(format!("{enum_name}::{variant} has no id")))?;
+        match ids.id_size {
+            1 => self.sw.write(&(id as u8)),
+            2 => self.sw.write(&(id as u16)),
+            4 => self.sw.write(&(id as u32)),
+            _ => self.sw.write(&id),
+        }
+
+        Ok(if ids.framed { self.sw.begin_frame() } else { None })
+    }
+
+    /// write a length, or a placeholder for it if its not known yet
+    fn begin_len<'b>(&'b mut self, len: Option<usize>) -> SerdeCompound<'b, 'a, 'w> {
+        let placeholder = match len {
+            Some(len) => { self.sw.write(&len); None }
+            None => Some(self.sw.reserve_length()),
+        };
+
+        SerdeCompound::new(self, placeholder, None)
+    }
+}
+
+/// a value made of other values, ie a seq or struct
+pub struct SerdeCompound<'b, 'a, 'w> {
+    ser: &'b mut SerdeWriter<'a, 'w>,
+    /// where the length needs to be written once the value is done
+    placeholder: Option<usize>,
+    count: usize,
+    frame: Option<usize>,
+    /// (start, key end) of each map entry, so they can be sorted if the writer is canonical
+    entries: Vec<(usize, usize)>,
+}
+impl<'b, 'a, 'w> SerdeCompound<'b, 'a, 'w> {
+    fn new(ser: &'b mut SerdeWriter<'a, 'w>, placeholder: Option<usize>, frame: Option<usize>) -> Self {
+        // map entries are sorted in place
+        if ser.sw.canonical { ser.sw.hold() }
+        Self { ser, placeholder, count: 0, frame, entries: Vec::new() }
+    }
+
+    fn key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<(), SerdeError> {
+        let start = self.ser.sw.data.len();
+        self.element(key)?;
+        if self.ser.sw.canonical { self.entries.push((start, self.ser.sw.data.len())) }
+        Ok(())
+    }
+
+    /// sort the map entries by their encoded keys
+    fn sort_entries(&mut self) {
+        let Some(&(first, _)) = self.entries.first() else { return };
+        let data = &mut self.ser.sw.data;
+
+        let ends = self.entries.iter().skip(1).map(|(start, _)| *start).chain([data.len()]);
+        let mut entries = self.entries.iter().zip(ends)
+            .map(|(&(start, key_end), end)| (start, key_end, end))
+            .collect::<Vec<_>>();
+        entries.sort_unstable_by(|a, b| data[a.0..a.1].cmp(&data[b.0..b.1]));
+
+        let sorted = entries.iter().flat_map(|&(start, _, end)| data[start..end].iter().copied()).collect::<Vec<_>>();
+        data.truncate(first);
+        data.extend(sorted);
+    }
+
+    fn element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), SerdeError> {
+        self.count += 1;
+        value.serialize(&mut *self.ser)
+    }
+
+    fn end(mut self) -> Result<(), SerdeError> {
+        self.sort_entries();
+        if self.ser.sw.canonical { self.ser.sw.release() }
+        if let Some(at) = self.placeholder {
+            self.ser.sw.fill_length(at, self.count);
+        }
+        self.ser.sw.end_frame(self.frame);
+        Ok(())
+    }
+}
+
+impl<'b, 'a, 'w> ser::Serializer for &'b mut SerdeWriter<'a, 'w> {
+    type Ok = ();
+    type Error = SerdeError;
+    type SerializeSeq = SerdeCompound<'b, 'a, 'w>;
+    type SerializeTuple = SerdeCompound<'b, 'a, 'w>;
+    type SerializeTupleStruct = SerdeCompound<'b, 'a, 'w>;
+    type SerializeTupleVariant = SerdeCompound<'b, 'a, 'w>;
+    type SerializeMap = SerdeCompound<'b, 'a, 'w>;
+    type SerializeStruct = SerdeCompound<'b, 'a, 'w>;
+    type SerializeStructVariant = SerdeCompound<'b, 'a, 'w>;
+
+    fn serialize_bool(self, v: bool) -> Result<(), SerdeError> { self.sw.write(&v); Ok(()) }
+    fn serialize_i8(self, v: i8) -> Result<(), SerdeError> { self.sw.write(&v); Ok(()) }
+    fn serialize_i16(self, v: i16) -> Result<(), SerdeError> { self.sw.write(&v); Ok(()) }
+    fn serialize_i32(self, v: i32) -> Result<(), SerdeError> { self.sw.write(&v); Ok(()) }
+    fn serialize_i64(self, v: i64) -> Result<(), SerdeError> { self.sw.write(&v); Ok(()) }
+    fn serialize_i128(self, v: i128) -> Result<(), SerdeError> { self.sw.write(&v); Ok(()) }
+    fn serialize_u8(self, v: u8) -> Result<(), SerdeError> { self.sw.write(&v); Ok(()) }
+    fn serialize_u16(self, v: u16) -> Result<(), SerdeError> { self.sw.write(&v); Ok(()) }
+    fn serialize_u32(self, v: u32) -> Result<(), SerdeError> { self.sw.write(&v); Ok(()) }
+    fn serialize_u64(self, v: u64) -> Result<(), SerdeError> { self.sw.write(&v); Ok(()) }
+    fn serialize_u128(self, v: u128) -> Result<(), SerdeError> { self.sw.write(&v); Ok(()) }
+    fn serialize_f32(self, v: f32) -> Result<(), SerdeError> { self.sw.write(&v); Ok(()) }
+    fn serialize_f64(self, v: f64) -> Result<(), SerdeError> { self.sw.write(&v); Ok(()) }
+    fn serialize_char(self, v: char) -> Result<(), SerdeError> { self.sw.write(&v); Ok(()) }
+
+    fn serialize_str(self, v: &str) -> Result<(), SerdeError> {
+        self.write_len_bytes(v.as_bytes());
+        Ok(())
+    }
+    fn serialize_bytes(self, v: &[u8]) -> Result<(), SerdeError> {
+        self.write_len_bytes(v);
+        Ok(())
+    }
+
+    fn serialize_none(self) -> Result<(), SerdeError> {
+        self.sw.write(&false);
+        Ok(())
+    }
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<(), SerdeError> {
+        self.sw.write(&true);
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<(), SerdeError> { Ok(()) }
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<(), SerdeError> { Ok(()) }
+    fn serialize_unit_variant(self, name: &'static str, index: u32, variant: &'static str) -> Result<(), SerdeError> {
+        let frame = self.write_variant(name, index, variant)?;
+        self.sw.end_frame(frame);
+        Ok(())
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(self, _name: &'static str, value: &T) -> Result<(), SerdeError> {
+        value.serialize(self)
+    }
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        name: &'static str,
+        index: u32,
+        variant: &'static str,
+        value: &T
+    ) -> Result<(), SerdeError> {
+        let frame = self.write_variant(name, index, variant)?;
+        value.serialize(&mut *self)?;
+        self.sw.end_frame(frame);
+        Ok(())
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<Self::SerializeSeq, SerdeError> {
+        Ok(self.begin_len(len))
+    }
+    fn serialize_map(self, len: Option<usize>) -> Result<Self::SerializeMap, SerdeError> {
+        Ok(self.begin_len(len))
+    }
+
+    // tuples and structs have a known size, so theres no length
+    fn serialize_tuple(self, _len: usize) -> Result<Self::SerializeTuple, SerdeError> {
+        Ok(SerdeCompound::new(self, None, None))
+    }
+    fn serialize_tuple_struct(self, _name: &'static str, _len: usize) -> Result<Self::SerializeTupleStruct, SerdeError> {
+        Ok(SerdeCompound::new(self, None, None))
+    }
+    fn serialize_struct(self, _name: &'static str, _len: usize) -> Result<Self::SerializeStruct, SerdeError> {
+        Ok(SerdeCompound::new(self, None, None))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        name: &'static str,
+        index: u32,
+        variant: &'static str,
+        _len: usize
+    ) -> Result<Self::SerializeTupleVariant, SerdeError> {
+        let frame = self.write_variant(name, index, variant)?;
+        Ok(SerdeCompound::new(self, None, frame))
+    }
+    fn serialize_struct_variant(
+        self,
+        name: &'static str,
+        index: u32,
+        variant: &'static str,
+        _len: usize
+    ) -> Result<Self::SerializeStructVariant, SerdeError> {
+        let frame = self.write_variant(name, index, variant)?;
+        Ok(SerdeCompound::new(self, None, frame))
+    }
+
+    fn is_human_readable(&self) -> bool { false }
+}
+
+impl ser::SerializeSeq for SerdeCompound<'_, '_, '_> {
+    type Ok = ();
+    type Error = SerdeError;
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), SerdeError> { self.element(value) }
+    fn end(self) -> Result<(), SerdeError> { SerdeCompound::end(self) }
+}
+impl ser::SerializeTuple for SerdeCompound<'_, '_, '_> {
+    type Ok = ();
+    type Error = SerdeError;
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), SerdeError> { self.element(value) }
+    fn end(self) -> Result<(), SerdeError> { SerdeCompound::end(self) }
+}
+impl ser::SerializeTupleStruct for SerdeCompound<'_, '_, '_> {
+    type Ok = ();
+    type Error = SerdeError;
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), SerdeError> { self.element(value) }
+    fn end(self) -> Result<(), SerdeError> { SerdeCompound::end(self) }
+}
+impl ser::SerializeTupleVariant for SerdeCompound<'_, '_, '_> {
+    type Ok = ();
+    type Error = SerdeError;
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), SerdeError> { self.element(value) }
+    fn end(self) -> Result<(), SerdeError> { SerdeCompound::end(self) }
+}
+impl ser::SerializeMap for SerdeCompound<'_, '_, '_> {
+    type Ok = ();
+    type Error = SerdeError;
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<(), SerdeError> { self.key(key) }
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), SerdeError> { value.serialize(&mut *self.ser) }
+    fn end(self) -> Result<(), SerdeError> { SerdeCompound::end(self) }
+}
+impl ser::SerializeStruct for SerdeCompound<'_, '_, '_> {
+    type Ok = ();
+    type Error = SerdeError;
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, _key: &'static str, value: &T) -> Result<(), SerdeError> { self.element(value) }
+    fn end(self) -> Result<(), SerdeError> { SerdeCompound::end(self) }
+}
+impl ser::SerializeStructVariant for SerdeCompound<'_, '_, '_> {
+    type Ok = ();
+    type Error = SerdeError;
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, _key: &'static str, value: &T) -> Result<(), SerdeError> { self.element(value) }
+    fn end(self) -> Result<(), SerdeError> { SerdeCompound::end(self) }
+}
+
+
+/// serde deserializer which reads from a [`SerializationReader`]
+///
+/// the format isnt self-describing, so `deserialize_any` (ie untagged enums, `#[serde(flatten)]`) isnt supported
+pub struct SerdeReader<'r, 'de> {
+    sr: &'r mut SerializationReader<'de>,
+    options: &'r SerdeOptions,
+    /// name of the value being read, used for the error stack/trace
+    name: &'static str,
+}
+impl<'r, 'de> SerdeReader<'r, 'de> {
+    pub fn new(sr: &'r mut SerializationReader<'de>, options: &'r SerdeOptions) -> Self {
+        Self { sr, options, name: "value" }
+    }
+
+    fn read<R: Serializable>(&mut self) -> Result<R, SerdeError> {
+        Ok(self.sr.read(self.name)?)
+    }
+
+    fn read_variant(&mut self, enum_name: &'static str, variants: &'static [&'static str]) -> Result<(VariantKey, Option<usize>), SerdeError> {
+        let offset = self.sr.offset();
+        self.name = "variant id";
+
+        let Some(ids) = self.options.variant_ids(enum_name).copied() else {
+            let index = self.read::<u8>()?;
+            if index as usize >= variants.len() { return Err(self.sr.unknown_variant(enum_name, index, offset).into()) }
+            return Ok((VariantKey::Index(index as u32), None))
+        };
+
+        let id = match ids.id_size {
+            1 => self.read::<u8>()? as u64,
+            2 => self.read::<u16>()? as u64,
+            4 => self.read::<u32>()? as u64,
+            _ => self.read::<u64>()?,
+        };
+        let frame = if ids.framed { self.sr.begin_frame()? } else { None };
+
+        match ids.variant_of(id).or(ids.fallback) {
+            Some(variant) => Ok((VariantKey::Name(variant), frame)),
+            None => Err(self.sr.unknown_variant(enum_name, id, offset).into()),
+        }
+    }
+}
+
+impl<'de> de::Deserializer<'de> for &mut SerdeReader<'_, 'de> {
+    type Error = SerdeError;
+
+    fn deserialize_any<V: de::Visitor<'de>>(self, _visitor: V) -> Result<V::Value, SerdeError> {
+        Err(SerdeError::Custom("the tataku format isnt self-describing, the type must be known to read it".to_owned()))
+    }
+    fn deserialize_ignored_any<V: de::Visitor<'de>>(self, visitor: V) -> Result<V::Value, SerdeError> {
+        self.deserialize_any(visitor)
+    }
+
+    fn deserialize_bool<V: de::Visitor<'de>>(self, visitor: V) -> Result<V::Value, SerdeError> { visitor.visit_bool(self.read()?) }
+    fn deserialize_i8<V: de::Visitor<'de>>(self, visitor: V) -> Result<V::Value, SerdeError> { visitor.visit_i8(self.read()?) }
+    fn deserialize_i16<V: de::Visitor<'de>>(self, visitor: V) -> Result<V::Value, SerdeError> { visitor.visit_i16(self.read()?) }
+    fn deserialize_i32<V: de::Visitor<'de>>(self, visitor: V) -> Result<V::Value, SerdeError> { visitor.visit_i32(self.read()?) }
+    fn deserialize_i64<V: de::Visitor<'de>>(self, visitor: V) -> Result<V::Value, SerdeError> { visitor.visit_i64(self.read()?) }
+    fn deserialize_i128<V: de::Visitor<'de>>(self, visitor: V) -> Result<V::Value, SerdeError> { visitor.visit_i128(self.read()?) }
+    fn deserialize_u8<V: de::Visitor<'de>>(self, visitor: V) -> Result<V::Value, SerdeError> { visitor.visit_u8(self.read()?) }
+    fn deserialize_u16<V: de::Visitor<'de>>(self, visitor: V) -> Result<V::Value, SerdeError> { visitor.visit_u16(self.read()?) }
+    fn deserialize_u32<V: de::Visitor<'de>>(self, visitor: V) -> Result<V::Value, SerdeError> { visitor.visit_u32(self.read()?) }
+    fn deserialize_u64<V: de::Visitor<'de>>(self, visitor: V) -> Result<V::Value, SerdeError> { visitor.visit_u64(self.read()?) }
+    fn deserialize_u128<V: de::Visitor<'de>>(self, visitor: V) -> Result<V::Value, SerdeError> { visitor.visit_u128(self.read()?) }
+    fn deserialize_f32<V: de::Visitor<'de>>(self, visitor: V) -> Result<V::Value, SerdeError> { visitor.visit_f32(self.read()?) }
+    fn deserialize_f64<V: de::Visitor<'de>>(self, visitor: V) -> Result<V::Value, SerdeError> { visitor.visit_f64(self.read()?) }
+    fn deserialize_char<V: de::Visitor<'de>>(self, visitor: V) -> Result<V::Value, SerdeError> { visitor.visit_char(self.read()?) }
+
+    // borrow when we can
+    fn deserialize_str<V: de::Visitor<'de>>(self, visitor: V) -> Result<V::Value, SerdeError> {
+        if self.sr.is_borrowed() {
+            visitor.visit_borrowed_str(self.sr.read_borrowed(self.name)?)
+        } else {
+            visitor.visit_string(self.read()?)
+        }
+    }
+    fn deserialize_string<V: de::Visitor<'de>>(self, visitor: V) -> Result<V::Value, SerdeError> {
+        self.deserialize_str(visitor)
+    }
+    fn deserialize_bytes<V: de::Visitor<'de>>(self, visitor: V) -> Result<V::Value, SerdeError> {
+        if self.sr.is_borrowed() {
+            visitor.visit_borrowed_bytes(self.sr.read_borrowed(self.name)?)
+        } else {
+            visitor.visit_byte_buf(self.read()?)
+        }
+    }
+    fn deserialize_byte_buf<V: de::Visitor<'de>>(self, visitor: V) -> Result<V::Value, SerdeError> {
+        self.deserialize_bytes(visitor)
+    }
+
+    fn deserialize_option<V: de::Visitor<'de>>(self, visitor: V) -> Result<V::Value, SerdeError> {
+        if self.read()? {
+            visitor.visit_some(self)
+        } else {
+            visitor.visit_none()
+        }
+    }
+
+    fn deserialize_unit<V: de::Visitor<'de>>(self, visitor: V) -> Result<V::Value, SerdeError> {
+        visitor.visit_unit()
+    }
+    fn deserialize_unit_struct<V: de::Visitor<'de>>(self, _name: &'static str, visitor: V) -> Result<V::Value, SerdeError> {
+        visitor.visit_unit()
+    }
+    fn deserialize_newtype_struct<V: de::Visitor<'de>>(self, _name: &'static str, visitor: V) -> Result<V::Value, SerdeError> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_seq<V: de::Visitor<'de>>(self, visitor: V) -> Result<V::Value, SerdeError> {
+        let len = self.read()?;
+        self.sr.check_collection_length(len)?;
+        visitor.visit_seq(Elements { de: self, remaining: len, fields: None })
+    }
+    fn deserialize_map<V: de::Visitor<'de>>(self, visitor: V) -> Result<V::Value, SerdeError> {
+        let len = self.read()?;
+        self.sr.check_collection_length(len)?;
+        visitor.visit_map(Elements { de: self, remaining: len, fields: None })
+    }
+
+    fn deserialize_tuple<V: de::Visitor<'de>>(self, len: usize, visitor: V) -> Result<V::Value, SerdeError> {
+        visitor.visit_seq(Elements { de: self, remaining: len, fields: None })
+    }
+    fn deserialize_tuple_struct<V: de::Visitor<'de>>(self, _name: &'static str, len: usize, visitor: V) -> Result<V::Value, SerdeError> {
+        self.deserialize_tuple(len, visitor)
+    }
+    fn deserialize_struct<V: de::Visitor<'de>>(
+        self,
+        _name: &'static str,
+        fields: &'static [&'static str],
+        visitor: V
+    ) -> Result<V::Value, SerdeError> {
+        visitor.visit_seq(Elements { de: self, remaining: fields.len(), fields: Some(fields) })
+    }
+
+    fn deserialize_enum<V: de::Visitor<'de>>(
+        self,
+        name: &'static str,
+        variants: &'static [&'static str],
+        visitor: V
+    ) -> Result<V::Value, SerdeError> {
+        let (variant, frame) = self.read_variant(name, variants)?;
+        visitor.visit_enum(Variant { de: self, variant, frame })
+    }
+
+    fn deserialize_identifier<V: de::Visitor<'de>>(self, visitor: V) -> Result<V::Value, SerdeError> {
+        self.deserialize_str(visitor)
+    }
+
+    fn is_human_readable(&self) -> bool { false }
+}
+
+/// items of a seq/map, or fields of a tuple/struct
+struct Elements<'a, 'r, 'de> {
+    de: &'a mut SerdeReader<'r, 'de>,
+    remaining: usize,
+    fields: Option<&'static [&'static str]>,
+}
+impl<'a, 'r, 'de> Elements<'a, 'r, 'de> {
+    fn next<T: de::DeserializeSeed<'de>>(&mut self, seed: T, name: &'static str) -> Result<Option<T::Value>, SerdeError> {
+        if self.remaining == 0 { return Ok(None) }
+
+        self.de.name = match self.fields {
+            Some(fields) => fields[fields.len() - self.remaining],
+            None => name,
+        };
+        self.remaining -= 1;
+        seed.deserialize(&mut *self.de).map(Some)
+    }
+}
+impl<'de> de::SeqAccess<'de> for Elements<'_, '_, 'de> {
+    type Error = SerdeError;
+
+    fn next_element_seed<T: de::DeserializeSeed<'de>>(&mut self, seed: T) -> Result<Option<T::Value>, SerdeError> {
+        self.next(seed, "item")
+    }
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.de.sr.capacity_for::<u8>(self.remaining))
+    }
+}
+impl<'de> de::MapAccess<'de> for Elements<'_, '_, 'de> {
+    type Error = SerdeError;
+
+    fn next_key_seed<K: de::DeserializeSeed<'de>>(&mut self, seed: K) -> Result<Option<K::Value>, SerdeError> {
+        self.next(seed, "key")
+    }
+    fn next_value_seed<V: de::DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value, SerdeError> {
+        self.de.name = "value";
+        seed.deserialize(&mut *self.de)
+    }
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.de.sr.capacity_for::<u8>(self.remaining))
+    }
+}
+
+/// how a variant was identified
+enum VariantKey {
+    Name(&'static str),
+    Index(u32),
+}
+
+/// an enum variant which has had its id read
+struct Variant<'a, 'r, 'de> {
+    de: &'a mut SerdeReader<'r, 'de>,
+    variant: VariantKey,
+    frame: Option<usize>,
+}
+impl<'de> de::EnumAccess<'de> for Variant<'_, '_, 'de> {
+    type Error = SerdeError;
+    type Variant = Self;
+
+    fn variant_seed<V: de::DeserializeSeed<'de>>(self, seed: V) -> Result<(V::Value, Self), SerdeError> {
+        let value = match self.variant {
+            VariantKey::Name(name) => seed.deserialize(IntoDeserializer::<SerdeError>::into_deserializer(name))?,
+            VariantKey::Index(index) => seed.deserialize(IntoDeserializer::<SerdeError>::into_deserializer(index))?,
+        };
+        Ok((value, self))
+    }
+}
+impl<'de> de::VariantAccess<'de> for Variant<'_, '_, 'de> {
+    type Error = SerdeError;
+
+    // anything left in the frame (ie fields from a newer protocol) is skipped
+    fn unit_variant(self) -> Result<(), SerdeError> {
+        self.de.sr.end_frame(self.frame);
+        Ok(())
+    }
+    fn newtype_variant_seed<T: de::DeserializeSeed<'de>>(self, seed: T) -> Result<T::Value, SerdeError> {
+        let value = seed.deserialize(&mut *self.de)?;
+        self.de.sr.end_frame(self.frame);
+        Ok(value)
+    }
+    fn tuple_variant<V: de::Visitor<'de>>(self, len: usize, visitor: V) -> Result<V::Value, SerdeError> {
+        let value = de::Deserializer::deserialize_tuple(&mut *self.de, len, visitor)?;
+        self.de.sr.end_frame(self.frame);
+        Ok(value)
+    }
+    fn struct_variant<V: de::Visitor<'de>>(self, fields: &'static [&'static str], visitor: V) -> Result<V::Value, SerdeError> {
+        let value = de::Deserializer::deserialize_struct(&mut *self.de, "", fields, visitor)?;
+        self.de.sr.end_frame(self.frame);
+        Ok(value)
+    }
+}
+
+
+#[test]
+fn test_serde_bridge() {
+    use std::collections::{ HashMap, HashSet };
+    use crate::types::{ Score, Md5Hash };
+    use crate::types::replays::ReplayAction;
+    use crate::types::network::multiplayer::*;
+
+    fn check<T: Serializable + Serialize + for<'de> Deserialize<'de> + PartialEq>(value: T) {
+        let options = SerdeOptions::default();
+        let bytes = SimpleWriter::new().write::<T>(&value).done();
+        assert_eq!(options.to_bytes(&value).unwrap(), bytes, "{value:?}");
+        assert_eq!(options.from_bytes::<T>(&bytes).unwrap(), value);
+    }
+
+    check(LobbyInfo {
+        id: 5,
+        name: "lobby".to_owned(),
+        has_password: true,
+        host: 1,
+        players: vec![1, 2, 3],
+        current_beatmap: Some("map".to_owned()),
+        state: LobbyState::Playing,
+    });
+    check(FullLobbyInfo {
+        id: 5,
+        name: "lobby".to_owned(),
+        host: 1,
+        state: LobbyState::Idle,
+        players: vec![LobbyUser { user_id: 1, state: LobbyUserState::InGame, mods: HashSet::from(["easy".to_owned()]), speed: 100 }],
+        slots: HashMap::from([(0, LobbySlot::Filled { user: 1 }), (1, LobbySlot::Locked), (2, LobbySlot::Empty)]),
+        current_beatmap: None,
+    });
+    check(LobbyUser { user_id: 2, state: LobbyUserState::Ready, mods: HashSet::new(), speed: 150 });
+    check(vec![LobbyState::Unknown, LobbyState::Idle]);
+    check((String::new(), Some(5u8), None::<u64>, 'a', [1.5f32; 3]));
+
+    // hand-written impls which use the variant index
+    let action = ReplayAction::MousePos(1.0, 2.5);
+    let bytes = SimpleWriter::new().write::<ReplayAction>(action).done();
+    assert_eq!(SerdeOptions::default().to_bytes(&action).unwrap(), bytes);
+    assert!(matches!(SerdeOptions::default().from_bytes(&bytes), Ok(ReplayAction::MousePos(1.0, 2.5))));
+
+    // versioned types (ie scores, mod definitions) write a version number which serde doesnt know about,
+    // and the score's serde impl is used for json (speed is an f32, hit timings are included),
+    // so these only match their own bytes
+    let mut score = Score::new(Md5Hash::default(), "user".to_owned(), "osu".to_owned());
+    score.judgments.insert("x300".to_owned(), 5);
+    score.hit_timings = vec![1.0, -2.0];
+    let bytes = SerdeOptions::default().to_bytes(&score).unwrap();
+    let read = SerdeOptions::default().from_bytes::<Score>(&bytes).unwrap();
+    assert_eq!(SerdeOptions::default().to_bytes(&read).unwrap(), bytes);
+
+    // unknown ids fall back to the default variant, unless the enum is unregistered
+    let data = SimpleWriter::new().write::<u8>(200).done();
+    assert_eq!(SerdeOptions::default().from_bytes::<LobbyState>(&data).unwrap(), LobbyState::Unknown);
+    assert!(SerdeOptions::empty().from_bytes::<LobbyState>(&data).is_err());
+
+    // canonical maps are sorted the same way
+    let map = (0..20u32).map(|n| (n.to_string(), n)).collect::<HashMap<_, _>>();
+    let mut sw = SerializationWriter::new().canonical();
+    SerdeOptions::default().write(&mut sw, &map).unwrap();
+    assert_eq!(sw.data(), SimpleWriter::new().canonical().write::<HashMap<String, u32>>(&map).done());
+
+    // values from the serde bridge can be used as fields
+    let value = Serde(HashMap::from([(1u8, vec!["a".to_owned()])]));
+    let bytes = SimpleWriter::new().write::<Serde<_>>(&value).done();
+    assert_eq!(bytes, SimpleWriter::new().write::<HashMap<u8, Vec<String>>>(&value.0).done());
+    assert_eq!(SerializationReader::new(bytes).read::<Serde<HashMap<u8, Vec<String>>>>("value").unwrap(), value);
+}
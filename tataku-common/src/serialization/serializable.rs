@@ -1,9 +1,15 @@
 use std::{
     rc::Rc,
     sync::Arc, 
+    borrow::Cow,
     convert::TryInto, 
     collections::HashSet,
     collections::HashMap,
+    collections::BTreeSet,
+    collections::BTreeMap,
+    collections::VecDeque,
+    time::{ Duration, SystemTime },
+    net::{ IpAddr, Ipv4Addr, Ipv6Addr },
 };
 
 use crate::serialization::*;
@@ -11,19 +17,44 @@ use crate::serialization::*;
 pub trait Serializable: core::fmt::Debug {
     fn read(sr: &mut SerializationReader) -> SerializationResult<Self> where Self: Sized;
     fn write(&self, sw: &mut SerializationWriter);
+
+    /// how many bytes [`Self::write`] would write, with `sw`'s settings (ie to size a buffer up front)
+    ///
+    /// this writes to a scratch buffer by default, so types which can add it up themselves should
+    fn serialized_len(&self, sw: &SerializationWriter) -> usize {
+        sw.measure(|sw| self.write(sw))
+    }
+}
+
+/// a type which writes its version first, and can also be written as an older version.
+/// 
+/// implemented by the [`Serializable`](crate::macros::Serializable) derive when it has a `current_version`
+pub trait VersionedSerializable: Serializable {
+    const CURRENT_VERSION: u16;
+
+    /// write this as an older version (ie for older game builds)
+    /// 
+    /// returns the names of the fields which couldnt be written in that version.
+    /// panics if `version` is newer than [`Self::CURRENT_VERSION`]
+    fn write_versioned(&self, sw: &mut SerializationWriter, version: u16) -> Vec<&'static str>;
 }
 impl Serializable for String {
     fn read(sr: &mut SerializationReader) -> SerializationResult<Self> {
         let len = usize::read(sr)?;
+        sr.check_string_length(len)?;
         let bytes = sr.read_slice(len)?.to_vec();
         Ok(String::from_utf8(bytes)?)
     }
 
     fn write(&self, sw: &mut SerializationWriter) {
         let bytes = self.as_bytes();
-        sw.write(&(bytes.len() as u64));
+        sw.write_length(bytes.len());
         sw.write_raw_bytes(bytes);
     }
+
+    fn serialized_len(&self, sw: &SerializationWriter) -> usize {
+        sw.length_size(self.len()) + self.len()
+    }
 }
 
 
@@ -38,40 +69,198 @@ macro_rules! impl_for_num {
             fn write(&self, sw: &mut SerializationWriter) {
                 sw.data.extend(self.to_le_bytes())
             }
+
+            fn serialized_len(&self, _sw: &SerializationWriter) -> usize {
+                std::mem::size_of::<$t>()
+            }
+        } )+
+    }
+}
+impl_for_num![u8, i8, half::f16, f32, f64];
+
+// these are varints if the encoding says so
+macro_rules! impl_for_unsigned {
+    ($($t:ty),+) => { $(
+        impl Serializable for $t {
+            fn read(sr: &mut SerializationReader) -> SerializationResult<Self> {
+                if !sr.encoding().varint_integers {
+                    let bytes = sr.read_slice(std::mem::size_of::<$t>())?;
+                    return Ok(Self::from_le_bytes(bytes.try_into().unwrap()))
+                }
+
+                // read_varint already checks the size
+                sr.read_varint(stringify!($t), <$t>::BITS).map(|n| n as $t)
+            }
+
+            fn write(&self, sw: &mut SerializationWriter) {
+                if sw.encoding().varint_integers {
+                    sw.write_varint(*self as u128)
+                } else {
+                    sw.data.extend(self.to_le_bytes())
+                }
+            }
+
+            fn serialized_len(&self, sw: &SerializationWriter) -> usize {
+                if sw.encoding().varint_integers { varint_len(*self as u128) } else { std::mem::size_of::<$t>() }
+            }
+        } )+
+    }
+}
+impl_for_unsigned![u16, u32, u64, u128];
+
+macro_rules! impl_for_signed {
+    ($($t:ty),+) => { $(
+        impl Serializable for $t {
+            fn read(sr: &mut SerializationReader) -> SerializationResult<Self> {
+                if !sr.encoding().varint_integers {
+                    let bytes = sr.read_slice(std::mem::size_of::<$t>())?;
+                    return Ok(Self::from_le_bytes(bytes.try_into().unwrap()))
+                }
+
+                // zigzag uses the same number of bits, so read_varint checks the size here too
+                sr.read_varint(stringify!($t), <$t>::BITS).map(|n| zigzag_decode(n) as $t)
+            }
+
+            fn write(&self, sw: &mut SerializationWriter) {
+                if sw.encoding().varint_integers {
+                    sw.write_varint(zigzag_encode(*self as i128))
+                } else {
+                    sw.data.extend(self.to_le_bytes())
+                }
+            }
+
+            fn serialized_len(&self, sw: &SerializationWriter) -> usize {
+                if sw.encoding().varint_integers { varint_len(zigzag_encode(*self as i128)) } else { std::mem::size_of::<$t>() }
+            }
         } )+
     }
 }
-impl_for_num![u8, i8, u16, i16, u32, i32, u64, i64, u128, i128, f32, f64];
+impl_for_signed![i16, i32, i64, i128];
 
-// usize is read as a u64
+// usize is a length, so its read as a u64 (or a varint)
 impl Serializable for usize {
     fn read(sr: &mut SerializationReader) -> SerializationResult<Self> {
-        u64::read(sr).map(|n| n as usize)
+        sr.read_length()
     }
 
     fn write(&self, sw: &mut SerializationWriter) {
-        (*self as u64).write(sw)
+        sw.write_length(*self)
+    }
+
+    fn serialized_len(&self, sw: &SerializationWriter) -> usize {
+        sw.length_size(*self)
     }
 }
 impl Serializable for bool {
     fn read(sr: &mut SerializationReader) -> SerializationResult<Self> {
-        Ok(u8::read(sr)? & 1 == 1)
+        let offset = sr.offset();
+        match u8::read(sr)? {
+            0 => Ok(false),
+            1 => Ok(true),
+            n => Err(sr.invalid_value("bool", format!("expected 0 or 1, got {n}"), offset)),
+        }
     }
 
     fn write(&self, sw: &mut SerializationWriter) {
         sw.write::<u8>(&if *self {1} else {0});
     }
+
+    fn serialized_len(&self, _sw: &SerializationWriter) -> usize { 1 }
+}
+
+// chars are written as their u32 value
+impl Serializable for char {
+    fn read(sr: &mut SerializationReader) -> SerializationResult<Self> {
+        let offset = sr.offset();
+        let n = u32::read(sr)?;
+        char::from_u32(n).ok_or_else(|| sr.invalid_value("char", format!("{n:#x} is not a unicode scalar value"), offset))
+    }
+
+    fn write(&self, sw: &mut SerializationWriter) {
+        sw.write(&(*self as u32));
+    }
+
+    fn serialized_len(&self, sw: &SerializationWriter) -> usize {
+        (*self as u32).serialized_len(sw)
+    }
+}
+
+// unit is written as nothing
+impl Serializable for () {
+    fn read(_sr: &mut SerializationReader) -> SerializationResult<Self> { Ok(()) }
+    fn write(&self, _sw: &mut SerializationWriter) {}
+    fn serialized_len(&self, _sw: &SerializationWriter) -> usize { 0 }
+}
+
+// written the same as a String, always read as owned
+impl Serializable for Cow<'_, str> {
+    fn read(sr: &mut SerializationReader) -> SerializationResult<Self> {
+        String::read(sr).map(Cow::Owned)
+    }
+
+    fn write(&self, sw: &mut SerializationWriter) {
+        let bytes = self.as_bytes();
+        sw.write_length(bytes.len());
+        sw.write_raw_bytes(bytes);
+    }
+
+    fn serialized_len(&self, sw: &SerializationWriter) -> usize {
+        sw.length_size(self.len()) + self.len()
+    }
 }
 
 // serialization for tuples
-impl<T:Serializable, T2:Serializable> Serializable for (T, T2) {
+macro_rules! impl_for_tuple {
+    ($($n:literal $t:ident),+) => {
+        impl<$($t:Serializable),+> Serializable for ($($t,)+) {
+            fn read(sr: &mut SerializationReader) -> SerializationResult<Self> {
+                Ok(($(sr.read::<$t>(ItemName("tuple item", $n))?,)+))
+            }
+
+            #[allow(non_snake_case)]
+            fn write(&self, sw: &mut SerializationWriter) {
+                let ($($t,)+) = self;
+                $( sw.write($t); )+
+            }
+
+            #[allow(non_snake_case)]
+            fn serialized_len(&self, sw: &SerializationWriter) -> usize {
+                let ($($t,)+) = self;
+                0 $( + $t.serialized_len(sw) )+
+            }
+        }
+    };
+}
+impl_for_tuple!(0 T1);
+impl_for_tuple!(0 T1, 1 T2);
+impl_for_tuple!(0 T1, 1 T2, 2 T3);
+impl_for_tuple!(0 T1, 1 T2, 2 T3, 3 T4);
+impl_for_tuple!(0 T1, 1 T2, 2 T3, 3 T4, 4 T5);
+impl_for_tuple!(0 T1, 1 T2, 2 T3, 3 T4, 4 T5, 5 T6);
+impl_for_tuple!(0 T1, 1 T2, 2 T3, 3 T4, 4 T5, 5 T6, 6 T7);
+impl_for_tuple!(0 T1, 1 T2, 2 T3, 3 T4, 4 T5, 5 T6, 6 T7, 7 T8);
+impl_for_tuple!(0 T1, 1 T2, 2 T3, 3 T4, 4 T5, 5 T6, 6 T7, 7 T8, 8 T9);
+impl_for_tuple!(0 T1, 1 T2, 2 T3, 3 T4, 4 T5, 5 T6, 6 T7, 7 T8, 8 T9, 9 T10);
+impl_for_tuple!(0 T1, 1 T2, 2 T3, 3 T4, 4 T5, 5 T6, 6 T7, 7 T8, 8 T9, 9 T10, 10 T11);
+impl_for_tuple!(0 T1, 1 T2, 2 T3, 3 T4, 4 T5, 5 T6, 6 T7, 7 T8, 8 T9, 9 T10, 10 T11, 11 T12);
+
+// fixed size arrays dont need their length written
+impl<T:Serializable, const N: usize> Serializable for [T; N] {
     fn read(sr: &mut SerializationReader) -> SerializationResult<Self> {
-        Ok((T::read(sr)?, T2::read(sr)?))
+        let mut out = Vec::with_capacity(sr.capacity_for::<T>(N));
+        for n in 0..N { out.push(sr.read(ItemName("array item", n))?) }
+        // we just read exactly N items
+        Ok(out.try_into().unwrap_or_else(|_| unreachable!()))
     }
 
     fn write(&self, sw: &mut SerializationWriter) {
-        sw.write(&self.0);
-        sw.write(&self.1);
+        for i in self.iter() {
+            sw.write(i)
+        }
+    }
+
+    fn serialized_len(&self, sw: &SerializationWriter) -> usize {
+        self.iter().map(|i| i.serialized_len(sw)).sum()
     }
 }
 
@@ -85,12 +274,17 @@ impl<T:Serializable> Serializable for &Vec<T> {
             sw.write(i)
         }
     }
+
+    fn serialized_len(&self, sw: &SerializationWriter) -> usize {
+        (*self).serialized_len(sw)
+    }
 }
 impl<T:Serializable> Serializable for Vec<T> {
     fn read(sr: &mut SerializationReader) -> SerializationResult<Self> {
         let count = usize::read(sr)?; //sr.read_u64("Vec len")?;
-        let mut out:Vec<T> = Vec::with_capacity(count);
-        for n in 0..count { out.push(sr.read(format!("Vec item #{n}"))?) }
+        sr.check_collection_length(count)?;
+        let mut out:Vec<T> = Vec::with_capacity(sr.capacity_for::<T>(count));
+        for n in 0..count { out.push(sr.read(ItemName("Vec item", n))?) }
         Ok(out)
     }
 
@@ -101,8 +295,33 @@ impl<T:Serializable> Serializable for Vec<T> {
             sw.write(i)
         }
     }
+
+    fn serialized_len(&self, sw: &SerializationWriter) -> usize {
+        sw.length_size(self.len()) + self.iter().map(|i| i.serialized_len(sw)).sum::<usize>()
+    }
 }   
 
+impl<T:Serializable> Serializable for VecDeque<T> {
+    fn read(sr: &mut SerializationReader) -> SerializationResult<Self> {
+        let count = usize::read(sr)?;
+        sr.check_collection_length(count)?;
+        let mut out = VecDeque::with_capacity(sr.capacity_for::<T>(count));
+        for n in 0..count { out.push_back(sr.read(ItemName("VecDeque item", n))?) }
+        Ok(out)
+    }
+
+    fn write(&self, sw: &mut SerializationWriter) {
+        sw.write(&self.len());
+        for i in self.iter() {
+            sw.write(i)
+        }
+    }
+
+    fn serialized_len(&self, sw: &SerializationWriter) -> usize {
+        sw.length_size(self.len()) + self.iter().map(|i| i.serialized_len(sw)).sum::<usize>()
+    }
+}
+
 // serialization for options
 impl<T:Serializable> Serializable for Option<T> {
     fn read(sr: &mut SerializationReader) -> SerializationResult<Self> {
@@ -117,17 +336,22 @@ impl<T:Serializable> Serializable for Option<T> {
         sw.write(&self.is_some());
         if let Some(t) = self { sw.write(t) }
     }
+
+    fn serialized_len(&self, sw: &SerializationWriter) -> usize {
+        1 + self.as_ref().map_or(0, |t| t.serialized_len(sw))
+    }
 }
 
 // serialization for hashmap and hashsedt
 impl<A:Serializable+core::hash::Hash+Eq, B:Serializable> Serializable for HashMap<A, B> {
     fn read(sr: &mut SerializationReader) -> SerializationResult<Self> {
-        let count = u64::read(sr)?;
+        let count = usize::read(sr)?;
+        sr.check_collection_length(count)?;
 
-        let mut hashmap = HashMap::new();
-        for _ in 0..count {
-            let key = A::read(sr)?; // sr.read(format!("HashMap key #{n}"))?;
-            let val = B::read(sr)?; // sr.read(format!("HashMap value #{n}"))?;
+        let mut hashmap = HashMap::with_capacity(sr.capacity_for::<(A, B)>(count));
+        for n in 0..count {
+            let key = sr.read(ItemName("HashMap key", n))?;
+            let val = sr.read(ItemName("HashMap value", n))?;
             hashmap.insert(key, val);
         }
 
@@ -135,31 +359,228 @@ impl<A:Serializable+core::hash::Hash+Eq, B:Serializable> Serializable for HashMa
     }
 
     fn write(&self, sw: &mut SerializationWriter) {
-        sw.write(&self.len());
+        sw.write_map(self.iter());
+    }
 
-        for (key, val) in self {
-            sw.write(key);
-            sw.write(val);
-        }
+    fn serialized_len(&self, sw: &SerializationWriter) -> usize {
+        sw.map_len(self.iter())
     }
 }
 
 impl<T:Serializable+core::hash::Hash+Eq> Serializable for HashSet<T> {
     fn read(sr: &mut SerializationReader) -> SerializationResult<Self> {
-        let count = u64::read(sr)?; 
-        let mut out: HashSet<T> = HashSet::new();
-        for n in 0..count { out.insert(sr.read(format!("HashSet value #{n}"))?); }
+        let count = usize::read(sr)?; 
+        sr.check_collection_length(count)?;
+        let mut out: HashSet<T> = HashSet::with_capacity(sr.capacity_for::<T>(count));
+        for n in 0..count { out.insert(sr.read(ItemName("HashSet value", n))?); }
         Ok(out)
     }
 
     fn write(&self, sw: &mut SerializationWriter) {
-        sw.write(&(self.len() as u64));
-        for i in self.iter() {
-            sw.write(i)
-        }
+        sw.write_set(self.iter());
+    }
+
+    fn serialized_len(&self, sw: &SerializationWriter) -> usize {
+        sw.set_len(self.iter())
     }
 }   
 
+// serialization for btreemap and btreeset
+impl<A:Serializable+Ord, B:Serializable> Serializable for BTreeMap<A, B> {
+    fn read(sr: &mut SerializationReader) -> SerializationResult<Self> {
+        let count = usize::read(sr)?;
+        sr.check_collection_length(count)?;
+
+        let mut map = BTreeMap::new();
+        for n in 0..count {
+            let key = sr.read(ItemName("BTreeMap key", n))?;
+            let val = sr.read(ItemName("BTreeMap value", n))?;
+            map.insert(key, val);
+        }
+
+        Ok(map)
+    }
+
+    // already sorted, but not necessarily by the encoded keys
+    fn write(&self, sw: &mut SerializationWriter) {
+        sw.write_map(self.iter());
+    }
+
+    fn serialized_len(&self, sw: &SerializationWriter) -> usize {
+        sw.map_len(self.iter())
+    }
+}
+
+impl<T:Serializable+Ord> Serializable for BTreeSet<T> {
+    fn read(sr: &mut SerializationReader) -> SerializationResult<Self> {
+        let count = usize::read(sr)?; 
+        sr.check_collection_length(count)?;
+        let mut out = BTreeSet::new();
+        for n in 0..count { out.insert(sr.read(ItemName("BTreeSet value", n))?); }
+        Ok(out)
+    }
+
+    fn write(&self, sw: &mut SerializationWriter) {
+        sw.write_set(self.iter());
+    }
+
+    fn serialized_len(&self, sw: &SerializationWriter) -> usize {
+        sw.set_len(self.iter())
+    }
+}
+
+// serialization for results
+impl<T:Serializable, E:Serializable> Serializable for Result<T, E> {
+    fn read(sr: &mut SerializationReader) -> SerializationResult<Self> {
+        let offset = sr.offset();
+        match u8::read(sr)? {
+            0 => Ok(Ok(sr.read("Ok")?)),
+            1 => Ok(Err(sr.read("Err")?)),
+            id => Err(sr.unknown_variant("Result", id, offset)),
+        }
+    }
+
+    fn write(&self, sw: &mut SerializationWriter) {
+        match self {
+            Ok(t) => {
+                sw.write::<u8>(&0);
+                sw.write(t);
+            }
+            Err(e) => {
+                sw.write::<u8>(&1);
+                sw.write(e);
+            }
+        }
+    }
+
+    fn serialized_len(&self, sw: &SerializationWriter) -> usize {
+        1 + match self {
+            Ok(t) => t.serialized_len(sw),
+            Err(e) => e.serialized_len(sw),
+        }
+    }
+}
+
+
+// serialization for time
+const NANOS_PER_SEC: u32 = 1_000_000_000;
+
+impl Serializable for Duration {
+    fn read(sr: &mut SerializationReader) -> SerializationResult<Self> {
+        let offset = sr.offset();
+        let secs = u64::read(sr)?;
+        let nanos = u32::read(sr)?;
+        if nanos >= NANOS_PER_SEC {
+            return Err(sr.invalid_value("Duration", format!("{nanos} nanoseconds is more than a second"), offset))
+        }
+
+        Ok(Duration::new(secs, nanos))
+    }
+
+    fn write(&self, sw: &mut SerializationWriter) {
+        sw.write(&self.as_secs());
+        sw.write(&self.subsec_nanos());
+    }
+
+    fn serialized_len(&self, sw: &SerializationWriter) -> usize {
+        self.as_secs().serialized_len(sw) + self.subsec_nanos().serialized_len(sw)
+    }
+}
+
+/// written as the time since the unix epoch, as (seconds (i64), nanoseconds (u32)).
+/// 
+/// times before the epoch have negative seconds, with the nanoseconds still counting forward
+impl Serializable for SystemTime {
+    fn read(sr: &mut SerializationReader) -> SerializationResult<Self> {
+        let offset = sr.offset();
+        let secs = i64::read(sr)?;
+        let nanos = u32::read(sr)?;
+        if nanos >= NANOS_PER_SEC {
+            return Err(sr.invalid_value("SystemTime", format!("{nanos} nanoseconds is more than a second"), offset))
+        }
+
+        let time = if secs >= 0 {
+            SystemTime::UNIX_EPOCH.checked_add(Duration::from_secs(secs as u64))
+        } else {
+            SystemTime::UNIX_EPOCH.checked_sub(Duration::from_secs(secs.unsigned_abs()))
+        };
+
+        time.and_then(|t| t.checked_add(Duration::from_nanos(nanos as u64)))
+            .ok_or_else(|| sr.invalid_value("SystemTime", "time is out of range", offset))
+    }
+
+    fn write(&self, sw: &mut SerializationWriter) {
+        let (secs, nanos) = match self.duration_since(SystemTime::UNIX_EPOCH) {
+            Ok(since) => (since.as_secs() as i64, since.subsec_nanos()),
+            Err(e) => {
+                let before = e.duration();
+                match before.subsec_nanos() {
+                    0 => (-(before.as_secs() as i64), 0),
+                    n => (-(before.as_secs() as i64) - 1, NANOS_PER_SEC - n),
+                }
+            }
+        };
+
+        sw.write(&secs);
+        sw.write(&nanos);
+    }
+}
+
+
+// serialization for ip addresses
+impl Serializable for Ipv4Addr {
+    fn read(sr: &mut SerializationReader) -> SerializationResult<Self> {
+        Ok(Self::from(<[u8; 4]>::read(sr)?))
+    }
+
+    fn write(&self, sw: &mut SerializationWriter) {
+        sw.write(&self.octets());
+    }
+
+    fn serialized_len(&self, _sw: &SerializationWriter) -> usize { 4 }
+}
+impl Serializable for Ipv6Addr {
+    fn read(sr: &mut SerializationReader) -> SerializationResult<Self> {
+        Ok(Self::from(<[u8; 16]>::read(sr)?))
+    }
+
+    fn write(&self, sw: &mut SerializationWriter) {
+        sw.write(&self.octets());
+    }
+
+    fn serialized_len(&self, _sw: &SerializationWriter) -> usize { 16 }
+}
+impl Serializable for IpAddr {
+    fn read(sr: &mut SerializationReader) -> SerializationResult<Self> {
+        let offset = sr.offset();
+        match u8::read(sr)? {
+            0 => Ok(IpAddr::V4(sr.read("V4")?)),
+            1 => Ok(IpAddr::V6(sr.read("V6")?)),
+            id => Err(sr.unknown_variant("IpAddr", id, offset)),
+        }
+    }
+
+    fn write(&self, sw: &mut SerializationWriter) {
+        match self {
+            IpAddr::V4(ip) => {
+                sw.write::<u8>(&0);
+                sw.write(ip);
+            }
+            IpAddr::V6(ip) => {
+                sw.write::<u8>(&1);
+                sw.write(ip);
+            }
+        }
+    }
+
+    fn serialized_len(&self, _sw: &SerializationWriter) -> usize {
+        match self {
+            IpAddr::V4(_) => 1 + 4,
+            IpAddr::V6(_) => 1 + 16,
+        }
+    }
+}
+
 
 // implement for wrapper types
 macro_rules! impl_wrapper {
@@ -172,6 +593,10 @@ macro_rules! impl_wrapper {
             fn write(&self, sw: &mut SerializationWriter) {
                 self.as_ref().write(sw)
             }
+
+            fn serialized_len(&self, sw: &SerializationWriter) -> usize {
+                self.as_ref().serialized_len(sw)
+            }
         })+
     };
 }
@@ -188,7 +613,7 @@ pub struct StackData {
 
 #[allow(unused_imports)]
 mod test {
-    use super::{ SerializationReader, SerializationWriter };
+    use super::*;
 
     #[test]
     fn writer_test() {
@@ -199,4 +624,145 @@ mod test {
         let line = writer.data().iter().map(|b|format!("{:#x}", b)).collect::<Vec<String>>().join(", ");
         println!("{}", line);
     }
+
+    #[test]
+    fn std_types_test() {
+        fn roundtrip<T: Serializable + PartialEq>(value: T) {
+            let data = SimpleWriter::new().write::<T>(&value).done();
+            let mut reader = SerializationReader::new(data);
+            let read = reader.read::<T>("value").unwrap();
+            assert_eq!(read, value);
+            assert!(!reader.can_read(), "{value:?} left data unread");
+        }
+
+        roundtrip(BTreeMap::from([(1u8, "a".to_owned()), (2, "b".to_owned())]));
+        roundtrip(BTreeSet::from([5u32, 10]));
+        roundtrip(VecDeque::from([1i16, -1]));
+        roundtrip([1u8, 2, 3, 4]);
+        roundtrip((1u8, 2u16, 3u32, 4u64, 5i8, 6i16, 7i32, 8i64, 9.0f32, 10.0f64, true, 'c'));
+        roundtrip('🥁');
+        roundtrip(());
+        roundtrip(Cow::<str>::Borrowed("drum"));
+        roundtrip(half::f16::from_f32(1.5));
+        roundtrip(Duration::new(5, 10));
+        roundtrip(SystemTime::UNIX_EPOCH + Duration::new(1_700_000_000, 5));
+        roundtrip(SystemTime::UNIX_EPOCH - Duration::new(100, 5));
+        roundtrip(IpAddr::V4(Ipv4Addr::LOCALHOST));
+        roundtrip(IpAddr::V6(Ipv6Addr::LOCALHOST));
+        roundtrip(Ok::<u8, String>(1));
+        roundtrip(Err::<u8, String>("error".to_owned()));
+
+        // unit takes up no space
+        assert!(SimpleWriter::new().write::<()>(()).done().is_empty());
+        // arrays dont write their length
+        assert_eq!(SimpleWriter::new().write::<[u16; 2]>([1, 2]).done(), [1, 0, 2, 0]);
+
+        // tuple items are named like array items
+        let mut reader = SerializationReader::new(SimpleWriter::new().write::<(u8, String)>((1, "a".to_owned())).done()).trace();
+        reader.read::<(u8, String)>("value").unwrap();
+        assert!(reader.trace_entries().iter().any(|e| e.path == "value[1]"), "{:?}", reader.trace_entries());
+    }
+
+    #[test]
+    fn canonical_test() {
+        use crate::types::{ Score, Md5Hash };
+
+        // insert the same entries in different orders, so the maps iterate differently
+        let keys = (0..50).map(|n| format!("key {n}")).collect::<Vec<_>>();
+        let forward = keys.iter().map(|k| (k.clone(), k.len() as u16)).collect::<HashMap<_, _>>();
+        let mut backward = HashMap::new();
+        keys.iter().rev().for_each(|k| { backward.insert(k.clone(), k.len() as u16); });
+
+        let write = |map: &HashMap<String, u16>| SimpleWriter::new().canonical().write::<HashMap<String, u16>>(map).done();
+        assert_eq!(write(&forward), write(&backward));
+        // still reads the same
+        assert_eq!(SerializationReader::new(write(&forward)).read::<HashMap<String, u16>>("map").unwrap(), forward);
+
+        // sorted by the encoded key, not by the key itself
+        let set = BTreeSet::from([1u16, 256]);
+        assert_eq!(SimpleWriter::new().canonical().write::<BTreeSet<u16>>(&set).done()[8..], [0, 1, 1, 0]);
+        
+        let mut a = Score::new(Md5Hash::default(), "user".to_owned(), "osu".to_owned());
+        let mut b = a.clone();
+        a.judgments = forward.clone();
+        b.judgments = backward.clone();
+        a.stat_data.insert("a".to_owned(), vec![1.0]);
+        a.stat_data.insert("b".to_owned(), vec![2.0]);
+        b.stat_data.insert("b".to_owned(), vec![2.0]);
+        b.stat_data.insert("a".to_owned(), vec![1.0]);
+        assert_eq!(
+            SimpleWriter::new().canonical().write::<Score>(&a).done(), 
+            SimpleWriter::new().canonical().write::<Score>(&b).done()
+        );
+    }
+
+    #[test]
+    fn derive_test() {
+        use crate::macros::Serializable;
+
+        #[derive(Serializable, Debug, PartialEq)]
+        struct Tuple(u8, #[serialize(name = "text")] String);
+        #[derive(Serializable, Debug, PartialEq)]
+        struct Unit;
+        #[derive(Serializable, Debug, PartialEq)]
+        struct Wrapper<T> { value: T, count: u16 }
+
+        #[derive(Serializable, Debug, PartialEq)]
+        enum Implicit { A, B(u8), C { x: i16 } }
+        #[derive(Serializable, Debug, PartialEq)]
+        #[repr(u16)]
+        enum Explicit {
+            A = 5,
+            B,
+            #[serialize(fallback)]
+            Unknown = 300,
+        }
+
+        /// written as a string
+        mod as_string {
+            use crate::serialization::*;
+            pub fn read(sr: &mut SerializationReader) -> SerializationResult<u32> {
+                let offset = sr.offset();
+                let s = sr.read::<String>("value")?;
+                s.parse().map_err(|_| sr.invalid_value("u32", "not a number", offset))
+            }
+            pub fn write(value: &u32, sw: &mut SerializationWriter) { sw.write(&value.to_string()) }
+            pub fn schema() -> TypeSchema { TypeSchema::primitive("NumberString", "a number as a String") }
+        }
+        #[derive(Serializable, Debug, PartialEq)]
+        struct With { #[serialize(with = as_string)] n: u32 }
+        #[derive(Serializable, Debug, PartialEq)]
+        enum WithEnum { A(#[serialize(with = as_string)] u32, #[serialize(skip)] u8) }
+
+        fn roundtrip<T: Serializable + PartialEq>(value: T, expected: &[u8]) {
+            let data = SimpleWriter::new().write::<T>(&value).done();
+            assert_eq!(data, expected, "{value:?}");
+            assert_eq!(SerializationWriter::new().serialized_len(&value), data.len());
+            assert_eq!(SerializationReader::new(data).read::<T>("value"), Ok(value));
+        }
+
+        roundtrip(Tuple(1, "a".to_owned()), &[1, 1, 0, 0, 0, 0, 0, 0, 0, b'a']);
+        roundtrip(Unit, &[]);
+        roundtrip(Wrapper { value: 1u8, count: 2 }, &[1, 2, 0]);
+        roundtrip(Implicit::A, &[0]);
+        roundtrip(Implicit::B(3), &[1, 3]);
+        roundtrip(Implicit::C { x: -1 }, &[2, 0xff, 0xff]);
+        roundtrip(Explicit::A, &[5, 0]);
+        roundtrip(Explicit::B, &[6, 0]);
+        roundtrip(With { n: 12 }, &[2, 0, 0, 0, 0, 0, 0, 0, b'1', b'2']);
+        roundtrip(WithEnum::A(1, 0), &[0, 1, 0, 0, 0, 0, 0, 0, 0, b'1']);
+
+        // unknown ids
+        assert_eq!(SerializationReader::new(vec![7, 0]).read::<Explicit>("value"), Ok(Explicit::Unknown));
+        let error = SerializationReader::new(vec![3]).read::<Implicit>("value").unwrap_err();
+        assert!(matches!(error.inner, SerializationErrorEnum::UnknownVariant { type_name: "Implicit", id: 3, .. }), "{error:?}");
+
+        // schemas
+        assert_eq!(<Wrapper<u8> as WireSchema>::schema().name, "Wrapper<u8>");
+        let SchemaKind::Struct { fields, .. } = Tuple::schema().kind else { panic!() };
+        assert_eq!(fields.iter().map(|f| f.name).collect::<Vec<_>>(), ["field0", "text"]);
+        let SchemaKind::Enum { id_type: "u16", strict: false, fallback: Some("Unknown"), .. } = Explicit::schema().kind else { panic!() };
+        let SchemaKind::Struct { fields, .. } = With::schema().kind else { panic!() };
+        assert_eq!((fields[0].ty)().name, "NumberString");
+    }
 }
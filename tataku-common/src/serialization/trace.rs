@@ -0,0 +1,133 @@
+use std::fmt::Write;
+
+/// how many bytes are shown per hexdump row
+const ROW_SIZE: usize = 16;
+/// values longer than this are cut off in the hexdump
+const MAX_VALUE_LEN: usize = 64;
+
+/// a value which was read by a tracing [`SerializationReader`](crate::serialization::SerializationReader)
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TraceEntry {
+    /// where the value is, ie `score.mods[2].name`
+    pub path: String,
+    pub type_name: &'static str,
+    /// offset of the first byte of the value
+    pub start: usize,
+    /// offset after the last byte of the value
+    pub end: usize,
+    /// the decoded value (debug formatted), or the error if it failed to read
+    pub value: String,
+}
+
+/// render `data` as a hexdump, with each entry listed under the row it starts in.
+///
+/// entries are indented by how deeply they're nested inside other entries
+pub fn render_hexdump(data: &[u8], trace: &[TraceEntry]) -> String {
+    let mut entries = trace.iter().collect::<Vec<_>>();
+    entries.sort_by_key(|e| (e.start, std::cmp::Reverse(e.end)));
+
+    // figure out how deeply nested each entry is
+    let mut open_ends: Vec<usize> = Vec::new();
+    let mut depths = Vec::with_capacity(entries.len());
+    for e in entries.iter() {
+        while open_ends.last().is_some_and(|&end| e.start >= end || e.end > end) {
+            open_ends.pop();
+        }
+        depths.push(open_ends.len());
+        open_ends.push(e.end);
+    }
+
+    let mut out = String::new();
+    let mut entries = entries.into_iter().zip(depths).peekable();
+    let row_count = data.len().div_ceil(ROW_SIZE).max(1);
+
+    for row in 0..row_count {
+        let row_start = row * ROW_SIZE;
+        let bytes = &data[row_start.min(data.len())..(row_start + ROW_SIZE).min(data.len())];
+
+        let _ = write!(out, "{row_start:08x} ");
+        for i in 0..ROW_SIZE {
+            if i % 8 == 0 { out.push(' ') }
+            match bytes.get(i) {
+                Some(b) => { let _ = write!(out, "{b:02x} "); }
+                None => out.push_str("   "),
+            }
+        }
+        let ascii = bytes.iter().map(|&b| if b.is_ascii_graphic() || b == b' ' { b as char } else { '.' }).collect::<String>();
+        let _ = writeln!(out, " |{ascii}|");
+
+        // anything left over after the last row goes in the last row
+        let is_last = row + 1 == row_count;
+        while let Some((e, depth)) = entries.next_if(|(e, _)| is_last || e.start < row_start + ROW_SIZE) {
+            let mut value = e.value.clone();
+            if value.len() > MAX_VALUE_LEN {
+                let cut = (0..=MAX_VALUE_LEN).rev().find(|&i| value.is_char_boundary(i)).unwrap_or_default();
+                value.truncate(cut);
+                value.push_str("...");
+            }
+
+            let _ = writeln!(
+                out,
+                "{:10}{}{:#06x}..{:#06x} {} ({}) = {value}",
+                "",
+                "  ".repeat(depth),
+                e.start,
+                e.end,
+                e.path,
+                short_type_name(e.type_name)
+            );
+        }
+    }
+
+    out
+}
+
+/// remove module paths from a type name, ie `alloc::vec::Vec<alloc::string::String>` -> `Vec<String>`
+pub fn short_type_name(type_name: &str) -> String {
+    let mut out = String::new();
+    let mut segment = String::new();
+
+    for c in type_name.chars() {
+        if c.is_alphanumeric() || c == '_' || c == ':' {
+            segment.push(c);
+            continue;
+        }
+
+        out.push_str(segment.rsplit("::").next().unwrap_or_default());
+        segment.clear();
+        out.push(c);
+    }
+    out.push_str(segment.rsplit("::").next().unwrap_or_default());
+
+    out
+}
+
+
+#[test]
+fn test_trace() {
+    use crate::serialization::*;
+    use crate::types::ModDefinition;
+
+    let mods = vec![
+        ModDefinition::new("easy", "EZ", "Easy", true, 0.5),
+        ModDefinition::new("no_fail", "NF", "No Fail", false, 0.5),
+    ];
+    let data = SimpleWriter::new().write::<Vec<ModDefinition>>(&mods).done();
+
+    let mut reader = SerializationReader::new(data).trace();
+    reader.read::<Vec<ModDefinition>>("mods").unwrap();
+
+    let name = reader.trace_entries().iter().find(|e| e.path == "mods[1].short_name").unwrap();
+    assert_eq!(name.value, "\"NF\"");
+    assert_eq!(name.end - name.start, 8 + 2);
+    assert_eq!(short_type_name(name.type_name), "String");
+
+    let hexdump = reader.hexdump();
+    assert!(hexdump.contains("mods[1].short_name (String) = \"NF\""));
+    assert!(hexdump.contains("mods (Vec<ModDefinition>)"));
+
+    assert_eq!(
+        short_type_name("std::collections::HashMap<alloc::string::String, (u32, &[u8])>"),
+        "HashMap<String, (u32, &[u8])>"
+    );
+}
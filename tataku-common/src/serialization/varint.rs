@@ -0,0 +1,170 @@
+use crate::serialization::*;
+
+/// how lengths and integers are written
+///
+/// this comes from the [`Protocol`], but can be overridden on a reader or writer
+/// (ie to read data written with a different encoding during a rollout)
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Encoding {
+    /// lengths (of strings, collections and packet frames) are written as LEB128 varints instead of u64s
+    pub varint_lengths: bool,
+    /// integers bigger than a byte are written as LEB128 varints, signed ones are zigzag encoded first
+    pub varint_integers: bool,
+}
+impl Encoding {
+    /// everything is written as its full size
+    pub const FIXED: Self = Self { varint_lengths: false, varint_integers: false };
+    /// only lengths are varints
+    pub const VARINT_LENGTHS: Self = Self { varint_lengths: true, varint_integers: false };
+    /// lengths and integers are varints
+    pub const COMPACT: Self = Self { varint_lengths: true, varint_integers: true };
+}
+
+/// map signed numbers to unsigned ones so small negative numbers stay small (0, -1, 1, -2 -> 0, 1, 2, 3)
+pub fn zigzag_encode(n: i128) -> u128 {
+    ((n << 1) ^ (n >> 127)) as u128
+}
+pub fn zigzag_decode(n: u128) -> i128 {
+    (n >> 1) as i128 ^ -((n & 1) as i128)
+}
+
+/// how many bytes `n` is as a varint
+pub fn varint_len(n: u128) -> usize {
+    // 0 still needs a byte
+    (u128::BITS - (n | 1).leading_zeros()).div_ceil(7) as usize
+}
+
+impl SerializationWriter<'_> {
+    /// write `n` as an LEB128 varint (7 bits per byte, the high bit is set if there's more)
+    pub fn write_varint(&mut self, mut n: u128) {
+        while n >= 0x80 {
+            self.data.push(n as u8 | 0x80);
+            n >>= 7;
+        }
+        self.data.push(n as u8);
+    }
+
+    /// write a length (of a string, collection or frame)
+    pub fn write_length(&mut self, len: usize) {
+        if self.encoding().varint_lengths {
+            self.write_varint(len as u128)
+        } else {
+            self.write_raw_bytes(&(len as u64).to_le_bytes())
+        }
+    }
+
+    /// how many bytes `len` is written as
+    pub fn length_size(&self, len: usize) -> usize {
+        if self.encoding().varint_lengths { varint_len(len as u128) } else { 8 }
+    }
+
+    /// reserve space for a length which isnt known yet. fill it in with [`Self::fill_length`]
+    pub(crate) fn reserve_length(&mut self) -> usize {
+        self.hold();
+        let at = self.data.len();
+        // varints depend on the value, so they're inserted once it's known
+        if !self.encoding().varint_lengths { self.write_raw_bytes(&[0; 8]) }
+        at
+    }
+
+    /// where the data after a length reserved at `at` starts
+    pub(crate) fn after_length(&self, at: usize) -> usize {
+        if self.encoding().varint_lengths { at } else { at + 8 }
+    }
+
+    /// fill in a length reserved by [`Self::reserve_length`]
+    pub(crate) fn fill_length(&mut self, at: usize, len: usize) {
+        self.release();
+        if self.encoding().varint_lengths {
+            let mut varint = self.sub_writer();
+            varint.write_varint(len as u128);
+            self.data.splice(at..at, varint.data);
+        } else {
+            self.data[at..at + 8].copy_from_slice(&(len as u64).to_le_bytes());
+        }
+    }
+}
+
+impl SerializationReader<'_> {
+    /// read an LEB128 varint which should fit in `bits` bits
+    pub fn read_varint(&mut self, type_name: &'static str, bits: u32) -> SerializationResult<u128> {
+        let offset = self.offset();
+        let mut n = 0u128;
+
+        for shift in (0..bits).step_by(7) {
+            let byte = self.read_slice(1)?[0];
+            let value = (byte & 0x7f) as u128;
+
+            // the last byte can only have the bits which are left
+            if bits - shift < 7 && value >> (bits - shift) != 0 {
+                return Err(self.invalid_value(type_name, format!("varint is too big for {bits} bits"), offset))
+            }
+
+            n |= value << shift;
+            if byte & 0x80 == 0 { return Ok(n) }
+        }
+
+        Err(self.invalid_value(type_name, format!("varint is longer than {bits} bits"), offset))
+    }
+
+    /// read a length (of a string, collection or frame)
+    pub fn read_length(&mut self) -> SerializationResult<usize> {
+        let n = if self.encoding().varint_lengths {
+            self.read_varint("length", u64::BITS)? as u64
+        } else {
+            u64::from_le_bytes(self.read_slice(8)?.try_into().unwrap_or_else(|_| unreachable!()))
+        };
+
+        // doesnt matter on 64 bit, but on 32 bit anything bigger couldnt be read anyways
+        Ok(usize::try_from(n).unwrap_or(usize::MAX))
+    }
+}
+
+
+#[test]
+fn test_varints() {
+    fn roundtrip<T: Serializable + PartialEq + Copy>(value: T, len: usize) {
+        let mut writer = SerializationWriter::new().with_encoding(Encoding::COMPACT);
+        writer.write(&value);
+        let data = writer.data();
+        assert_eq!(data.len(), len, "{value:?} was written as {data:?}");
+
+        let mut reader = SerializationReader::new(data).with_encoding(Encoding::COMPACT);
+        assert_eq!(reader.read::<T>("value"), Ok(value));
+        assert!(!reader.can_read());
+    }
+
+    roundtrip(0u16, 1);
+    roundtrip(127u32, 1);
+    roundtrip(128u32, 2);
+    roundtrip(u64::MAX, 10);
+    roundtrip(u128::MAX, 19);
+    roundtrip(-1i32, 1);
+    roundtrip(63i16, 1);
+    roundtrip(-65i16, 2);
+    roundtrip(i64::MIN, 10);
+    roundtrip(i128::MIN, 19);
+    // bytes are always a byte
+    roundtrip(255u8, 1);
+    roundtrip(-1i8, 1);
+
+    for n in [0, 1, -1, 2, -2, i128::MAX, i128::MIN] {
+        assert_eq!(zigzag_decode(zigzag_encode(n)), n);
+    }
+    assert_eq!([0, -1, 1, -2].map(zigzag_encode), [0, 1, 2, 3]);
+    assert_eq!([0, 127, 128, u64::MAX as u128, u128::MAX].map(varint_len), [1, 1, 2, 10, 19]);
+
+    // a one character string is 2 bytes, instead of 9
+    let data = SimpleWriter::new().with_encoding(Encoding::VARINT_LENGTHS).write::<String>("a".to_owned()).done();
+    assert_eq!(data, [1, b'a']);
+
+    // too big for the type
+    let mut reader = SerializationReader::new(vec![0xff, 0xff, 0x04]).with_encoding(Encoding::COMPACT);
+    assert!(matches!(reader.read::<u16>("value").unwrap_err().inner, SerializationErrorEnum::InvalidValue { type_name: "u16", offset: 0, .. }));
+    // never ends
+    let mut reader = SerializationReader::new(vec![0x80; 20]).with_encoding(Encoding::COMPACT);
+    assert!(matches!(reader.read::<u64>("value").unwrap_err().inner, SerializationErrorEnum::InvalidValue { type_name: "u64", offset: 0, .. }));
+    // ran out
+    let mut reader = SerializationReader::new(vec![0x80]).with_encoding(Encoding::COMPACT);
+    assert_eq!(reader.read::<u32>("value").unwrap_err().inner, SerializationErrorEnum::Incomplete { offset: 1 });
+}
@@ -1,32 +1,250 @@
+use std::io::Write;
 use crate::serialization::*;
 
+/// how much data is kept before it's given to the sink
+const FLUSH_SIZE: usize = 64 * 1024;
+
 #[derive(Default)]
-pub struct SerializationWriter {
-    pub(crate) data: Vec<u8>
+pub struct SerializationWriter<'w> {
+    pub(crate) data: Vec<u8>,
+    pub protocol: Protocol,
+    /// overrides the protocol's [`Encoding`]
+    pub encoding: Option<Encoding>,
+    /// sort map and set entries so the same value is always written the same way
+    pub canonical: bool,
+
+    /// where the data goes once nothing needs to go back and change it (see [`Self::with_sink`])
+    sink: Option<Box<dyn Write + 'w>>,
+    /// how many bytes have been given to the sink
+    flushed: usize,
+    /// how many lengths are waiting to be filled in. nothing can be flushed until they are
+    held: usize,
+    /// the first error from the sink
+    error: Option<std::io::Error>,
 }
 #[allow(dead_code)]
-impl SerializationWriter {
+impl<'w> SerializationWriter<'w> {
     pub fn new() -> Self {
         Self::default()
     }
 
+    /// write into `buf`'s allocation instead of a new one (ie to reuse a buffer across broadcasts)
+    pub fn from_vec(mut buf: Vec<u8>) -> Self {
+        buf.clear();
+        Self { data: buf, ..Self::default() }
+    }
+
+    /// stream the data to `sink` as it's written, instead of keeping all of it in memory (ie for huge replays).
+    /// data is given to the sink in chunks, call [`Self::finish`] once everything has been written
+    ///
+    /// `&mut [u8]` is a sink too, but [`Self::write_to_slice`] checks the size first
+    pub fn with_sink(mut self, sink: impl Write + 'w) -> Self {
+        self.sink = Some(Box::new(sink));
+        self
+    }
+
+    pub fn with_protocol(mut self, protocol: Protocol) -> Self {
+        self.protocol = protocol;
+        self
+    }
+
+    /// use a different [`Encoding`] than the protocol's (ie while rolling out a new one)
+    pub fn with_encoding(mut self, encoding: Encoding) -> Self {
+        self.encoding = Some(encoding);
+        self
+    }
+
+    /// how lengths and integers are being written
+    pub fn encoding(&self) -> Encoding {
+        self.encoding.unwrap_or(self.protocol.encoding())
+    }
+
+    /// write maps and sets sorted by their encoded keys, so the output is reproducible (ie for hashing or signing)
+    pub fn canonical(mut self) -> Self {
+        self.canonical = true;
+        self
+    }
+
+    /// an empty writer with the same settings, without the sink
+    pub(crate) fn sub_writer<'b>(&self) -> SerializationWriter<'b> {
+        SerializationWriter { protocol: self.protocol, encoding: self.encoding, canonical: self.canonical, ..Default::default() }
+    }
+
+    /// the written data. if there's a sink, this is only what hasn't been given to it yet
     pub fn data(self) -> Vec<u8> {
         self.data
     }
 
+    /// the written data, without giving up the writer
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// how many bytes have been written, including what's been given to the sink
+    pub fn bytes_written(&self) -> usize {
+        self.flushed + self.data.len()
+    }
+
+    /// throw away the written data, but keep the allocation
+    pub fn clear(&mut self) {
+        self.data.clear();
+        self.flushed = 0;
+    }
+
+    /// how many bytes `s` would be written as, with this writer's settings
+    pub fn serialized_len<S:Serializable>(&self, s: &S) -> usize {
+        s.serialized_len(self)
+    }
+
+    /// how many bytes `write` writes, by writing it to a scratch writer with the same settings
+    pub fn measure(&self, write: impl FnOnce(&mut SerializationWriter)) -> usize {
+        let mut writer = self.sub_writer();
+        write(&mut writer);
+        writer.data.len()
+    }
+
     pub fn write<S:Serializable>(&mut self, s: &S) {
         s.write(self);
+        if self.held == 0 && self.data.len() >= FLUSH_SIZE { self.flush_sink() }
+    }
+
+    /// write `s` to the start of `buf`, returning how many bytes were written
+    ///
+    /// errors without writing anything if `buf` is too small. the data goes through this writer's buffer,
+    /// which is cleared first, so reuse the writer to avoid allocating.
+    /// if the writer has a sink, a scratch writer is used instead, so nothing goes to the sink and nothing waiting for it is lost
+    pub fn write_to_slice<S:Serializable>(&mut self, s: &S, buf: &mut [u8]) -> std::io::Result<usize> {
+        let len = self.serialized_len(s);
+        if len > buf.len() {
+            return Err(std::io::Error::new(std::io::ErrorKind::WriteZero, format!("{len} bytes dont fit in a {} byte buffer", buf.len())))
+        }
+
+        if self.sink.is_some() {
+            let mut writer = self.sub_writer();
+            s.write(&mut writer);
+            buf[..len].copy_from_slice(&writer.data);
+        } else {
+            self.clear();
+            s.write(self);
+            buf[..len].copy_from_slice(&self.data);
+        }
+        Ok(len)
+    }
+
+    /// give everything left to the sink, returning how many bytes were written in total (or the first error from the sink)
+    pub fn finish(mut self) -> std::io::Result<usize> {
+        self.flush_sink();
+        if let Some(sink) = &mut self.sink && let Err(e) = sink.flush() {
+            self.error.get_or_insert(e);
+        }
+
+        match self.error {
+            Some(e) => Err(e),
+            None => Ok(self.bytes_written()),
+        }
+    }
+
+    /// give the data to the sink, if there is one
+    fn flush_sink(&mut self) {
+        let Some(sink) = &mut self.sink else { return };
+        // once the sink fails, the rest of the data is thrown away
+        if self.error.is_none() && let Err(e) = sink.write_all(&self.data) {
+            self.error = Some(e)
+        }
+
+        self.flushed += self.data.len();
+        self.data.clear();
+    }
+
+    /// stop the data from being flushed, because something is going to change it (ie sort it)
+    pub(crate) fn hold(&mut self) {
+        self.held += 1;
+    }
+    pub(crate) fn release(&mut self) {
+        self.held -= 1;
     }
 
     pub fn write_raw_bytes(&mut self, bytes: &[u8]) {
         self.data.extend(bytes);
     }
+
+    /// write the entries of a map, sorted by their encoded key if this writer is canonical
+    pub fn write_map<'a, K: Serializable + 'a, V: Serializable + 'a>(&mut self, entries: impl ExactSizeIterator<Item = (&'a K, &'a V)>) {
+        self.write(&entries.len());
+        if !self.canonical {
+            for (key, val) in entries {
+                self.write(key);
+                self.write(val);
+            }
+            return
+        }
+
+        let mut encoded = entries.map(|(key, val)| {
+            let mut key_writer = self.sub_writer();
+            key_writer.write(key);
+            let mut val_writer = self.sub_writer();
+            val_writer.write(val);
+            (key_writer.data, val_writer.data)
+        }).collect::<Vec<_>>();
+        encoded.sort_unstable_by(|(a, _), (b, _)| a.cmp(b));
+
+        for (key, val) in encoded {
+            self.write_raw_bytes(&key);
+            self.write_raw_bytes(&val);
+        }
+    }
+
+    /// write the items of a set, sorted by their encoding if this writer is canonical
+    pub fn write_set<'a, T: Serializable + 'a>(&mut self, items: impl ExactSizeIterator<Item = &'a T>) {
+        self.write(&items.len());
+        if !self.canonical {
+            items.for_each(|i| self.write(i));
+            return
+        }
+
+        let mut encoded = items.map(|i| {
+            let mut writer = self.sub_writer();
+            writer.write(i);
+            writer.data
+        }).collect::<Vec<_>>();
+        encoded.sort_unstable();
+        encoded.iter().for_each(|i| self.write_raw_bytes(i));
+    }
+
+    /// how many bytes [`Self::write_map`] would write
+    pub fn map_len<'a, K: Serializable + 'a, V: Serializable + 'a>(&self, entries: impl ExactSizeIterator<Item = (&'a K, &'a V)>) -> usize {
+        self.length_size(entries.len()) + entries.map(|(key, val)| key.serialized_len(self) + val.serialized_len(self)).sum::<usize>()
+    }
+
+    /// how many bytes [`Self::write_set`] would write
+    pub fn set_len<'a, T: Serializable + 'a>(&self, items: impl ExactSizeIterator<Item = &'a T>) -> usize {
+        self.length_size(items.len()) + items.map(|i| i.serialized_len(self)).sum::<usize>()
+    }
+
+    /// how many bytes the frame around a `payload_len` byte payload adds, if the protocol is framed
+    pub fn frame_size(&self, payload_len: usize) -> usize {
+        if self.protocol.is_framed() { self.length_size(payload_len) } else { 0 }
+    }
+
+    /// reserve space for the payload length of a packet frame, if the protocol is framed
+    pub fn begin_frame(&mut self) -> Option<usize> {
+        if !self.protocol.is_framed() { return None }
+
+        Some(self.reserve_length())
+    }
+
+    /// fill in the payload length reserved by [`Self::begin_frame`]
+    pub fn end_frame(&mut self, frame: Option<usize>) {
+        let Some(start) = frame else { return };
+        let len = self.data.len() - self.after_length(start);
+        self.fill_length(start, len);
+    }
 }
 
 /// helper for inline-writing data
 #[derive(Default)]
 pub struct SimpleWriter {
-    writer: SerializationWriter
+    writer: SerializationWriter<'static>
 }
 impl SimpleWriter {
     pub fn new() -> Self { 
@@ -34,6 +252,16 @@ impl SimpleWriter {
             writer: SerializationWriter::new() 
         }
     }
+    pub fn canonical(self) -> Self {
+        Self {
+            writer: self.writer.canonical()
+        }
+    }
+    pub fn with_encoding(self, encoding: Encoding) -> Self {
+        Self {
+            writer: self.writer.with_encoding(encoding)
+        }
+    }
     pub fn done(self) -> Vec<u8> { 
         self.writer.data() 
     }
@@ -43,3 +271,60 @@ impl SimpleWriter {
         self
     }
 }
+
+
+#[test]
+fn test_writer_targets() {
+    use crate::packets::ChatPacket;
+
+    // big enough to be flushed in the middle of the frame, if it wasnt held
+    let packets = vec![ChatPacket::Server_FriendsList { friend_ids: (0..50_000).collect() }; 3];
+    for protocol in [Protocol::LEGACY, Protocol::FRAMED, Protocol::COMPACT] {
+        let mut writer = SerializationWriter::new().with_protocol(protocol);
+        writer.write(&packets);
+        let expected = writer.data();
+
+        // streamed
+        let mut streamed = Vec::new();
+        let mut writer = SerializationWriter::new().with_protocol(protocol).with_sink(&mut streamed);
+        writer.write(&packets);
+        assert!(writer.as_bytes().len() < expected.len(), "nothing was flushed");
+        assert_eq!(writer.serialized_len(&packets), expected.len());
+        assert_eq!(writer.finish().unwrap(), expected.len());
+        assert_eq!(streamed, expected);
+
+        // into a slice
+        let mut buf = vec![0; expected.len() + 10];
+        let mut writer = SerializationWriter::new().with_protocol(protocol);
+        assert_eq!(writer.write_to_slice(&packets, &mut buf).unwrap(), expected.len());
+        assert_eq!(buf[..expected.len()], expected);
+        assert!(writer.write_to_slice(&packets, &mut buf[..10]).is_err());
+
+        // into a slice, from a writer with a sink. nothing goes to the sink, and what it was waiting for isnt lost
+        let mut streamed = Vec::new();
+        let mut writer = SerializationWriter::new().with_protocol(protocol).with_sink(&mut streamed);
+        writer.write(&1u8);
+        let mut buf = vec![0; expected.len()];
+        assert_eq!(writer.write_to_slice(&packets, &mut buf).unwrap(), expected.len());
+        assert_eq!(buf, expected);
+        assert_eq!(writer.finish().unwrap(), 1);
+        assert_eq!(streamed, [1]);
+    }
+
+    // reusing a vec
+    let buf = Vec::with_capacity(1024);
+    let ptr = buf.as_ptr();
+    let mut writer = SerializationWriter::from_vec(buf);
+    writer.write(&"hello".to_owned());
+    writer.clear();
+    writer.write(&1u8);
+    assert_eq!(writer.as_bytes(), [1]);
+    let buf = writer.data();
+    assert_eq!(buf.as_ptr(), ptr);
+
+    // a sink which fails
+    let mut buf = [0u8; 4];
+    let mut writer = SerializationWriter::new().with_sink(&mut buf[..]);
+    writer.write(&0u64);
+    assert_eq!(writer.finish().unwrap_err().kind(), std::io::ErrorKind::WriteZero);
+}
@@ -1,4 +1,6 @@
-use crate::prelude::*;
+#[cfg(test)]
+use crate::types::replays::{ Replay, ReplayLoadError };
+use crate::serialization::*;
 
 
 #[derive(Debug, Clone)]
@@ -34,7 +36,7 @@ impl<'a, T> From<&'a T> for RawOrOther<'a, T> {
 
 pub struct VersionedWriter {
     version: u16,
-    writer: SerializationWriter
+    writer: SerializationWriter<'static>
 }
 impl VersionedWriter {
     pub fn new(version: u16) -> Self { Self { version, writer: SerializationWriter::new() } }
@@ -90,9 +92,9 @@ fn test1() {
     // let bytes = std::fs::read("/tmp/replays/4fda112a7401e5b9a379adbaa14d3c5a.ttkr").unwrap();
     let bytes = std::fs::read("/tmp/replays/12eeb05fbfa169060b0f7597f3130057.ttkr").unwrap();
 
-    let mut reader = SerializationReader::new(bytes).debug();
+    let mut reader = SerializationReader::new(bytes).trace();
     if let Err(ReplayLoadError::SerializationError(e)) = Replay::try_read_replay(&mut reader) {
         // println!("error reading replay: {e:?}");
-        panic!("{e}")
+        panic!("{e}\n{}", reader.hexdump())
     }
 }
\ No newline at end of file
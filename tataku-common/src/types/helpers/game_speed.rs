@@ -63,6 +63,11 @@ impl From<f32> for GameSpeed {
     fn from(value: f32) -> Self { Self::from_f32(value) }
 }
 
+#[cfg(feature="test")]
+impl crate::serialization::RandomValue for GameSpeed {
+    fn random(rng: &mut crate::serialization::RandomSource) -> Self { Self::from_u16(u16::random(rng)) }
+}
+
 impl std::str::FromStr for GameSpeed {
     type Err = ReflectError<'static>;
 
@@ -43,3 +43,22 @@ impl Serializable for MapGame {
         }
     }
 }
+impl WireSchema for MapGame {
+    fn schema() -> TypeSchema {
+        TypeSchema::primitive("MapGame", "the game's name as a String (ie `osu`, `quaver`)")
+    }
+}
+#[cfg(feature="test")]
+impl RandomValue for MapGame {
+    fn random(rng: &mut RandomSource) -> Self {
+        match rng.below(3) {
+            0 => Self::Osu,
+            1 => Self::Quaver,
+            // known names are read as their own variant
+            _ => Self::Other(format!("game {}", String::random(rng))),
+        }
+    }
+    fn random_variants(rng: &mut RandomSource) -> Vec<Self> {
+        vec![Self::Osu, Self::Quaver, Self::Other(format!("game {}", String::random(rng)))]
+    }
+}
@@ -6,6 +6,8 @@ use crate::types::{
     Md5Hash
 };
 
+#[cfg_attr(feature="test", derive(crate::macros::RandomValue))]
+#[derive(Serializable)]
 #[derive(Clone, Debug)]
 #[derive(Serialize, Deserialize)]
 pub struct ScoreSubmit {
@@ -25,30 +27,10 @@ pub struct ScoreSubmit {
     /// this helpers the server get info for this map if it doesnt already have it
     pub map_info: ScoreMapInfo,
 }
-impl Serializable for ScoreSubmit {
-    fn read(sr: &mut SerializationReader) -> SerializationResult<Self> where Self: Sized {
-        sr.push_parent("Score Submit");
-        let a = Ok(Self {
-            username: sr.read("username")?,
-            password: sr.read("password")?,
-            game: sr.read("game")?,
-            score: sr.read("score")?,
-            map_info: sr.read("map_info")?,
-        });
-        sr.pop_parent();
-        a
-    }
-
-    fn write(&self, sw: &mut SerializationWriter) {
-        sw.write(&self.username);
-        sw.write(&self.password);
-        sw.write(&self.game);
-        sw.write(&self.score);
-        sw.write(&self.map_info);
-    }
-}
 
 
+#[cfg_attr(feature="test", derive(crate::macros::RandomValue))]
+#[derive(Serializable)]
 #[derive(Clone, Debug)]
 #[derive(Serialize, Deserialize)]
 pub struct ScoreMapInfo {
@@ -56,25 +38,6 @@ pub struct ScoreMapInfo {
     pub map_hash: Md5Hash,
     pub playmode: String,
 }
-impl Serializable for ScoreMapInfo {
-    fn read(sr: &mut SerializationReader) -> SerializationResult<Self> where Self: Sized {
-        sr.push_parent("ScoreMapInfo");
-        let a = Ok(Self {
-            game: sr.read("game")?,
-            map_hash: sr.read("map_hash")?,
-            playmode: sr.read("playmode")?
-        });
-
-        sr.pop_parent();
-        a
-    }
-
-    fn write(&self, sw: &mut SerializationWriter) {
-        sw.write(&self.game);
-        sw.write(&self.map_hash);
-        sw.write(&self.playmode);
-    }
-}
 
 
 #[derive(Clone)]
@@ -82,6 +82,15 @@ impl Serializable for Md5Hash {
         sw.write(&s);
     }
 }
+impl WireSchema for Md5Hash {
+    fn schema() -> TypeSchema {
+        TypeSchema::primitive("Md5Hash", "the hash as a hex String")
+    }
+}
+#[cfg(feature="test")]
+impl RandomValue for Md5Hash {
+    fn random(rng: &mut RandomSource) -> Self { Self(u128::random(rng)) }
+}
 
 #[test]
 fn beatmap_hash_test() {
@@ -6,23 +6,30 @@ use crate::serialization::*;
 const CURRENT_VERSION:u16 = 1;
 
 /// a simple mod definition
-#[derive(Reflect)]
+#[cfg_attr(feature="test", derive(crate::macros::RandomValue))]
+#[derive(Reflect, Serializable)]
 #[derive(Clone, Debug)]
 #[derive(Serialize, Deserialize)]
+#[serialize(current_version = CURRENT_VERSION)]
 pub struct ModDefinition {
     /// mod identifier, used in the mods hashmap
+    #[serialize(added = 1)]
     pub name: String,
 
     /// short (usually 2 letter) name for the mod (ie HR, EZ)
+    #[serialize(added = 1)]
     pub short_name: String,
 
     /// actual display name for the mod
+    #[serialize(added = 1)]
     pub display_name: String,
 
     /// does this mod adjust the difficulty rating? used for diff calc
+    #[serialize(added = 1)]
     pub adjusts_difficulty: bool,
 
     /// how much does this mod adjust the score multiplier?
+    #[serialize(added = 1, default = 1.0)]
     pub score_multiplier: f32,
 }
 impl ModDefinition {
@@ -73,37 +80,3 @@ impl std::cmp::Ord for ModDefinition {
     }
 }
 
-impl Serializable for ModDefinition {
-    fn read(sr: &mut SerializationReader) -> SerializationResult<Self> {
-        sr.push_parent("ModDefinition");
-
-        let version = sr.read::<u16>("version")?;
-
-        macro_rules! version {
-            ($version:expr, $a:expr, $default:expr) => {
-                if version >= $version {
-                    sr.read($a)?
-                } else {
-                    $default
-                }
-            };
-        }
-
-        Ok(Self {
-            name: version!(1, "name", String::new()),
-            short_name: version!(1, "short_name", String::new()),
-            display_name: version!(1, "display_name", String::new()),
-            adjusts_difficulty: version!(1, "adjusts_difficulty", false),
-            score_multiplier: version!(1, "score_multiplier", 1.0),
-        })
-    }
-
-    fn write(&self, sw: &mut SerializationWriter) {
-        sw.write(&CURRENT_VERSION);
-        sw.write(&self.name);
-        sw.write(&self.short_name);
-        sw.write(&self.display_name);
-        sw.write(&self.adjusts_difficulty);
-        sw.write(&self.score_multiplier);
-    }
-}
@@ -1,5 +1,6 @@
 use crate::serialization::*;
 
+#[cfg_attr(feature="test", derive(crate::macros::RandomValue))]
 #[repr(u8)]
 #[derive(crate::macros::PacketSerialization)]
 #[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
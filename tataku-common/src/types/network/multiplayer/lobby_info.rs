@@ -8,6 +8,7 @@ use crate::types::{
     network::multiplayer::*
 };
 
+#[cfg_attr(feature="test", derive(crate::macros::RandomValue))]
 #[derive(Serialize, Deserialize)]
 #[derive(Reflect, Serializable)]
 #[derive(Clone, Debug, Default, Eq, PartialEq)]
@@ -36,6 +37,7 @@ pub struct LobbyInfo {
 
 
 /// extra lobby data
+#[cfg_attr(feature="test", derive(crate::macros::RandomValue))]
 #[derive(Serialize, Deserialize)]
 #[derive(Reflect, Serializable)]
 #[derive(Clone, Debug, Default, Eq, PartialEq)]
@@ -62,6 +64,7 @@ pub struct FullLobbyInfo {
     pub current_beatmap: Option<LobbyBeatmap>,
 }
 
+#[cfg_attr(feature="test", derive(crate::macros::RandomValue))]
 #[derive(Serialize, Deserialize)]
 #[derive(Reflect, Serializable)]
 #[derive(Clone, Debug, Default, Eq, PartialEq)]
@@ -2,6 +2,7 @@ use crate::macros::*;
 use crate::reflection::*;
 use crate::serialization::*;
 
+#[cfg_attr(feature="test", derive(crate::macros::RandomValue))]
 #[repr(u8)]
 #[derive(Serialize, Deserialize)]
 #[derive(Reflect, PacketSerialization)]
@@ -4,6 +4,7 @@ use crate::serialization::*;
 use std::collections::HashSet;
 use crate::types::network::multiplayer::LobbyUserState;
 
+#[cfg_attr(feature="test", derive(crate::macros::RandomValue))]
 #[derive(Serialize, Deserialize)]
 #[derive(Reflect, Serializable)]
 #[derive(Clone, Debug, Default, Eq, PartialEq)]
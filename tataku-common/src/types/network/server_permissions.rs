@@ -31,7 +31,7 @@ impl Serializable for Vec<ServerPermissions> {
                 })+
             }
         }
-        check!(Bot, Donator, Moderator);
+        check!(Bot, Donator, Moderator, ChatOnly);
 
         Ok(list)
     }
@@ -44,3 +44,26 @@ impl Serializable for Vec<ServerPermissions> {
         sw.write(&num)
     }
 }
+impl WireSchema for Vec<ServerPermissions> {
+    fn schema() -> TypeSchema {
+        TypeSchema::new("ServerPermissions", SchemaKind::Bitmask {
+            id_type: "u16",
+            flags: vec![
+                ("Bot", ServerPermissions::Bot as u64),
+                ("Donator", ServerPermissions::Donator as u64),
+                ("Moderator", ServerPermissions::Moderator as u64),
+                ("ChatOnly", ServerPermissions::ChatOnly as u64),
+            ],
+        })
+    }
+}
+#[cfg(feature="test")]
+impl RandomValue for Vec<ServerPermissions> {
+    fn random(rng: &mut RandomSource) -> Self {
+        // in the order they're read
+        [ServerPermissions::Bot, ServerPermissions::Donator, ServerPermissions::Moderator, ServerPermissions::ChatOnly]
+            .into_iter()
+            .filter(|_| rng.bool())
+            .collect()
+    }
+}
@@ -10,6 +10,7 @@ use crate::types::{
     replays::ReplayAction,
 };
 
+#[cfg_attr(feature="test", derive(crate::macros::RandomValue))]
 #[repr(u8)]
 #[derive(Clone, Debug, Default)]
 #[allow(clippy::large_enum_variant)]
@@ -2,7 +2,8 @@ use crate::reflection::*;
 use crate::serialization::*;
 use crate::types::network::spectator::*;
 
-#[derive(crate::macros::Reflect)]
+#[cfg_attr(feature="test", derive(crate::macros::RandomValue))]
+#[derive(crate::macros::Reflect, crate::macros::Serializable)]
 #[derive(Clone, Debug)]
 pub struct SpectatorFrame {
     pub time: f32,
@@ -16,18 +17,3 @@ impl SpectatorFrame {
         }
     }
 }
-impl Serializable for SpectatorFrame {
-    fn read(sr: &mut SerializationReader) -> SerializationResult<Self> where Self: Sized {
-        sr.push_parent("SpectatorFrame");
-        let a = Ok(Self {
-            time: sr.read("time")?,
-            action: sr.read("action")?,
-        });
-        sr.pop_parent();
-        a
-    }
-    fn write(&self, sw: &mut SerializationWriter) {
-        sw.write(&self.time);
-        sw.write(&self.action);
-    }
-}
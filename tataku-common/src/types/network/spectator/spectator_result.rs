@@ -1,5 +1,6 @@
 use crate::serialization::*;
 
+#[cfg_attr(feature="test", derive(crate::macros::RandomValue))]
 #[repr(u8)]
 #[allow(non_camel_case_types)]
 #[derive(crate::macros::PacketSerialization)]
@@ -2,7 +2,8 @@ use crate::macros::*;
 use crate::reflection::*;
 use crate::serialization::*;
 
-#[derive(Reflect, FromStr)]
+#[cfg_attr(feature="test", derive(crate::macros::RandomValue))]
+#[derive(Reflect, FromStr, Serializable)]
 #[derive(Serialize, Deserialize)]
 #[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
 pub enum KeyPress {
@@ -33,7 +34,10 @@ pub enum KeyPress {
     RightMouse = 34,
 
     SkipIntro = 254,
-    #[default] Unknown = 255
+    /// unknown keys are read as this
+    #[default]
+    #[serialize(fallback)]
+    Unknown = 255
 }
 
 impl From<KeyPress> for u8 {
@@ -61,11 +65,14 @@ impl From<u8> for KeyPress {
             10 => Mania7,
             11 => Mania8,
             12 => Mania9,
+            13 => Mania10,
 
             30 => Left,
             31 => Right,
             32 => Dash,
 
+            33 => LeftMouse,
+            34 => RightMouse,
 
             254 => SkipIntro,
             255 => Unknown,
@@ -73,12 +80,3 @@ impl From<u8> for KeyPress {
         }
     }
 }
-
-impl Serializable for KeyPress {
-    fn read(sr: &mut SerializationReader) -> SerializationResult<Self> {
-        Ok(sr.read::<u8>("KeyPress")?.into())
-    }
-    fn write(&self, sw:&mut SerializationWriter) { 
-        sw.write::<u8>(&(*self as u8)) 
-    }
-}
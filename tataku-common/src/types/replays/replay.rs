@@ -16,17 +16,23 @@ use crate::types::{
 // v6 had breaking changes since we moved the replay to the score (whereas before the score used to be in the replay)
 pub(crate) const CURRENT_VERSION:u16 = 6;
 
+/// the last score version which was written inside replays
+const LAST_SCORE_VERSION_WITHOUT_REPLAY:u16 = 8;
+
+#[cfg_attr(feature="test", derive(crate::macros::RandomValue))]
 #[derive(Clone, Debug, Default)]
 #[derive(Serialize, Deserialize)]
-#[derive(Reflect)]
+#[derive(Reflect, Serializable)]
+#[serialize(current_version = CURRENT_VERSION)]
 pub struct Replay {
-    // removed in v6
-    // score: Option<Score>
-
     /// any extra gameplay variables which are helpful to know
+    #[serialize(removed_field(name = "playstyle", ty = u8, added = 1, removed = 2))]
+    #[serialize(removed_field(name = "score", ty = Option<Score>, added = 2, removed = 6))]
+    #[serialize(added = 4)]
     pub gamemode_data: HashMap<String, String>,
 
     /// time offset 
+    #[serialize(added = 5)]
     pub offset: f32,
 
     /// (time, key)
@@ -46,95 +52,64 @@ impl Replay {
     pub fn try_read_replay(sr: &mut SerializationReader) -> Result<Score, ReplayLoadError> {
         sr.push_parent("Replay (as Score)");
         // all versions wrote the version number
-        let version = sr.read::<u16>("version")?;
-
-        macro_rules! version {
-            ($v:expr, $a: expr) => {
-                if version >= $v {
-                    sr.read($a)?
-                } else {
-                    Default::default()
-                }
-            };
-            ($v:expr, $a: expr, $def:expr) => {
-                if version >= $v {
-                    sr.read($a)?
-                } else {
-                    $def
-                }
-            };
-        }
-
-        // if version is less than 6
-        if version < 6 {
-            if version == 1 { return Err(ReplayLoadError::TooOld) }
-
-            let score: Option<Score> = sr.read("score")?;
-            let Some(mut score) = score else {return Err(ReplayLoadError::NoScore)};
-
-
-            score.replay = Some(Replay {
-                gamemode_data: version!(4, "gamemode_data"),
-                offset: version!(5, "offset"),
-                frames: sr.read("frames")?,
-            });
-
-            sr.pop_parent();
+        let version = sr.peek::<u16>("version")?;
+
+        let score = match version {
+            1 => return Err(ReplayLoadError::TooOld),
+
+            // the score was inside the replay, right after the version
+            ..6 => {
+                sr.read::<u16>("version")?;
+                let Some(mut score) = sr.read::<Option<Score>>("score")? else { return Err(ReplayLoadError::NoScore) };
+
+                // then the rest of the replay
+                score.replay = Some(Replay {
+                    gamemode_data: if version >= 4 { sr.read("gamemode_data")? } else { HashMap::new() },
+                    offset: if version >= 5 { sr.read("offset")? } else { 0.0 },
+                    frames: sr.read("frames")?,
+                });
+                score
+            }
 
-            Ok(score)
-        } else {
             // newer version will just be the score directly
+            _ => sr.read("score")?,
+        };
 
-            // unread the version
-            sr.unread(std::mem::size_of::<u16>());
-
-            // reread everything as a score
-            let score:Score = sr.read("score")?;
-
-            sr.pop_parent();
-
-            Ok(score)
-        }
+        sr.pop_parent();
+        Ok(score)
     }
-}
-impl Serializable for Replay {
-    fn read(sr: &mut SerializationReader) -> SerializationResult<Self> {
-        let mut r = Replay::new();
-        sr.push_parent("Replay");
-        
-        // all versions wrote the version number
-        let version = sr.read::<u16>("version")?;
 
-        if version < 6 {
-            if version == 1 { let _playstyle = sr.read::<u8>("playstyle")?; }
+    /// write a score's replay as an older replay version, the opposite of [`Self::try_read_replay`]
+    /// 
+    /// returns the names of the fields which couldnt be written in that version
+    pub fn write_with_score(score: &Score, sw: &mut SerializationWriter, version: u16) -> Vec<&'static str> {
+        let replay = score.replay.clone().unwrap_or_default();
+        
+        match version {
+            // the score is the replay
+            6.. => score.write_versioned(sw, Score::CURRENT_VERSION),
+
+            // the score was inside the replay, right after the version
+            2..6 => {
+                sw.write(&version);
+                sw.write(&true);
+                let mut lost = score.write_versioned(sw, LAST_SCORE_VERSION_WITHOUT_REPLAY);
+                lost.retain(|f| *f != "replay");
+
+                // then the rest of the replay
+                if version >= 4 { sw.write(&replay.gamemode_data) } else { lost.push("gamemode_data") }
+                if version >= 5 { sw.write(&replay.offset) } else { lost.push("offset") }
+                sw.write(&replay.frames);
+                lost
+            }
 
-            if version > 1 {
-                let _score: Option<Score> = sr.read("score (unused)")?;
+            // no score
+            _ => {
+                let mut lost = replay.write_versioned(sw, version);
+                lost.push("score");
+                lost
             }
-                
-            return Ok(Replay {
-                gamemode_data: if version >= 4 { sr.read("gamemode_data")? } else { Default::default() },
-                offset: if version >= 5 { sr.read("offset")? } else { Default::default() },
-                frames: sr.read("frames")?,
-            });
         }
-
-        r.gamemode_data = sr.read("gamemode_data")?;
-        r.offset = sr.read("offset")?;
-        r.frames = sr.read("frames")?;
-        
-        Ok(r)
-    }
-
-    fn write(&self, sw: &mut SerializationWriter) {
-        sw.write(&CURRENT_VERSION); // all versions
-        // sw.write(self.playstyle as u8); // removed in v2
-        // sw.write(&self.score_data); // added in v2, removed in v6
-        sw.write(&self.gamemode_data); // added in v3, fixed in v4
-        sw.write(&self.offset); // added in v5
-
-        // println!("writing {} replay frames", self.frames.len());
-        sw.write(&self.frames); // all versions
     }
 }
 
@@ -153,7 +128,9 @@ impl From<SerializationError> for ReplayLoadError {
 #[cfg(feature = "test")]
 pub(crate) mod tests {
     use crate::tests::*;
-    use crate::prelude::*;
+    use crate::types::*;
+    use crate::types::replays::*;
+    use crate::serialization::*;
     use std::collections::HashMap;
 
     fn make_replay_inner<'a>(
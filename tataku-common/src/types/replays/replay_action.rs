@@ -3,44 +3,12 @@ use crate::reflection::*;
 use crate::serialization::*;
 use crate::types::replays::KeyPress;
 
-#[derive(Reflect)]
+#[cfg_attr(feature="test", derive(crate::macros::RandomValue))]
+#[derive(Reflect, Serializable)]
 #[derive(Clone, Copy, Debug)]
 #[derive(Serialize, Deserialize)]
 pub enum ReplayAction {
-    Press(KeyPress),
-    Release(KeyPress),
-    MousePos(f32, f32)
-}
-impl Serializable for ReplayAction {
-    fn read(sr: &mut SerializationReader) -> SerializationResult<Self> {
-        sr.push_parent("ReplayAction");
-
-        let a = Ok(match sr.read::<u8>("id")? {
-            0 => ReplayAction::Press(sr.read("press")?),
-            1 => ReplayAction::Release(sr.read("release")?),
-            2 => ReplayAction::MousePos(sr.read("x")?, sr.read("y")?),
-            _ => panic!("error reading replay frame type")
-        });
-        sr.pop_parent();
-
-        a
-    }
-
-    fn write(&self, sw: &mut SerializationWriter) {
-        match self {
-            ReplayAction::Press(k) => {
-                sw.write::<u8>(&0);
-                sw.write(k);
-            }
-            ReplayAction::Release(k) => {
-                sw.write::<u8>(&1);
-                sw.write(k);
-            }
-            ReplayAction::MousePos(x, y) => {
-                sw.write::<u8>(&2);
-                sw.write(x);
-                sw.write(y);
-            }
-        }
-    }
+    Press(#[serialize(name = "key")] KeyPress),
+    Release(#[serialize(name = "key")] KeyPress),
+    MousePos(#[serialize(name = "x")] f32, #[serialize(name = "y")] f32)
 }
@@ -3,7 +3,8 @@ use crate::reflection::*;
 use crate::serialization::*;
 use crate::types::replays::ReplayAction;
 
-#[derive(Reflect)]
+#[cfg_attr(feature="test", derive(crate::macros::RandomValue))]
+#[derive(Reflect, Serializable)]
 #[derive(Copy, Clone, Debug)]
 #[derive(Serialize, Deserialize)]
 pub struct ReplayFrame {
@@ -18,22 +19,3 @@ impl ReplayFrame {
         }
     }
 }
-
-impl Serializable for ReplayFrame {
-    fn read(sr: &mut SerializationReader) -> SerializationResult<Self> {
-        sr.push_parent("ReplayFrame");
-
-        let a = Ok(Self {
-            time: sr.read("time")?,
-            action: sr.read("action")?,
-        });
-
-        sr.pop_parent();
-        a
-    }
-
-    fn write(&self, sw: &mut SerializationWriter) {
-        sw.write(&self.time);
-        sw.write(&self.action);
-    }
-}
@@ -7,7 +7,8 @@ use std::collections::HashMap;
 use crate::types::{
     replays::Replay,
     Md5Hash,
-    GameSpeed
+    GameSpeed,
+    ModDefinition,
 };
 
 // v2 added game speed as an f32
@@ -19,44 +20,60 @@ use crate::types::{
 // v10 made the hash function not stupid, also changed the speed to be serialized as a u16
 const CURRENT_VERSION:u16 = 10;
 
-#[derive(Reflect)]
+#[cfg_attr(feature="test", derive(crate::macros::RandomValue))]
+#[derive(Reflect, Serializable)]
 #[derive(Clone, Debug, Default)]
 #[derive(Serialize, Deserialize)]
+#[serialize(current_version = CURRENT_VERSION)]
 pub struct Score {
     pub version: u16,
     pub username: String,
     pub beatmap_hash: Md5Hash,
     pub playmode: String,
     /// time in non-leap seconds since unix_epoch (UTC)
+    #[serialize(added = 5)]
     pub time: u64,
 
     pub score: u64,
     pub combo: u16,
     pub max_combo: u16,
 
+    #[serialize(removed = 4, read_as = OldJudgments, convert = judgments_from_old, convert_back = judgments_to_old, lossy = judgments_lost_in_old)]
+    #[serialize(added = 4)]
     pub judgments: HashMap<String, u16>,
 
+    #[serialize(removed = 9, read_as = f64, convert = |a: f64| a as f32, convert_back = |a: &f32| *a as f64)]
+    #[serialize(added = 9)]
     pub accuracy: f32,
     #[reflect(skip)]
+    #[serialize(added = 2, removed = 10, read_as = f32, convert = GameSpeed::from_f32, convert_back = GameSpeed::as_f32)]
+    #[serialize(added = 10, read_as = u16, convert = GameSpeed::from_u16, convert_back = GameSpeed::as_u16)]
     pub speed: crate::types::GameSpeed,
 
     /// new mods format
     /// TODO: it was kinda silly to include all this in score submits to the server etc
     /// it might be better so move back to just ids, and then provide an api for retrieving mod data
+    #[serialize(added = 3, removed = 6, read_as = Option<String>, convert = mods_from_manager, convert_back = mods_to_manager, lossy = mods_lost_in_manager)]
+    #[serialize(added = 6, removed = 9, read_as = HashSet<String>, convert = mods_from_ids, convert_back = mods_to_ids)]
+    #[serialize(added = 9)]
     pub mods: Vec<crate::types::ModDefinition>,
 
     /// how many performance points this is worth
+    #[serialize(added = 7)]
     pub performance: f32,
 
     /// time diff for actual note hits. if the note wasnt hit, it wont be here
     /// (user_hit_time - correct_time)
+    #[serialize(skip)]
     pub hit_timings: Vec<f32>,
 
     /// this was poorly documented when i initially added it to tataku.
     /// once i have the brain power to figure it out i'll update this doc
+    #[serialize(added = 8)]
     pub stat_data: HashMap<String, Vec<f32>>,
 
     /// replay data for this score
+    #[serialize(added = 9)]
     pub replay: Option<Replay>,
 }
 impl Score {
@@ -114,7 +131,7 @@ impl Score {
                 let mut judgments = self.judgments
                     .iter()
                     .collect::<Vec<_>>();
-                judgments.sort_by(|(a, _), (b,_)| a.cmp(b));
+                judgments.sort_by_key(|(a, _)| *a);
 
 
                 // it should be safe to ignore errors because what could possibly fail here?
@@ -201,16 +218,16 @@ impl Score {
         judgments.join("|")
     }
 
+    /// the opposite of [`Self::judgment_string`]. entries which cant be parsed are skipped
     pub fn judgments_from_string(judgment_string: &str) -> HashMap<String, u16> {
         let mut judgments = HashMap::new();
 
         let entries = judgment_string.split("|");
         for entry in entries {
             let mut split = entry.split(":");
-            if let Some((key, val)) = split.next().zip(split.next()) {
-                let key = key.to_owned();
-                let val = val.parse().unwrap();
-                judgments.insert(key, val);
+            if let Some((key, val)) = split.next().zip(split.next())
+            && let Ok(val) = val.parse() {
+                judgments.insert(key.to_owned(), val);
             }
         }
 
@@ -232,77 +249,6 @@ impl Score {
     }
 }
 
-impl Serializable for Score {
-    fn read(sr: &mut SerializationReader) -> SerializationResult<Self> {
-        sr.push_parent("Score");
-
-        let version = sr.read::<u16>("version")?;
-
-        if version < 9 {
-            return read_old_score(version, sr);
-        }
-
-        // everything here so far exists in v9
-        let a = Ok(Score {
-            version,
-            username: sr.read("username")?,
-            beatmap_hash: sr.read("beatmap_hash")?,
-            playmode: sr.read("playmode")?,
-            time: sr.read("time")?,
-
-            score: sr.read("score")?,
-            combo: sr.read("combo")?,
-            max_combo: sr.read("max_combo")?,
-            judgments: sr.read("judgments")?,
-            accuracy: sr.read("accuracy")?,
-            speed: if version < 10 {
-                GameSpeed::from_f32(sr.read("speed")?)
-            } else {
-                GameSpeed::from_u16(sr.read("speed")?)
-            },
-
-            mods: sr.read("mods")?,
-            performance: sr.read("performance")?,
-
-            // mods_string,
-            stat_data: sr.read("stat_data")?,
-            replay: sr.read("replay")?,
-
-            hit_timings: Vec::new(),
-        });
-
-        sr.pop_parent();
-        a
-    }
-
-    fn write(&self, sw: &mut SerializationWriter) {
-        // always serializes as the latest version
-        sw.write(&CURRENT_VERSION);
-        sw.write(&self.username);
-        sw.write(&self.beatmap_hash);
-        sw.write(&self.playmode);
-        sw.write(&self.time);
-
-        sw.write(&self.score);
-        sw.write(&self.combo);
-        sw.write(&self.max_combo);
-
-        sw.write(&self.judgments);
-
-        sw.write(&self.accuracy);
-        sw.write(&self.speed.as_u16());
-
-        // sw.write(self.mods_string.clone());
-        sw.write(&self.mods);
-        sw.write(&self.performance);
-        sw.write(&self.stat_data);
-
-        sw.write(&self.replay);
-    }
-}
-
-
-
 /// helper struct
 #[derive(Copy, Clone, Debug)]
 pub struct HitError {
@@ -327,158 +273,74 @@ pub(super) struct ModManager {
 }
 
 
-/// used to read versions older than v9
-/// this can probably be obsoleted if/when the game "releases", or at least finds a stable score struct
-fn read_old_score(
-    version: u16,
-    sr: &mut SerializationReader,
-) -> SerializationResult<Score> {
-    macro_rules! version {
-        ($v:expr, $a: expr, $def:expr) => {
-            if version >= $v {
-                sr.read($a)?
-            } else {
-                $def
-            }
-        };
-    }
-
-    sr.push_parent("Score (old)");
-
-    let username = sr.read("username")?;
-    let beatmap_hash = sr.read("beatmap_hash")?;
-    let playmode = sr.read("playmode")?;
-    let time = version!(5, "time", 0); // v5 added time
-
-    let score = sr.read("score")?;
-    let combo = sr.read("combo")?;
-    let max_combo = sr.read("max_combo")?;
-
-    // before version 4, judgments were stored manually
-    let mut judgments = HashMap::new();
-    if version < 4 {
-        for i in [
-            "x50",
-            "x100",
-            "x300",
-            "xgeki",
-            "xkatu",
-            "xmiss"
-        ] {
-            let val:u16 = sr.read(i)?;
-            judgments.insert(i.to_owned(), val);
-        }
-    } else {
-        let count:usize = sr.read("judment count")?;
-        for n in 0..count {
-            let key = sr.read(format!("judgement key #{n}"))?;
-            let val = sr.read(format!("judgment val #{n}"))?;
-            judgments.insert(key, val);
-        }
-    }
-
-    let accuracy:f64 = sr.read("accuracy")?;
-    let speed = if version >= 2 {
-        GameSpeed::from_f32(sr.read::<f32>("speed")?)
-    } else {
-        GameSpeed::default()
-    };
+/// judgments before v4 were stored manually, in this order
+const OLD_JUDGMENTS: [&str; 6] = ["x50", "x100", "x300", "xgeki", "xkatu", "xmiss"];
+type OldJudgments = (u16, u16, u16, u16, u16, u16);
 
-    let mut mods2 = HashSet::new();
-    let mut mods = Vec::new();
-
-    // v 3-5 stored mods as a string
-    match version {
-        // v 0,1,2 did not have mods
-        0..=2 => {}
-
-        // v 3-5 stored mods as a string
-        3..=5 => {
-            // old mods
-            let mods_string: Option<String> = sr.read("mods string")?;
-
-            // parse here, save time later
-            if let Some(str) = &mods_string
-            && let Ok(manager) = serde_json::from_str::<ModManager>(str) {
-                if manager.autoplay { mods2.insert("autoplay".to_owned()); }
-                if manager.nofail { mods2.insert("no_fail".to_owned()); }
-                if manager.hard_rock { mods2.insert("hard_rock".to_owned()); }
-                if manager.easy { mods2.insert("easy".to_owned()); }
-            }
-        }
+fn judgments_from_old((x50, x100, x300, xgeki, xkatu, xmiss): OldJudgments) -> HashMap<String, u16> {
+    OLD_JUDGMENTS.into_iter()
+        .map(str::to_owned)
+        .zip([x50, x100, x300, xgeki, xkatu, xmiss])
+        .collect()
+}
 
-        // 6-8 stored mods as a hashset of mod ids
-        6..=8 => {
-            mods2 = sr.read("mods hashset")?;
-        }
+/// judgments other than the old ones are lost
+fn judgments_to_old(judgments: &HashMap<String, u16>) -> OldJudgments {
+    let [x50, x100, x300, xgeki, xkatu, xmiss] = OLD_JUDGMENTS.map(|j| judgments.get(j).copied().unwrap_or_default());
+    (x50, x100, x300, xgeki, xkatu, xmiss)
+}
+fn judgments_lost_in_old(judgments: &HashMap<String, u16>) -> bool {
+    judgments.iter().any(|(j, count)| *count > 0 && !OLD_JUDGMENTS.contains(&j.as_str()))
+}
 
-        // v9 started storing mod information in the score
-        9.. => {
-            mods = sr.read("mods")?;
-        }
+/// the mods a [`ModManager`] knew about
+const MANAGER_MODS: [&str; 4] = ["easy", "hard_rock", "autoplay", "no_fail"];
 
-        // _ => unreachable!()
-    }
+/// v3-5 stored mods as a json [`ModManager`]
+fn mods_from_manager(mods_string: Option<String>) -> Vec<ModDefinition> {
+    let mut mods = HashSet::new();
 
-    // if version >= 3 {
-    //     if version <= 5 {
-    //         let mut mods = HashSet::new();
-    //         // old mods
-    //         let mods_string: Option<String> = sr.read()?;
-    //         // parse here, save time later
-    //         if let Some(str) = &mods_string {
-    //             if let Ok(manager) = serde_json::from_str::<ModManager>(str) {
-    //                 if manager.autoplay { mods.insert("autoplay".to_owned()); }
-    //                 if manager.nofail { mods.insert("no_fail".to_owned()); }
-    //                 if manager.hard_rock { mods.insert("hard_rock".to_owned()); }
-    //                 if manager.easy { mods.insert("easy".to_owned()); }
-    //             }
-    //         }
-    //     } else {
-    //         // new mods as of v6
-    //         mods2 = sr.read()?;
-    //     }
-    // }
-
-
-    if !mods2.is_empty() {
-        mods = mods2.into_iter().map(|m| crate::types::ModDefinition {
-            name: m.clone(),
-            short_name: "??".to_string(),
-            display_name: m,
-            adjusts_difficulty: false,
-            score_multiplier: 1.0,
-        }).collect();
+    if let Some(str) = &mods_string
+    && let Ok(manager) = serde_json::from_str::<ModManager>(str) {
+        if manager.autoplay { mods.insert("autoplay".to_owned()); }
+        if manager.nofail { mods.insert("no_fail".to_owned()); }
+        if manager.hard_rock { mods.insert("hard_rock".to_owned()); }
+        if manager.easy { mods.insert("easy".to_owned()); }
     }
 
-    let performance = version!(7, "performance", 0.0);
-    let stat_data = version!(8, "stat_data", HashMap::new());
-
-    let a = Ok(Score {
-        version,
-        username,
-        beatmap_hash,
-        playmode,
-        time,
-
-        score,
-        combo,
-        max_combo,
-        judgments,
-        accuracy: accuracy as f32,
-        speed,
-        performance,
-
-        hit_timings: Vec::new(),
-
-        mods,
-        stat_data,
-        replay: None,
-    });
+    mods_from_ids(mods)
+}
 
-    sr.pop_parent();
+/// mods the [`ModManager`] didnt know about are lost
+fn mods_to_manager(mods: &[ModDefinition]) -> Option<String> {
+    if mods.is_empty() { return None }
+
+    let has = |name: &str| mods.iter().any(|m| m.name == name);
+    let manager = ModManager {
+        speed: None,
+        easy: has("easy"),
+        hard_rock: has("hard_rock"),
+        autoplay: has("autoplay"),
+        nofail: has("no_fail"),
+    };
+    serde_json::to_string(&manager).ok()
+}
+fn mods_lost_in_manager(mods: &[ModDefinition]) -> bool {
+    mods.iter().any(|m| !MANAGER_MODS.contains(&m.name.as_str()))
+}
 
-    a
+/// v6-8 stored mods as a hashset of mod ids
+fn mods_from_ids(mods: HashSet<String>) -> Vec<ModDefinition> {
+    mods.into_iter().map(|m| ModDefinition {
+        name: m.clone(),
+        short_name: "??".to_string(),
+        display_name: m,
+        adjusts_difficulty: false,
+        score_multiplier: 1.0,
+    }).collect()
+}
+fn mods_to_ids(mods: &[ModDefinition]) -> HashSet<String> {
+    mods.iter().map(|m| m.name.clone()).collect()
 }
 
 
@@ -487,8 +349,10 @@ fn read_old_score(
 #[cfg(feature = "test")]
 pub(super) mod tests {
     use crate::tests::*;
-    use crate::prelude::*;
-    use std::collections::HashMap;
+    use crate::types::*;
+    use crate::types::replays::*;
+    use crate::serialization::*;
+    use std::collections::{ HashMap, HashSet };
 
     pub fn make_score<'a>(
         version: u16,
@@ -555,7 +419,7 @@ pub(super) mod tests {
                     *val = mods.contains(m);
                 }
 
-                writer.write_ranged(3..=5,&serde_json::to_string(&mod_manager).unwrap(), "mods_string");
+                writer.write_ranged(3..=5, &Some(serde_json::to_string(&mod_manager).unwrap()), "mods_string");
             }
 
             // mods are stored as hashset
@@ -643,3 +507,120 @@ pub(super) mod tests {
         }
     }
 }
+
+#[test]
+fn test_versioned_fields() {
+    let manager = ModManager { easy: true, ..Default::default() };
+
+    // v3 had manual judgments, an f64 accuracy, speed as an f32, and mods as json
+    let v3 = SimpleWriter::new()
+        .write::<u16>(3)
+        .write::<String>("user".to_owned())
+        .write::<Md5Hash>(Md5Hash::default())
+        .write::<String>("osu".to_owned())
+        .write::<u64>(1000)
+        .write::<u16>(10)
+        .write::<u16>(20)
+        .write::<OldJudgments>((1, 2, 3, 4, 5, 6))
+        .write::<f64>(0.5)
+        .write::<f32>(1.5)
+        .write::<Option<String>>(Some(serde_json::to_string(&manager).unwrap()))
+        .done();
+
+    let mut reader = SerializationReader::new(v3);
+    let score = reader.read::<Score>("score").unwrap();
+    reader.finish().unwrap();
+    assert_eq!(score.version, 3);
+    assert_eq!(score.time, 0);
+    assert_eq!(score.get_judgment("xmiss"), 6);
+    assert_eq!(score.accuracy, 0.5);
+    assert_eq!(score.speed, GameSpeed::from_f32(1.5));
+    assert_eq!(score.mods_string_sorted(), "easy");
+
+    // only the current fields are written
+    let data = SimpleWriter::new().write::<Score>(&score).done();
+    let score = SerializationReader::new(data).read::<Score>("score").unwrap();
+    assert_eq!(score.version, CURRENT_VERSION);
+    assert_eq!(score.get_judgment("xmiss"), 6);
+    assert_eq!(score.speed, GameSpeed::from_f32(1.5));
+    assert_eq!(score.mods_string_sorted(), "easy");
+}
+
+#[test]
+fn test_write_versioned() {
+    use crate::types::replays::{ Replay, ReplayFrame, ReplayAction, KeyPress };
+
+    let mut score = Score::new(Md5Hash::default(), "user".to_owned(), "osu".to_owned());
+    score.time = 100;
+    score.judgments = judgments_from_old((1, 2, 3, 4, 5, 6));
+    score.judgments.insert("custom".to_owned(), 7);
+    score.accuracy = 0.5;
+    score.speed = GameSpeed::from_f32(1.5);
+    score.mods = vec![ModDefinition::new("easy", "EZ", "Easy", true, 0.5)];
+    score.performance = 10.0;
+    score.stat_data.insert("stat".to_owned(), vec![1.0]);
+    score.replay = Some(Replay {
+        frames: vec![ReplayFrame::new(0.0, ReplayAction::Press(KeyPress::Left))],
+        offset: 5.0,
+        ..Default::default()
+    });
+
+    for version in 1..=CURRENT_VERSION {
+        let mut writer = SerializationWriter::new();
+        let lost = score.write_versioned(&mut writer, version);
+
+        let mut reader = SerializationReader::new(writer.data());
+        let read = reader.read::<Score>("score").unwrap_or_else(|e| panic!("error reading score v{version}: {e}"));
+        reader.finish().unwrap();
+
+        assert_eq!(read.version, version);
+        assert_eq!(read.username, score.username);
+        assert_eq!(read.get_judgment("xmiss"), 6);
+        assert_eq!(read.accuracy, score.accuracy);
+        assert_eq!(read.time, if version >= 5 { 100 } else { 0 });
+        assert_eq!(read.speed, if version >= 2 { score.speed } else { GameSpeed::default() });
+        assert_eq!(read.mods_string_sorted(), if version >= 3 { "easy" } else { "" });
+        assert_eq!(read.replay.is_some(), version >= 9);
+        assert_eq!(lost.contains(&"replay"), version < 9);
+        assert_eq!(lost.contains(&"time"), version < 5);
+        // "custom" isnt one of the old judgments
+        assert_eq!(lost.contains(&"judgments"), version < 4);
+        assert_eq!(lost.contains(&"mods"), version < 3);
+    }
+
+    // the mod manager only knew about a few mods
+    let mut with_new_mod = score.clone();
+    with_new_mod.mods.push(ModDefinition::new("flashlight", "FL", "Flashlight", true, 1.1));
+    for version in 1..=CURRENT_VERSION {
+        let lost = with_new_mod.write_versioned(&mut SerializationWriter::new(), version);
+        assert_eq!(lost.contains(&"mods"), version < 6);
+    }
+
+    // zeroed judgments arent worth anything
+    let mut zeroed = score.clone();
+    zeroed.judgments.insert("custom".to_owned(), 0);
+    assert!(!zeroed.write_versioned(&mut SerializationWriter::new(), 3).contains(&"judgments"));
+
+    // old replays had the score inside them
+    for version in 2..=crate::types::replays::CURRENT_VERSION {
+        let mut writer = SerializationWriter::new();
+        let lost = Replay::write_with_score(&score, &mut writer, version);
+        assert_eq!(lost.contains(&"offset"), version < 5);
+
+        let read = Replay::try_read_replay(&mut SerializationReader::new(writer.data())).unwrap();
+        assert_eq!(read.username, score.username);
+        let replay = read.replay.unwrap_or_else(|| panic!("no replay in v{version}"));
+        assert_eq!(replay.frames.len(), 1);
+        assert_eq!(replay.offset, if version >= 5 { 5.0 } else { 0.0 });
+    }
+}
+
+#[test]
+fn test_judgments_from_string() {
+    let score = Score { judgments: judgments_from_old((1, 2, 3, 4, 5, 6)), ..Default::default() };
+    assert_eq!(Score::judgments_from_string(&score.judgment_string()), score.judgments);
+
+    // bad entries are skipped
+    let judgments = Score::judgments_from_string("x300:5|xmiss:lots|x100:-1|:|x50");
+    assert_eq!(judgments, [("x300".to_owned(), 5)].into());
+}
@@ -1,6 +1,7 @@
 use crate::serialization::*;
 use crate::macros::PacketSerialization;
 
+#[cfg_attr(feature="test", derive(crate::macros::RandomValue))]
 #[repr(u8)]
 #[derive(PacketSerialization)]
 #[derive(Copy, Clone, Debug, Default, PartialEq)]